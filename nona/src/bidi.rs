@@ -0,0 +1,285 @@
+use crate::fonts::extends_cluster;
+use std::borrow::Cow;
+use std::ops::Range;
+
+/// Paragraph direction that right-to-left and left-to-right runs of a line are ordered in
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum BaseDirection {
+    /// Taken from the first strong (letter) char of each line, left-to-right if there is none
+    Auto,
+    LeftToRight,
+    RightToLeft,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Class {
+    L,
+    R,
+    Number,
+    Neutral,
+}
+
+fn class(c: char) -> Class {
+    match c {
+        '0'..='9' | '\u{0660}'..='\u{0669}' | '\u{06F0}'..='\u{06F9}' => Class::Number,
+        '\u{0590}'..='\u{08FF}'
+        | '\u{FB1D}'..='\u{FDFF}'
+        | '\u{FE70}'..='\u{FEFF}'
+        | '\u{10800}'..='\u{10FFF}'
+        | '\u{1E800}'..='\u{1EFFF}' => Class::R,
+        c if c.is_alphabetic() => Class::L,
+        _ => Class::Neutral,
+    }
+}
+
+fn mirror(c: char) -> char {
+    match c {
+        '(' => ')',
+        ')' => '(',
+        '[' => ']',
+        ']' => '[',
+        '{' => '}',
+        '}' => '{',
+        '<' => '>',
+        '>' => '<',
+        '«' => '»',
+        '»' => '«',
+        c => c,
+    }
+}
+
+/// Reorders a single line from logical to visual (left to right) order, mirroring brackets in
+/// right-to-left runs. A simplified take on the Unicode bidi algorithm: there are no explicit
+/// embeddings or isolates, numbers keep their digit order inside right-to-left runs, and
+/// neutrals between runs of the same direction join them, otherwise they take the paragraph
+/// direction. Combining marks stay with the char before them.
+pub(crate) fn visual_order(line: &str, base: BaseDirection) -> Cow<'_, str> {
+    let clusters = match visual_clusters(line, base) {
+        Some(clusters) => clusters,
+        None => return Cow::Borrowed(line),
+    };
+    let mut visual = String::with_capacity(line.len());
+    for (range, level) in clusters {
+        push_cluster(&mut visual, &line[range], level);
+    }
+    Cow::Owned(visual)
+}
+
+/// Visual order of `line`, with the byte offset into it of a caret placed before the char at
+/// byte `caret` of `line`, or after the last char for `line.len()`. The caret sits on the
+/// right of right-to-left chars. `None` when the line needs no reordering.
+pub(crate) fn visual_caret(
+    line: &str,
+    base: BaseDirection,
+    caret: usize,
+) -> Option<(String, usize)> {
+    let clusters = visual_clusters(line, base)?;
+    let mut visual = String::with_capacity(line.len());
+    let mut visual_ranges = Vec::with_capacity(clusters.len());
+    for (range, level) in &clusters {
+        let start = visual.len();
+        push_cluster(&mut visual, &line[range.clone()], *level);
+        visual_ranges.push(start..visual.len());
+    }
+
+    let rtl = |level: u8| level % 2 == 1;
+    let before = clusters
+        .iter()
+        .position(|(range, _)| range.contains(&caret))
+        .map(|i| {
+            if rtl(clusters[i].1) {
+                visual_ranges[i].end
+            } else {
+                visual_ranges[i].start
+            }
+        });
+    // past the end the caret follows the last char in logical order
+    let after_last = || {
+        let (i, (_, level)) = clusters
+            .iter()
+            .enumerate()
+            .max_by_key(|(_, (range, _))| range.start)?;
+        Some(if rtl(*level) {
+            visual_ranges[i].start
+        } else {
+            visual_ranges[i].end
+        })
+    };
+    let offset = before.or_else(after_last).unwrap_or(0);
+    Some((visual, offset))
+}
+
+/// Appends a cluster to `visual`, mirroring its bracket at odd levels
+fn push_cluster(visual: &mut String, cluster: &str, level: u8) {
+    let mut chars = cluster.chars();
+    if let Some(c) = chars.next() {
+        visual.push(if level % 2 == 1 { mirror(c) } else { c });
+    }
+    visual.extend(chars);
+}
+
+/// Byte ranges of the clusters of `line` in visual order, with their embedding levels. `None`
+/// when the line is left to right throughout.
+fn visual_clusters(line: &str, base: BaseDirection) -> Option<Vec<(Range<usize>, u8)>> {
+    let mut clusters: Vec<(usize, usize, Class)> = Vec::new();
+    for (idx, c) in line.char_indices() {
+        match clusters.last_mut() {
+            Some(last) if extends_cluster(c) => last.1 = idx + c.len_utf8(),
+            _ => clusters.push((idx, idx + c.len_utf8(), class(c))),
+        }
+    }
+
+    let first_strong = clusters
+        .iter()
+        .map(|cluster| cluster.2)
+        .find(|class| matches!(class, Class::L | Class::R));
+    let rtl = match base {
+        BaseDirection::Auto => first_strong == Some(Class::R),
+        BaseDirection::LeftToRight => false,
+        BaseDirection::RightToLeft => true,
+    };
+    if !rtl && clusters.iter().all(|cluster| cluster.2 != Class::R) {
+        return None;
+    }
+    let base_class = if rtl { Class::R } else { Class::L };
+
+    // numbers after left-to-right text, or at the start of a left-to-right line, are part of it
+    let mut classes: Vec<Class> = Vec::with_capacity(clusters.len());
+    let mut last_strong = base_class;
+    for cluster in &clusters {
+        let class = match cluster.2 {
+            Class::Number if last_strong == Class::L => Class::L,
+            class => class,
+        };
+        if matches!(class, Class::L | Class::R) {
+            last_strong = class;
+        }
+        classes.push(class);
+    }
+
+    // neutrals take the direction around them when both sides agree, numbers counting as
+    // right-to-left, and the paragraph direction otherwise
+    let direction = |class: Class| match class {
+        Class::Number => Class::R,
+        class => class,
+    };
+    let mut i = 0;
+    while i < classes.len() {
+        if classes[i] != Class::Neutral {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        while i < classes.len() && classes[i] == Class::Neutral {
+            i += 1;
+        }
+        let before = match start {
+            0 => base_class,
+            _ => direction(classes[start - 1]),
+        };
+        let after = classes.get(i).copied().map_or(base_class, direction);
+        let resolved = if before == after { before } else { base_class };
+        for class in &mut classes[start..i] {
+            *class = resolved;
+        }
+    }
+
+    let levels: Vec<u8> = classes
+        .iter()
+        .map(|class| match (class, rtl) {
+            (Class::R, _) => 1,
+            (Class::L, false) => 0,
+            _ => 2,
+        })
+        .collect();
+
+    // reverse every run at or above each level, from the highest level down to the lowest odd
+    let mut order: Vec<usize> = (0..clusters.len()).collect();
+    let max_level = levels.iter().copied().max().unwrap_or(0);
+    for level in (1..=max_level).rev() {
+        let mut i = 0;
+        while i < order.len() {
+            if levels[order[i]] < level {
+                i += 1;
+                continue;
+            }
+            let start = i;
+            while i < order.len() && levels[order[i]] >= level {
+                i += 1;
+            }
+            order[start..i].reverse();
+        }
+    }
+
+    Some(
+        order
+            .into_iter()
+            .map(|idx| (clusters[idx].0..clusters[idx].1, levels[idx]))
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn left_to_right_text_is_borrowed_as_is() {
+        assert!(matches!(
+            visual_order("plain text 123", BaseDirection::Auto),
+            Cow::Borrowed("plain text 123")
+        ));
+    }
+
+    #[test]
+    fn right_to_left_runs_are_reversed_in_place() {
+        assert_eq!(
+            visual_order("abc אבג def", BaseDirection::LeftToRight),
+            "abc גבא def"
+        );
+        assert_eq!(
+            visual_order("abc אבג def", BaseDirection::Auto),
+            "abc גבא def"
+        );
+    }
+
+    #[test]
+    fn right_to_left_paragraphs_order_runs_from_the_right() {
+        assert_eq!(visual_order("אבג abc", BaseDirection::Auto), "abc גבא");
+        assert_eq!(visual_order("12 ab", BaseDirection::RightToLeft), "ab 12");
+        // numbers after letters belong to their run
+        assert_eq!(visual_order("ab 12", BaseDirection::RightToLeft), "ab 12");
+    }
+
+    #[test]
+    fn numbers_keep_their_digit_order() {
+        assert_eq!(visual_order("אב 123", BaseDirection::Auto), "123 בא");
+        assert_eq!(
+            visual_order("abc אב 12 גד", BaseDirection::LeftToRight),
+            "abc דג 12 בא"
+        );
+    }
+
+    #[test]
+    fn carets_sit_on_the_trailing_side_of_each_char() {
+        // "ab גד": the caret before ג is right of it, at the end of the visual line
+        let line = "ab גד";
+        let caret = |i: usize| visual_caret(line, BaseDirection::LeftToRight, i).unwrap();
+        assert_eq!(caret(0), ("ab דג".to_string(), 0));
+        assert_eq!(caret(3).1, "ab דג".len());
+        let dalet = line.char_indices().nth(4).unwrap().0;
+        assert_eq!(caret(dalet).1, "ab ד".len());
+        // after the last char, left of it since it is right-to-left
+        assert_eq!(caret(line.len()).1, "ab ".len());
+        assert!(visual_caret("ab", BaseDirection::Auto, 1).is_none());
+    }
+
+    #[test]
+    fn brackets_are_mirrored_and_marks_stay_with_their_base() {
+        assert_eq!(visual_order("(אב)", BaseDirection::Auto), "(בא)");
+        assert_eq!(
+            visual_order("א\u{05B0}ב", BaseDirection::Auto),
+            "בא\u{05B0}"
+        );
+    }
+}
@@ -16,7 +16,7 @@ bitflags! {
 
 #[derive(Debug, Default, Copy, Clone)]
 pub(crate) struct VPoint {
-    xy: Point,
+    pub(crate) xy: Point,
     d: Point,
     len: f32,
     dm: Point,
@@ -31,6 +31,25 @@ pub(crate) struct PathCache {
     pub(crate) bounds: Bounds,
 }
 
+/// How `expand_stroke` outlines paths. The cap and join apply to paths without their own.
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct StrokeStyle {
+    pub(crate) line_cap: LineCap,
+    pub(crate) line_join: LineJoin,
+    pub(crate) miter_limit: f32,
+    /// Whether to spread the stroke gradient along the path, see `mark_along_path`
+    pub(crate) along_path: bool,
+}
+
+/// Copy of the expanded paths of a `PathCache`, with the vertex pointers of each path stored
+/// as offsets so they can be restored into another buffer
+pub(crate) struct Tessellation {
+    paths: Vec<Path>,
+    offsets: Vec<(Option<usize>, Option<usize>)>,
+    vertexes: Vec<Vertex>,
+    bounds: Bounds,
+}
+
 /// Copied from `rawpointer` rust crate https://docs.rs/rawpointer/0.1.0/i686-apple-darwin/src/rawpointer/lib.rs.html#15-22
 /// Return the number of elements of `T` from `start` to `end`.<br>
 /// Return the arithmetic difference if `T` is zero size.
@@ -50,17 +69,67 @@ impl PathCache {
         self.paths.clear();
     }
 
+    pub(crate) fn save_tessellation(&self) -> Tessellation {
+        let base = self.vertexes.as_ptr();
+        let offset = |ptr: *mut Vertex| {
+            if ptr.is_null() {
+                None
+            } else {
+                Some(ptrdistance(base, ptr))
+            }
+        };
+        Tessellation {
+            paths: self.paths.clone(),
+            offsets: self
+                .paths
+                .iter()
+                .map(|path| (offset(path.fill), offset(path.stroke)))
+                .collect(),
+            vertexes: self.vertexes.clone(),
+            bounds: self.bounds,
+        }
+    }
+
+    pub(crate) fn load_tessellation(&mut self, saved: &Tessellation) {
+        self.points.clear();
+        self.vertexes.clear();
+        self.vertexes.extend_from_slice(&saved.vertexes);
+        self.bounds = saved.bounds;
+
+        let base = self.vertexes.as_mut_ptr();
+        let pointer = |offset: Option<usize>| match offset {
+            Some(offset) => unsafe { base.add(offset) },
+            None => std::ptr::null_mut(),
+        };
+        self.paths.clear();
+        self.paths.extend(
+            saved
+                .paths
+                .iter()
+                .zip(&saved.offsets)
+                .map(|(path, offsets)| {
+                    let mut path = *path;
+                    path.fill = pointer(offsets.0);
+                    path.stroke = pointer(offsets.1);
+                    path
+                }),
+        );
+    }
+
     fn add_path(&mut self) -> &mut Path {
         self.paths.push(Path {
             first: self.points.len(),
             count: 0,
             closed: false,
+            reversed: false,
             num_bevel: 0,
             solidity: Solidity::Solid,
             fill: std::ptr::null_mut(),
             num_fill: 0,
             stroke: std::ptr::null_mut(),
             num_stroke: 0,
+            line_cap: None,
+            line_join: None,
             convex: false,
         });
         self.paths.last_mut().unwrap()
@@ -100,6 +169,13 @@ impl PathCache {
         }
     }
 
+    fn path_style(&mut self, line_cap: LineCap, line_join: LineJoin) {
+        if let Some(path) = self.paths.last_mut() {
+            path.line_cap = Some(line_cap);
+            path.line_join = Some(line_join);
+        }
+    }
+
     unsafe fn alloc_temp_vertexes(&mut self, count: usize) -> *mut Vertex {
         self.vertexes.resize(count, Default::default());
         if self.vertexes.is_empty() {
@@ -171,7 +247,8 @@ impl PathCache {
         );
     }
 
-    pub(crate) fn flatten_paths(&mut self, commands: &[Command], dist_tol: f32, tess_tol: f32) {
+    /// Builds the paths and points for `commands`, before any orientation fixups
+    pub(crate) fn add_commands(&mut self, commands: &[Command], dist_tol: f32, tess_tol: f32) {
         for cmd in commands {
             match cmd {
                 Command::MoveTo(pt) => {
@@ -196,7 +273,65 @@ impl PathCache {
                 }
                 Command::Close => self.close_path(),
                 Command::Solidity(solidity) => self.path_solidity(*solidity),
+                Command::SubpathStyle(cap, join) => self.path_style(*cap, *join),
+            }
+        }
+
+        // a subpath started but never drawn to has nothing to fill or stroke
+        self.paths.retain(|path| path.count >= 2);
+    }
+
+    /// Orientation of each path as built, `Solid` for counter-clockwise
+    pub(crate) fn path_windings(&self) -> Vec<Solidity> {
+        self.paths
+            .iter()
+            .map(|path| {
+                let pts = &self.points[path.first..path.first + path.count];
+                if poly_area(pts) < 0.0 {
+                    Solidity::Hole
+                } else {
+                    Solidity::Solid
+                }
+            })
+            .collect()
+    }
+
+    /// Moves every point to the nearest device pixel center. Neighbouring points that land on
+    /// the same center are merged, as zero length segments have no direction to expand along.
+    fn snap_to_pixel_centers(&mut self, ratio: f32, dist_tol: f32) {
+        let snap = |v: f32| ((v * ratio - 0.5).round() + 0.5) / ratio;
+        let mut kept = 0;
+        for path in &mut self.paths {
+            let first = kept;
+            for i in path.first..path.first + path.count {
+                let mut pt = self.points[i];
+                pt.xy = Point::new(snap(pt.xy.x), snap(pt.xy.y));
+                if kept > first && self.points[kept - 1].xy.equals(pt.xy, dist_tol) {
+                    self.points[kept - 1].flags |= pt.flags;
+                    continue;
+                }
+                self.points[kept] = pt;
+                kept += 1;
             }
+            path.first = first;
+            path.count = kept - first;
+        }
+        self.points.truncate(kept);
+    }
+
+    /// Flattens `commands` into paths. `pixel_snap` holds the device pixel ratio when points
+    /// should be moved to the nearest device pixel center.
+    pub(crate) fn flatten_paths(
+        &mut self,
+        commands: &[Command],
+        dist_tol: f32,
+        tess_tol: f32,
+        pixel_snap: Option<f32>,
+    ) {
+        self.add_commands(commands, dist_tol, tess_tol);
+
+        if let Some(ratio) = pixel_snap {
+            self.snap_to_pixel_centers(ratio, dist_tol);
         }
 
         self.bounds.min = Point::new(std::f32::MAX, std::f32::MAX);
@@ -217,13 +352,14 @@ impl PathCache {
                     path.closed = true;
                 }
 
+                path.reversed = false;
                 if path.count > 2 {
                     let area = poly_area(std::slice::from_raw_parts(pts, path.count));
-                    if path.solidity == Solidity::Solid && area < 0.0 {
-                        poly_reverse(std::slice::from_raw_parts_mut(pts, path.count));
-                    }
-                    if path.solidity == Solidity::Hole && area > 0.0 {
+                    if (path.solidity == Solidity::Solid && area < 0.0)
+                        || (path.solidity == Solidity::Hole && area > 0.0)
+                    {
                         poly_reverse(std::slice::from_raw_parts_mut(pts, path.count));
+                        path.reversed = true;
                     }
                 }
 
@@ -244,7 +380,62 @@ impl PathCache {
         }
     }
 
-    fn calculate_joins(&mut self, w: f32, line_join: LineJoin, miter_limit: f32) {
+    /// Moves the points of closed paths by `distance` along their miter directions, away from
+    /// the filled area for positive distances. Open paths have no inside and are left alone.
+    pub(crate) fn offset_closed_paths(&mut self, distance: f32) {
+        for path in &self.paths {
+            if !path.closed || path.count < 3 {
+                continue;
+            }
+
+            let pts = &mut self.points[path.first..path.first + path.count];
+            let count = pts.len();
+            let offsets: Vec<Point> = (0..count)
+                .map(|i| {
+                    let d0 = pts[(i + count - 1) % count].d;
+                    let d1 = pts[i].d;
+                    let mut dm = Point::new((d0.y + d1.y) * 0.5, -(d0.x + d1.x) * 0.5);
+                    let dmr2 = dm.x * dm.x + dm.y * dm.y;
+                    if dmr2 > 0.000001 {
+                        let scale = (1.0 / dmr2).min(600.0);
+                        dm.x *= scale;
+                        dm.y *= scale;
+                    }
+                    // the miter direction points into the filled area
+                    Point::new(-dm.x * distance, -dm.y * distance)
+                })
+                .collect();
+
+            for (pt, offset) in pts.iter_mut().zip(offsets) {
+                pt.xy = pt.xy.offset(offset.x, offset.y);
+            }
+            for i in 0..count {
+                let next = pts[(i + 1) % count].xy;
+                let pt = &mut pts[i];
+                pt.d = Point::new(next.x - pt.xy.x, next.y - pt.xy.y);
+                pt.len = pt.d.normalize();
+            }
+        }
+
+        self.bounds.min = Point::new(f32::MAX, f32::MAX);
+        self.bounds.max = Point::new(f32::MIN, f32::MIN);
+        for path in &self.paths {
+            for pt in &self.points[path.first..path.first + path.count] {
+                self.bounds.min.x = self.bounds.min.x.min(pt.xy.x);
+                self.bounds.min.y = self.bounds.min.y.min(pt.xy.y);
+                self.bounds.max.x = self.bounds.max.x.max(pt.xy.x);
+                self.bounds.max.y = self.bounds.max.y.max(pt.xy.y);
+            }
+        }
+    }
+
+    fn calculate_joins(
+        &mut self,
+        w: f32,
+        line_join: LineJoin,
+        miter_limit: f32,
+        subpath_styles: bool,
+    ) {
         let mut iw = 0.0;
         if w > 0.0 {
             iw = 1.0 / w;
@@ -257,6 +448,11 @@ impl PathCache {
                 let mut p0 = pts.offset(path.count as isize - 1);
                 let mut p1 = pts;
                 let mut nleft = 0;
+                let line_join = if subpath_styles {
+                    path.line_join.unwrap_or(line_join)
+                } else {
+                    line_join
+                };
 
                 path.num_bevel = 0;
 
@@ -320,11 +516,15 @@ impl PathCache {
         &mut self,
         mut w: f32,
         fringe: f32,
-        line_cap: LineCap,
-        line_join: LineJoin,
-        miter_limit: f32,
+        style: StrokeStyle,
         tess_tol: f32,
     ) {
+        let StrokeStyle {
+            line_cap,
+            line_join,
+            miter_limit,
+            along_path,
+        } = style;
         let aa = fringe;
         let mut u0 = 0.0;
         let mut u1 = 1.0;
@@ -337,21 +537,23 @@ impl PathCache {
             u1 = 0.5;
         }
 
-        self.calculate_joins(w, line_join, miter_limit);
+        self.calculate_joins(w, line_join, miter_limit, true);
 
         let mut cverts = 0;
         for path in &self.paths {
             let loop_ = path.closed;
+            let line_cap = path.line_cap.unwrap_or(line_cap);
+            let line_join = path.line_join.unwrap_or(line_join);
             if line_join == LineJoin::Round {
                 cverts += (path.count + path.num_bevel * (ncap + 2) + 1) * 2;
             } else {
                 cverts += (path.count + path.num_bevel * 5 + 1) * 2;
-                if !loop_ {
-                    if line_cap == LineCap::Round {
-                        cverts += (ncap * 2 + 2) * 2;
-                    } else {
-                        cverts += (3 + 3) * 2;
-                    }
+            }
+            if !loop_ {
+                if line_cap == LineCap::Round {
+                    cverts += (ncap * 2 + 2) * 2;
+                } else {
+                    cverts += (3 + 3) * 2;
                 }
             }
         }
@@ -369,6 +571,9 @@ impl PathCache {
                 path.fill = std::ptr::null_mut();
                 path.num_fill = 0;
 
+                let line_cap = path.line_cap.unwrap_or(line_cap);
+                let line_join = path.line_join.unwrap_or(line_join);
+
                 let loop_ = path.closed;
                 let mut dst = vertexes;
                 path.stroke = dst;
@@ -379,6 +584,22 @@ impl PathCache {
                     (pts, pts.add(1), 1, path.count - 1)
                 };
 
+                // arc length of p1 from the start of the path, for gradients along the stroke
+                let mut length = 0.0;
+                let mut total_length = 0.0;
+                for j in 0..path.count {
+                    if loop_ || j + 1 < path.count {
+                        total_length += (*pts.add(j)).len;
+                    }
+                }
+                if loop_ {
+                    length -= (*p0).len;
+                }
+                let total_length = total_length.max(1e-6);
+                // the gradient starts where the path was drawn from, even if flattening reversed it
+                let reversed = path.reversed;
+                let along = |s: f32| if reversed { 1.0 - s } else { s };
+
                 if !loop_ {
                     let mut d = Point::new((*p1).xy.x - (*p0).xy.x, (*p1).xy.y - (*p0).xy.y);
                     d.normalize();
@@ -425,7 +646,13 @@ impl PathCache {
                     }
                 }
 
+                if along_path {
+                    mark_along_path(path.stroke, dst, along(0.0));
+                }
+
                 for _ in s..e {
+                    let join_start = dst;
+                    length += (*p0).len;
                     if (*p1).flags.contains(PointFlags::PT_BEVEL)
                         || (*p1).flags.contains(PointFlags::PR_INNERBEVEL)
                     {
@@ -442,6 +669,11 @@ impl PathCache {
                                 aa,
                             );
                         } else {
+                            let miter_clip = if line_join == LineJoin::MiterClip {
+                                Some(miter_limit)
+                            } else {
+                                None
+                            };
                             dst = bevel_join(
                                 dst,
                                 p0.as_mut().unwrap(),
@@ -451,6 +683,7 @@ impl PathCache {
                                 u0,
                                 u1,
                                 aa,
+                                miter_clip,
                             );
                         }
                     } else {
@@ -470,10 +703,15 @@ impl PathCache {
                         );
                         dst = dst.add(1);
                     }
+                    if along_path {
+                        mark_along_path(join_start, dst, along(length / total_length));
+                    }
                     p0 = p1;
                     p1 = p1.add(1);
                 }
 
+                let end_start = dst;
+
                 if loop_ {
                     let v0 = vertexes;
                     let v1 = vertexes.add(1);
@@ -500,7 +738,7 @@ impl PathCache {
                                 u1,
                             );
                         }
-                        LineCap::Round => {
+                        LineCap::Square => {
                             dst = butt_cap_end(
                                 dst,
                                 p1.as_mut().unwrap(),
@@ -513,7 +751,7 @@ impl PathCache {
                                 u1,
                             );
                         }
-                        LineCap::Square => {
+                        LineCap::Round => {
                             dst = round_cap_end(
                                 dst,
                                 p1.as_mut().unwrap(),
@@ -529,6 +767,10 @@ impl PathCache {
                     }
                 }
 
+                if along_path {
+                    mark_along_path(end_start, dst, along(1.0));
+                }
+
                 path.num_stroke = ptrdistance(vertexes, dst);
                 vertexes = dst;
             }
@@ -545,7 +787,7 @@ impl PathCache {
         let aa = fringe_width;
         let fringe = w > 0.0;
 
-        self.calculate_joins(w, line_join, miter_limit);
+        self.calculate_joins(w, line_join, miter_limit, false);
 
         let mut cverts = 0;
         for path in &self.paths {
@@ -650,6 +892,7 @@ impl PathCache {
                                 lu,
                                 ru,
                                 fringe_width,
+                                None,
                             );
                         } else {
                             *dst = Vertex::new(
@@ -690,6 +933,37 @@ impl PathCache {
             }
         }
     }
+    /// Emits just the path points as a fan, skipping join calculation and fringe geometry.
+    /// Returns false, leaving the cache untouched, unless it holds a single convex path.
+    pub(crate) fn expand_fill_fan(&mut self) -> bool {
+        if self.paths.len() != 1 {
+            return false;
+        }
+
+        let path = &self.paths[0];
+        let pts = &self.points[path.first..path.first + path.count];
+        let convex = (0..pts.len()).all(|i| {
+            let p0 = &pts[(i + pts.len() - 1) % pts.len()];
+            let p1 = &pts[i];
+            p1.d.x * p0.d.y - p0.d.x * p1.d.y > 0.0
+        });
+        if !convex {
+            return false;
+        }
+
+        self.vertexes.clear();
+        self.vertexes
+            .extend(pts.iter().map(|pt| Vertex::new(pt.xy.x, pt.xy.y, 0.5, 1.0)));
+
+        let path = &mut self.paths[0];
+        path.convex = true;
+        path.num_bevel = 0;
+        path.fill = self.vertexes.as_mut_ptr();
+        path.num_fill = path.count;
+        path.stroke = std::ptr::null_mut();
+        path.num_stroke = 0;
+        true
+    }
 }
 
 fn triangle_area(a: &VPoint, b: &VPoint, c: &VPoint) -> f32 {
@@ -725,8 +999,13 @@ fn poly_reverse(pts: &mut [VPoint]) {
 }
 
 fn curve_divs(r: f32, arc: f32, tess_tol: f32) -> usize {
+    const MAX_DIVS: usize = 64;
+
+    // `da` degenerates for extreme radii and tolerances, and divisions shorter than the
+    // tolerance add nothing visible, so the arc length bounds the count
+    let max_divs = ((arc * r / tess_tol).ceil() as usize).clamp(2, MAX_DIVS);
     let da = (r / (r + tess_tol)).acos() * 2.0;
-    ((arc / da).ceil() as i32).max(2) as usize
+    ((arc / da).ceil() as usize).clamp(2, max_divs)
 }
 
 fn choose_bevel(bevel: bool, p0: &mut VPoint, p1: &mut VPoint, w: f32) -> (f32, f32, f32, f32) {
@@ -745,6 +1024,48 @@ fn choose_bevel(bevel: bool, p0: &mut VPoint, p1: &mut VPoint, w: f32) -> (f32,
     }
 }
 
+/// Outer corners of a miter cut off `limit * |w|` from `p1`, on the side of `p1` given by the
+/// sign of `w`. Falls back to the bevel corners when the segments fold back onto each other.
+fn clip_miter(p0: &VPoint, p1: &VPoint, w: f32, limit: f32) -> (f32, f32, f32, f32) {
+    let (dlx0, dly0) = (p0.d.y, -p0.d.x);
+    let (dlx1, dly1) = (p1.d.y, -p1.d.x);
+    let x0 = p1.xy.x + dlx0 * w;
+    let y0 = p1.xy.y + dly0 * w;
+    let x1 = p1.xy.x + dlx1 * w;
+    let y1 = p1.xy.y + dly1 * w;
+
+    let dm_len = (p1.dm.x * p1.dm.x + p1.dm.y * p1.dm.y).sqrt();
+    if dm_len < 0.000001 {
+        return (x0, y0, x1, y1);
+    }
+
+    // unit direction from p1 towards the miter tip
+    let ux = p1.dm.x / dm_len * w.signum();
+    let uy = p1.dm.y / dm_len * w.signum();
+    let dist = limit * w.abs();
+
+    // slide the corners along their segment edges until they reach the clip line
+    let along0 = p0.d.x * ux + p0.d.y * uy;
+    let along1 = -(p1.d.x * ux + p1.d.y * uy);
+    let t0 = if along0 > 0.000001 {
+        ((dist - (dlx0 * w * ux + dly0 * w * uy)) / along0).max(0.0)
+    } else {
+        0.0
+    };
+    let t1 = if along1 > 0.000001 {
+        ((dist - (dlx1 * w * ux + dly1 * w * uy)) / along1).max(0.0)
+    } else {
+        0.0
+    };
+
+    (
+        x0 + p0.d.x * t0,
+        y0 + p0.d.y * t0,
+        x1 - p1.d.x * t1,
+        y1 - p1.d.y * t1,
+    )
+}
+
 unsafe fn round_join(
     mut dst: *mut Vertex,
     p0: &mut VPoint,
@@ -843,6 +1164,7 @@ unsafe fn bevel_join(
     lu: f32,
     ru: f32,
     _fringe: f32,
+    miter_clip: Option<f32>,
 ) -> *mut Vertex {
     let dlx0 = p0.d.y;
     let dly0 = -p0.d.x;
@@ -859,7 +1181,21 @@ unsafe fn bevel_join(
         *dst = Vertex::new(p1.xy.x - dlx0 * rw, p1.xy.y - dly0 * rw, ru, 1.0);
         dst = dst.add(1);
 
-        if p1.flags.contains(PointFlags::PT_BEVEL) {
+        if let (true, Some(limit)) = (p1.flags.contains(PointFlags::PT_BEVEL), miter_clip) {
+            let (rx0, ry0, rx1, ry1) = clip_miter(p0, p1, -rw, limit);
+
+            *dst = Vertex::new(lx0, ly0, lu, 1.0);
+            dst = dst.add(1);
+
+            *dst = Vertex::new(rx0, ry0, ru, 1.0);
+            dst = dst.add(1);
+
+            *dst = Vertex::new(lx1, ly1, lu, 1.0);
+            dst = dst.add(1);
+
+            *dst = Vertex::new(rx1, ry1, ru, 1.0);
+            dst = dst.add(1);
+        } else if p1.flags.contains(PointFlags::PT_BEVEL) {
             *dst = Vertex::new(lx0, ly0, lu, 1.0);
             dst = dst.add(1);
 
@@ -909,7 +1245,21 @@ unsafe fn bevel_join(
         *dst = Vertex::new(rx0, ry0, ru, 1.0);
         dst = dst.add(1);
 
-        if p1.flags.contains(PointFlags::PT_BEVEL) {
+        if let (true, Some(limit)) = (p1.flags.contains(PointFlags::PT_BEVEL), miter_clip) {
+            let (lx0, ly0, lx1, ly1) = clip_miter(p0, p1, lw, limit);
+
+            *dst = Vertex::new(lx0, ly0, lu, 1.0);
+            dst = dst.add(1);
+
+            *dst = Vertex::new(rx0, ry0, ru, 1.0);
+            dst = dst.add(1);
+
+            *dst = Vertex::new(lx1, ly1, lu, 1.0);
+            dst = dst.add(1);
+
+            *dst = Vertex::new(rx1, ry1, ru, 1.0);
+            dst = dst.add(1);
+        } else if p1.flags.contains(PointFlags::PT_BEVEL) {
             *dst = Vertex::new(p1.xy.x + dlx0 * lw, p1.xy.y + dly0 * lw, lu, 1.0);
             dst = dst.add(1);
 
@@ -954,6 +1304,18 @@ unsafe fn bevel_join(
     dst
 }
 
+/// Stores the arc length fraction `s` of stroke body vertices in `from..to` as `v = 1 + s`.
+/// The stroke mask clamps v to 1, so only the fringe vertices at caps (v = 0) affect AA.
+unsafe fn mark_along_path(from: *mut Vertex, to: *mut Vertex, s: f32) {
+    let mut v = from;
+    while v < to {
+        if (*v).v >= 1.0 {
+            (*v).v = 1.0 + s;
+        }
+        v = v.add(1);
+    }
+}
+
 unsafe fn butt_cap_start(
     mut dst: *mut Vertex,
     p: &mut VPoint,
@@ -996,8 +1358,8 @@ unsafe fn butt_cap_end(
     u0: f32,
     u1: f32,
 ) -> *mut Vertex {
-    let px = p.xy.x - dx * d;
-    let py = p.xy.y - dy * d;
+    let px = p.xy.x + dx * d;
+    let py = p.xy.y + dy * d;
     let dlx = dy;
     let dly = -dx;
 
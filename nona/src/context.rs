@@ -1,9 +1,16 @@
-use crate::cache::PathCache;
-use crate::fonts::{FontId, Fonts, LayoutChar};
-use crate::renderer::{Renderer, Scissor, TextureType};
-use crate::{Color, Extent, NonaError, Point, Rect, Transform};
+#[cfg(feature = "bidi")]
+use crate::bidi::{visual_caret, visual_order, BaseDirection};
+use crate::cache::{PathCache, StrokeStyle, Tessellation};
+use crate::fonts::{FontId, Fonts, LayoutChar, TextLayout};
+use crate::renderer::{FillParams, Renderer, Scissor, StrokeParams, TextureType};
+use crate::svg::{arc_to_beziers, PathParser};
+use crate::ProfilerHooks;
+use crate::{Bounds, Color, Extent, NonaError, Point, Rect, Transform};
 use clamped::Clamp;
+use rusttype::Scale;
+use std::collections::HashMap;
 use std::f32::consts::PI;
+use std::rc::Rc;
 
 pub type ImageId = usize;
 
@@ -20,6 +27,92 @@ pub struct Paint {
     pub image: Option<ImageId>,
 }
 
+impl Paint {
+    pub fn builder() -> PaintBuilder {
+        PaintBuilder::new()
+    }
+
+    /// Alpha of the inner color. Solid colors and image patterns use the same alpha for both
+    /// colors. `global_alpha` is applied on top of it at draw time.
+    pub fn alpha(&self) -> f32 {
+        self.inner_color.a
+    }
+
+    /// Sets the alpha of both the inner and the outer color, e.g. to fade an image pattern
+    pub fn set_alpha(&mut self, alpha: f32) {
+        self.inner_color.a = alpha;
+        self.outer_color.a = alpha;
+    }
+}
+
+/// Fluent constructor for `Paint`. Starts as opaque white with an identity transform,
+/// the same as a solid color paint.
+#[derive(Debug, Copy, Clone)]
+pub struct PaintBuilder {
+    paint: Paint,
+}
+
+impl PaintBuilder {
+    pub fn new() -> PaintBuilder {
+        PaintBuilder {
+            paint: Color::rgb(1.0, 1.0, 1.0).into(),
+        }
+    }
+
+    pub fn xform(mut self, xform: Transform) -> PaintBuilder {
+        self.paint.xform = xform;
+        self
+    }
+
+    pub fn extent<E: Into<Extent>>(mut self, extent: E) -> PaintBuilder {
+        self.paint.extent = extent.into();
+        self
+    }
+
+    pub fn radius(mut self, radius: f32) -> PaintBuilder {
+        self.paint.radius = radius;
+        self
+    }
+
+    pub fn feather(mut self, feather: f32) -> PaintBuilder {
+        self.paint.feather = feather;
+        self
+    }
+
+    /// Sets both the inner and outer color
+    pub fn color<C: Into<Color>>(mut self, color: C) -> PaintBuilder {
+        let color = color.into();
+        self.paint.inner_color = color;
+        self.paint.outer_color = color;
+        self
+    }
+
+    pub fn inner_color<C: Into<Color>>(mut self, color: C) -> PaintBuilder {
+        self.paint.inner_color = color.into();
+        self
+    }
+
+    pub fn outer_color<C: Into<Color>>(mut self, color: C) -> PaintBuilder {
+        self.paint.outer_color = color.into();
+        self
+    }
+
+    pub fn image(mut self, img: ImageId) -> PaintBuilder {
+        self.paint.image = Some(img);
+        self
+    }
+
+    pub fn build(self) -> Paint {
+        self.paint
+    }
+}
+
+impl Default for PaintBuilder {
+    fn default() -> Self {
+        PaintBuilder::new()
+    }
+}
+
 #[derive(Debug, Copy, Clone)]
 pub enum Gradient {
     Linear {
@@ -53,6 +146,26 @@ pub struct ImagePattern {
     pub alpha: f32,
 }
 
+impl ImagePattern {
+    /// Paint that multiplies the image colors by `tint`, e.g. to draw an alpha mask in a color.
+    /// Converting the pattern with `Paint::from` is the same as a white tint.
+    pub fn with_tint(self, tint: Color) -> Paint {
+        let mut xform = Transform::rotate(self.angle);
+        xform.0[4] = self.center.x;
+        xform.0[5] = self.center.y;
+        let color = Color::rgba(tint.r, tint.g, tint.b, tint.a * self.alpha);
+        Paint {
+            xform,
+            extent: self.size,
+            radius: 0.0,
+            feather: 0.0,
+            inner_color: color,
+            outer_color: color,
+            image: Some(self.img),
+        }
+    }
+}
+
 impl From<Gradient> for Paint {
     fn from(grad: Gradient) -> Self {
         match grad {
@@ -142,18 +255,7 @@ impl From<Gradient> for Paint {
 
 impl From<ImagePattern> for Paint {
     fn from(pat: ImagePattern) -> Self {
-        let mut xform = Transform::rotate(pat.angle);
-        xform.0[4] = pat.center.x;
-        xform.0[5] = pat.center.y;
-        Paint {
-            xform,
-            extent: pat.size,
-            radius: 0.0,
-            feather: 0.0,
-            inner_color: Color::rgba(1.0, 1.0, 1.0, pat.alpha),
-            outer_color: Color::rgba(1.0, 1.0, 1.0, pat.alpha),
-            image: Some(pat.img),
-        }
+        pat.with_tint(Color::rgb(1.0, 1.0, 1.0))
     }
 }
 
@@ -177,11 +279,27 @@ pub enum Solidity {
     Hole,
 }
 
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum FillRule {
+    NonZero,
+    EvenOdd,
+}
+
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub enum LineJoin {
     Miter,
     Round,
     Bevel,
+    /// Like `Miter`, but joins over the miter limit are cut off at the limit instead of beveled
+    MiterClip,
+}
+
+/// Where strokes of closed paths lie relative to the path. Open paths are always centered.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum StrokeAlign {
+    Center,
+    Inner,
+    Outer,
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
@@ -191,6 +309,14 @@ pub enum LineCap {
     Square,
 }
 
+/// Direction text lines flow in. Vertical text stacks the glyphs of each line top to bottom,
+/// centered on the line, and places following lines (columns) to the left.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum TextDirection {
+    Horizontal,
+    Vertical,
+}
+
 bitflags! {
     pub struct Align: u32 {
         const LEFT = 0x1;
@@ -218,6 +344,18 @@ pub enum BlendFactor {
     SrcAlphaSaturate,
 }
 
+/// How the weighted source and destination are combined. `Min` and `Max` ignore the
+/// blend factors.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum BlendEquation {
+    #[default]
+    Add,
+    Subtract,
+    ReverseSubtract,
+    Min,
+    Max,
+}
+
 #[derive(Debug, Copy, Clone)]
 pub enum BasicCompositeOperation {
     SrcOver,
@@ -285,6 +423,7 @@ impl Into<CompositeOperationState> for CompositeOperation {
                     dst_rgb: dst_factor,
                     src_alpha: src_factor,
                     dst_alpha: dst_factor,
+                    equation: BlendEquation::Add,
                 }
             }
             CompositeOperation::BlendFunc { src, dst } => CompositeOperationState {
@@ -292,6 +431,7 @@ impl Into<CompositeOperationState> for CompositeOperation {
                 dst_rgb: dst,
                 src_alpha: src,
                 dst_alpha: dst,
+                equation: BlendEquation::Add,
             },
             CompositeOperation::BlendFuncSeparate {
                 src_rgb,
@@ -303,6 +443,7 @@ impl Into<CompositeOperationState> for CompositeOperation {
                 dst_rgb,
                 src_alpha,
                 dst_alpha,
+                equation: BlendEquation::Add,
             },
         }
     }
@@ -314,6 +455,7 @@ pub struct CompositeOperationState {
     pub dst_rgb: BlendFactor,
     pub src_alpha: BlendFactor,
     pub dst_alpha: BlendFactor,
+    pub equation: BlendEquation,
 }
 
 bitflags! {
@@ -327,7 +469,7 @@ bitflags! {
     }
 }
 
-#[derive(Debug, Copy, Clone, Default)]
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
 pub struct Vertex {
     pub x: f32,
     pub y: f32,
@@ -346,12 +488,16 @@ pub struct Path {
     pub(crate) first: usize,
     pub(crate) count: usize,
     pub(crate) closed: bool,
+    /// Whether `flatten_paths` reversed the points to match the solidity
+    pub(crate) reversed: bool,
     pub(crate) num_bevel: usize,
     pub(crate) solidity: Solidity,
     pub(crate) fill: *mut Vertex,
     pub(crate) num_fill: usize,
     pub(crate) stroke: *mut Vertex,
     pub(crate) num_stroke: usize,
+    pub(crate) line_cap: Option<LineCap>,
+    pub(crate) line_join: Option<LineJoin>,
     pub convex: bool,
 }
 
@@ -386,6 +532,65 @@ impl TextMetrics {
     }
 }
 
+/// Glyph quads of a text laid out by `Context::prepare_text`
+#[derive(Debug, Clone)]
+pub struct CachedText {
+    vertexes: Vec<Vertex>,
+}
+
+/// Draw calls captured by `Context::record`, for drawing again with `Context::replay` without
+/// rebuilding the paths
+#[derive(Clone, Default)]
+pub struct DisplayList {
+    ops: Vec<DisplayOp>,
+}
+
+impl DisplayList {
+    pub fn len(&self) -> usize {
+        self.ops.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ops.is_empty()
+    }
+}
+
+/// A recorded draw call, with the state and path relative to the transform at recording start
+#[derive(Clone)]
+struct DisplayOp {
+    state: State,
+    commands: Vec<Command>,
+    kind: DisplayOpKind,
+}
+
+/// Fill tessellation kept by `Context::fill_cached` along with what it depends on
+struct CachedPath {
+    xform: Transform,
+    antialias: bool,
+    fast_fill: bool,
+    fringe_width: f32,
+    tess_tol: f32,
+    tessellation: Tessellation,
+}
+
+/// Fill of a path that limits drawing, added by `Context::clip_path`
+struct ClipPath {
+    fill_rule: FillRule,
+    tessellation: Tessellation,
+}
+
+#[derive(Clone)]
+enum DisplayOpKind {
+    Fill(FillRule),
+    Stroke,
+    Text(Point, String),
+    CachedText(CachedText, Point),
+    TextOnPath(Vec<Point>, String),
+    FillRect(Rect, Color),
+    ClearRect(Rect, Color),
+    Triangles(Paint, Vec<Vertex>),
+}
+
 #[derive(Clone)]
 struct State {
     composite_operation: CompositeOperationState,
@@ -396,11 +601,24 @@ struct State {
     miter_limit: f32,
     line_join: LineJoin,
     line_cap: LineCap,
+    stroke_align: StrokeAlign,
+    fill_rule: FillRule,
     alpha: f32,
+    tint: Color,
+    stroke_gradient_along_path: bool,
+    front_face_ccw: bool,
     xform: Transform,
     scissor: Scissor,
+    clips: Vec<Rc<ClipPath>>,
     font_size: f32,
+    font_scale: (f32, f32),
     letter_spacing: f32,
+    column_spacing: f32,
+    text_direction: TextDirection,
+    #[cfg(feature = "bidi")]
+    base_direction: BaseDirection,
+    tab_size: f32,
+    tessellation_tolerance: Option<f32>,
     line_height: f32,
     text_align: Align,
     font_id: FontId,
@@ -417,7 +635,12 @@ impl Default for State {
             miter_limit: 10.0,
             line_join: LineJoin::Miter,
             line_cap: LineCap::Butt,
+            stroke_align: StrokeAlign::Center,
+            fill_rule: FillRule::NonZero,
             alpha: 1.0,
+            tint: Color::rgb(1.0, 1.0, 1.0),
+            stroke_gradient_along_path: false,
+            front_face_ccw: true,
             xform: Transform::identity(),
             scissor: Scissor {
                 xform: Default::default(),
@@ -426,8 +649,16 @@ impl Default for State {
                     height: -1.0,
                 },
             },
+            clips: Vec::new(),
             font_size: 16.0,
+            font_scale: (1.0, 1.0),
             letter_spacing: 0.0,
+            column_spacing: 0.0,
+            text_direction: TextDirection::Horizontal,
+            #[cfg(feature = "bidi")]
+            base_direction: BaseDirection::Auto,
+            tab_size: 0.0,
+            tessellation_tolerance: None,
             line_height: 1.0,
             text_align: Align::LEFT | Align::BASELINE,
             font_id: 0,
@@ -435,13 +666,83 @@ impl Default for State {
     }
 }
 
-#[derive(Debug)]
+impl State {
+    /// Font size in pixels for each axis, at the given rasterization scale
+    fn font_px_size(&self, scale: f32) -> Scale {
+        Scale {
+            x: self.font_size * scale * self.font_scale.0,
+            y: self.font_size * scale * self.font_scale.1,
+        }
+    }
+
+    /// Layout of text drawn at `position`, in device pixels, with the current font settings
+    fn text_layout(&self, scale: f32, position: Point) -> TextLayout {
+        TextLayout {
+            font_id: self.font_id,
+            position,
+            scale: self.font_px_size(scale),
+            align: self.text_align,
+            spacing: self.letter_spacing * scale,
+            tab_size: self.tab_size * scale,
+        }
+    }
+
+    fn stroke_style(&self) -> StrokeStyle {
+        StrokeStyle {
+            line_cap: self.line_cap,
+            line_join: self.line_join,
+            miter_limit: self.miter_limit,
+            along_path: self.stroke_gradient_along_path,
+        }
+    }
+
+    /// Applies global alpha and tint to a paint about to be sent to the renderer
+    fn modulate_paint(&self, paint: &mut Paint) {
+        let tint = self.tint;
+        let modulate = |color: &mut Color| {
+            color.r *= tint.r;
+            color.g *= tint.g;
+            color.b *= tint.b;
+            color.a *= tint.a * self.alpha;
+        };
+        modulate(&mut paint.inner_color);
+        modulate(&mut paint.outer_color);
+    }
+}
+
+/// Segment of a path returned by `Context::export_path`. Quadratic curves and arcs are
+/// exported as the cubic beziers they were converted to.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum PathSegment {
+    MoveTo(Point),
+    LineTo(Point),
+    CubicTo(Point, Point, Point),
+    Close,
+}
+
+#[derive(Debug, Clone)]
 pub(crate) enum Command {
     MoveTo(Point),
     LineTo(Point),
     BezierTo(Point, Point, Point),
     Close,
     Solidity(Solidity),
+    SubpathStyle(LineCap, LineJoin),
+}
+
+impl Command {
+    fn transformed(&self, xform: &Transform) -> Command {
+        match self {
+            Command::MoveTo(pt) => Command::MoveTo(xform.transform_point(*pt)),
+            Command::LineTo(pt) => Command::LineTo(xform.transform_point(*pt)),
+            Command::BezierTo(pt1, pt2, pt3) => Command::BezierTo(
+                xform.transform_point(*pt1),
+                xform.transform_point(*pt2),
+                xform.transform_point(*pt3),
+            ),
+            cmd => cmd.clone(),
+        }
+    }
 }
 
 pub struct Context {
@@ -449,7 +750,6 @@ pub struct Context {
     last_position: Point,
     states: Vec<State>,
     cache: PathCache,
-    tess_tol: f32,
     dist_tol: f32,
     fringe_width: f32,
     device_pixel_ratio: f32,
@@ -459,6 +759,21 @@ pub struct Context {
     fill_triangles_count: usize,
     stroke_triangles_count: usize,
     text_triangles_count: usize,
+    last_stroke_bounds: Bounds,
+    profiler: Option<ProfilerHooks>,
+    text_dpi_scale: f32,
+    premultiplied_target: bool,
+    tessellation_tolerance: f32,
+    device_pixel_ratio_override: Option<f32>,
+    fringe_width_override: Option<f32>,
+    fast_fill: bool,
+    pixel_snap: bool,
+    recording: Option<(Transform, DisplayList)>,
+    cached_paths: HashMap<u64, CachedPath>,
+    cached_path_key: Option<u64>,
+    scissor_stack: Vec<Scissor>,
+    clip_cache: PathCache,
+    applied_clips: Vec<Rc<ClipPath>>,
 }
 
 pub struct Canvas<'a, R: Renderer> {
@@ -480,6 +795,17 @@ impl<R: Renderer> std::ops::DerefMut for Canvas<'_, R> {
 }
 
 impl<'a, R: Renderer> Canvas<'a, R> {
+    /// The renderer this canvas draws with, borrowed for the duration of `attach_renderer`.
+    /// A `Canvas` only exists while a renderer is attached and `Context` never keeps one, so
+    /// there is no detached state to check for.
+    pub fn renderer(&self) -> &R {
+        self.renderer
+    }
+
+    pub fn renderer_mut(&mut self) -> &mut R {
+        self.renderer
+    }
+
     pub fn begin_frame(&mut self, clear_color: Option<Color>) -> Result<(), NonaError> {
         self.context.begin_frame(self.renderer, clear_color)
     }
@@ -488,6 +814,10 @@ impl<'a, R: Renderer> Canvas<'a, R> {
         self.context.end_frame(self.renderer)
     }
 
+    pub fn end_frame_capture(&mut self, rect: Rect) -> Result<Vec<u8>, NonaError> {
+        self.context.end_frame_capture(self.renderer, rect)
+    }
+
     pub fn create_image<D: AsRef<[u8]>>(
         &mut self,
         flags: ImageFlags,
@@ -504,6 +834,14 @@ impl<'a, R: Renderer> Canvas<'a, R> {
         self.context.image_size(self.renderer, img)
     }
 
+    pub fn image_fill(&mut self, img: ImageId, tile: bool, alpha: f32) -> Result<(), NonaError> {
+        self.context.image_fill(self.renderer, img, tile, alpha)
+    }
+
+    pub fn image_filter(&mut self, img: ImageId, nearest: bool) -> Result<(), NonaError> {
+        self.context.image_filter(self.renderer, img, nearest)
+    }
+
     pub fn delete_image(&mut self, img: ImageId) -> Result<(), NonaError> {
         self.context.delete_image(self.renderer, img)
     }
@@ -512,6 +850,35 @@ impl<'a, R: Renderer> Canvas<'a, R> {
         self.context.fill(self.renderer)
     }
 
+    pub fn fill_with_rule(&mut self, rule: FillRule) -> Result<(), NonaError> {
+        self.context.fill_with_rule(self.renderer, rule)
+    }
+
+    pub fn fill_cached(&mut self) -> Result<(), NonaError> {
+        self.context.fill_cached(self.renderer)
+    }
+
+    pub fn fill_glyph_outline(
+        &mut self,
+        font: FontId,
+        c: char,
+        size: f32,
+    ) -> Result<(), NonaError> {
+        self.context
+            .fill_glyph_outline(self.renderer, font, c, size)
+    }
+
+    pub fn draw_checkerboard<T: Into<Rect>>(
+        &mut self,
+        rect: T,
+        cell: f32,
+        c0: Color,
+        c1: Color,
+    ) -> Result<(), NonaError> {
+        self.context
+            .draw_checkerboard(self.renderer, rect, cell, c0, c1)
+    }
+
     pub fn stroke(&mut self) -> Result<(), NonaError> {
         self.context.stroke(self.renderer)
     }
@@ -519,17 +886,114 @@ impl<'a, R: Renderer> Canvas<'a, R> {
     pub fn text<S: AsRef<str>, P: Into<Point>>(&mut self, pt: P, text: S) -> Result<(), NonaError> {
         self.context.text(self.renderer, pt, text)
     }
+
+    pub fn box_shadow<T: Into<Rect>>(
+        &mut self,
+        rect: T,
+        radius: f32,
+        feather: f32,
+        color: Color,
+    ) -> Result<(), NonaError> {
+        self.context
+            .box_shadow(self.renderer, rect, radius, feather, color)
+    }
+
+    pub fn clear_rect<T: Into<Rect>>(&mut self, rect: T, color: Color) -> Result<(), NonaError> {
+        self.context.clear_rect(self.renderer, rect, color)
+    }
+
+    pub fn fill_rect<T: Into<Rect>>(&mut self, rect: T, color: Color) -> Result<(), NonaError> {
+        self.context.fill_rect(self.renderer, rect, color)
+    }
+
+    pub fn draw_triangles(&mut self, paint: Paint, vertices: &[Vertex]) -> Result<(), NonaError> {
+        self.context.draw_triangles(self.renderer, paint, vertices)
+    }
+
+    pub fn prepare_text<S: AsRef<str>>(&mut self, text: S) -> Result<CachedText, NonaError> {
+        self.context.prepare_text(self.renderer, text)
+    }
+
+    pub fn draw_cached_text<P: Into<Point>>(
+        &mut self,
+        cached: &CachedText,
+        pt: P,
+    ) -> Result<(), NonaError> {
+        self.context.draw_cached_text(self.renderer, cached, pt)
+    }
+
+    pub fn text_on_path<S: AsRef<str>>(
+        &mut self,
+        path_points: &[Point],
+        text: S,
+    ) -> Result<(), NonaError> {
+        self.context.text_on_path(self.renderer, path_points, text)
+    }
+
+    pub fn record<F: FnOnce(&mut Self) -> Result<(), NonaError>>(
+        &mut self,
+        f: F,
+    ) -> Result<DisplayList, NonaError> {
+        let previous = self.context.begin_recording();
+        let res = f(self);
+        let list = self.context.end_recording(previous);
+        res.map(|_| list)
+    }
+
+    pub fn reset_all(&mut self) -> Result<(), NonaError> {
+        self.context.reset_all(self.renderer)
+    }
+
+    pub fn replay(&mut self, list: &DisplayList) -> Result<(), NonaError> {
+        self.context.replay(self.renderer, list)
+    }
+}
+
+/// Settings fixed at `Context` creation
+#[derive(Debug, Copy, Clone)]
+pub struct ContextOptions {
+    /// Initial size of the glyph atlas texture, it grows when full
+    pub font_atlas_width: usize,
+    pub font_atlas_height: usize,
+    /// Max distance, in device pixels, of flattened curves from the true curve
+    pub tessellation_tolerance: f32,
+    /// Interpolate gradients and blend in linear light, see `Renderer::set_gamma_correct`
+    pub gamma_correct: bool,
+}
+
+impl Default for ContextOptions {
+    fn default() -> Self {
+        ContextOptions {
+            font_atlas_width: 1024,
+            font_atlas_height: 1024,
+            tessellation_tolerance: 0.25,
+            gamma_correct: false,
+        }
+    }
 }
 
 impl Context {
     pub fn create<R: Renderer>(renderer: &mut R) -> Result<Context, NonaError> {
-        let fonts = Fonts::new(renderer)?;
+        Self::create_with_options(renderer, Default::default())
+    }
+
+    pub fn create_with_options<R: Renderer>(
+        renderer: &mut R,
+        options: ContextOptions,
+    ) -> Result<Context, NonaError> {
+        let fonts = Fonts::new(
+            renderer,
+            options.font_atlas_width,
+            options.font_atlas_height,
+        )?;
+        if options.gamma_correct {
+            renderer.set_gamma_correct(true)?;
+        }
         Ok(Context {
             commands: Default::default(),
             last_position: Default::default(),
             states: vec![Default::default()],
             cache: Default::default(),
-            tess_tol: 0.0,
             dist_tol: 0.0,
             fringe_width: 0.0,
             device_pixel_ratio: 0.0,
@@ -539,21 +1003,43 @@ impl Context {
             fill_triangles_count: 0,
             stroke_triangles_count: 0,
             text_triangles_count: 0,
+            last_stroke_bounds: Default::default(),
+            profiler: None,
+            text_dpi_scale: 1.0,
+            premultiplied_target: false,
+            tessellation_tolerance: options.tessellation_tolerance,
+            device_pixel_ratio_override: None,
+            fringe_width_override: None,
+            fast_fill: false,
+            pixel_snap: false,
+            recording: None,
+            cached_paths: Default::default(),
+            cached_path_key: None,
+            scissor_stack: Vec::new(),
+            clip_cache: Default::default(),
+            applied_clips: Vec::new(),
         })
     }
 
+    /// Flattening tolerance of the current state, converted from device pixels
+    fn tess_tol(&self) -> f32 {
+        let tolerance = self.states.last().unwrap().tessellation_tolerance;
+        tolerance.unwrap_or(self.tessellation_tolerance) / self.device_pixel_ratio
+    }
+
     fn set_device_pixel_ratio(&mut self, ratio: f32) {
-        self.tess_tol = 0.25 / ratio;
         self.dist_tol = 0.01 / ratio;
-        self.fringe_width = 1.0 / ratio;
+        self.fringe_width = self.fringe_width_override.unwrap_or(1.0 / ratio);
         self.device_pixel_ratio = ratio;
     }
 
-    pub fn attach_renderer<R: Renderer>(
+    /// Runs `call` with a `Canvas` drawing through `renderer`, returning what `call` returns.
+    /// The renderer is only borrowed for the duration of the call.
+    pub fn attach_renderer<R: Renderer, T>(
         &mut self,
         renderer: &mut R,
-        call: impl Fn(&mut Canvas<R>),
-    ) {
+        call: impl FnOnce(&mut Canvas<R>) -> T,
+    ) -> T {
         let mut canvas = Canvas {
             context: self,
             renderer,
@@ -567,15 +1053,24 @@ impl Context {
         clear_color: Option<Color>,
     ) -> Result<(), NonaError> {
         let device_pixel_ratio = {
-            renderer.viewport(renderer.view_size().into(), renderer.device_pixel_ratio())?;
-            if let Some(color) = clear_color {
+            let ratio = self
+                .device_pixel_ratio_override
+                .unwrap_or_else(|| renderer.device_pixel_ratio());
+            renderer.viewport(renderer.view_size().into(), ratio)?;
+            if let Some(mut color) = clear_color {
+                if self.premultiplied_target {
+                    color.r *= color.a;
+                    color.g *= color.a;
+                    color.b *= color.a;
+                }
                 renderer.clear_screen(color)
             }
-            renderer.device_pixel_ratio()
+            ratio
         };
         self.set_device_pixel_ratio(device_pixel_ratio);
         self.states.clear();
         self.states.push(Default::default());
+        self.scissor_stack.clear();
         self.draw_call_count = 0;
         self.fill_triangles_count = 0;
         self.stroke_triangles_count = 0;
@@ -583,105 +1078,443 @@ impl Context {
         Ok(())
     }
 
-    pub fn end_frame<R: Renderer>(&mut self, renderer: &mut R) -> Result<(), NonaError> {
-        renderer.flush()
-    }
-
-    pub fn save(&mut self) {
-        if let Some(last) = self.states.last() {
-            let last = last.clone();
-            self.states.push(last);
-        }
+    /// Ends the frame like `end_frame`, then reads back `rect` of the result as RGBA8
+    /// pixels, rows top to bottom. `rect` is scaled by the device pixel ratio.
+    pub fn end_frame_capture<R: Renderer>(
+        &mut self,
+        renderer: &mut R,
+        rect: Rect,
+    ) -> Result<Vec<u8>, NonaError> {
+        self.end_frame(renderer)?;
+        let ratio = self.device_pixel_ratio;
+        let x = (rect.xy.x * ratio).round().max(0.0) as usize;
+        let y = (rect.xy.y * ratio).round().max(0.0) as usize;
+        let width = (rect.size.width * ratio).round().max(0.0) as usize;
+        let height = (rect.size.height * ratio).round().max(0.0) as usize;
+        renderer.read_pixels(x, y, width, height)
     }
 
-    pub fn restore(&mut self) {
-        if self.states.len() <= 1 {
-            return;
-        }
-        self.states.pop();
+    /// Forces the device pixel ratio used from the next `begin_frame` on, instead of the
+    /// renderer's. Tolerances and fringe width derive from it, so a fixed ratio gives the
+    /// same geometry on every machine. `None` goes back to the renderer's ratio.
+    pub fn set_device_pixel_ratio_override(&mut self, ratio: Option<f32>) {
+        self.device_pixel_ratio_override = ratio;
     }
 
-    fn state(&mut self) -> &State {
-        self.states.last().unwrap()
+    /// Overrides the width of the antialiasing fringe, normally one device pixel, until
+    /// `reset_fringe_width` is called
+    pub fn set_fringe_width(&mut self, width: f32) {
+        self.fringe_width_override = Some(width);
+        self.fringe_width = width;
     }
 
-    fn state_mut(&mut self) -> &mut State {
-        self.states.last_mut().unwrap()
+    pub fn reset_fringe_width(&mut self) {
+        self.fringe_width_override = None;
+        if self.device_pixel_ratio > 0.0 {
+            self.fringe_width = 1.0 / self.device_pixel_ratio;
+        }
     }
 
-    pub fn reset(&mut self) {
-        *self.state_mut() = Default::default();
+    pub fn fringe_width(&self) -> f32 {
+        self.fringe_width
     }
 
-    pub fn shape_antialias(&mut self, enabled: bool) {
-        self.state_mut().shape_antialias = enabled;
+    /// When enabled, fills of a single convex path with antialiasing off are drawn as a plain
+    /// triangle fan, skipping join calculation. Meant for large numbers of solid rectangles.
+    pub fn fast_fill(&mut self, enabled: bool) {
+        self.fast_fill = enabled;
     }
 
-    pub fn stroke_width(&mut self, width: f32) {
-        self.state_mut().stroke_width = width;
+    /// When enabled, strokes are moved to the nearest device pixel centers after flattening,
+    /// so thin axis-aligned lines of odd pixel widths cover whole pixels instead of blurring
+    /// over two.
+    pub fn pixel_snap(&mut self, enabled: bool) {
+        self.pixel_snap = enabled;
     }
 
-    pub fn miter_limit(&mut self, limit: f32) {
-        self.state_mut().miter_limit = limit;
+    /// Prepares frames for a target holding premultiplied alpha, for later compositing:
+    /// `begin_frame` premultiplies the clear color. Drawing needs no change, the renderers
+    /// already blend premultiplied colors. Takes effect from the next `begin_frame`.
+    pub fn premultiplied_target(&mut self, enabled: bool) {
+        self.premultiplied_target = enabled;
     }
 
-    pub fn line_cap(&mut self, cap: LineCap) {
-        self.state_mut().line_cap = cap;
+    pub fn end_frame<R: Renderer>(&mut self, renderer: &mut R) -> Result<(), NonaError> {
+        if let Some(profiler) = &mut self.profiler {
+            profiler.on_flush_begin(self.draw_call_count);
+        }
+        renderer.flush()?;
+        // renderers drop their clip on flush
+        self.applied_clips.clear();
+        self.fonts.delete_retired_textures(renderer)?;
+        if let Some(profiler) = &mut self.profiler {
+            profiler.on_flush_end(self.draw_call_count);
+        }
+        Ok(())
     }
 
-    pub fn line_join(&mut self, join: LineJoin) {
-        self.state_mut().line_join = join;
+    pub fn set_profiler(&mut self, hooks: ProfilerHooks) {
+        self.profiler = Some(hooks);
     }
 
-    pub fn global_alpha(&mut self, alpha: f32) {
-        self.state_mut().alpha = alpha;
+    pub fn clear_profiler(&mut self) {
+        self.profiler = None;
     }
 
-    pub fn transform(&mut self, xform: Transform) {
-        let state = self.state_mut();
-        state.xform = xform * state.xform;
+    pub fn save(&mut self) {
+        if let Some(last) = self.states.last() {
+            let last = last.clone();
+            self.states.push(last);
+        }
     }
 
-    pub fn reset_transform(&mut self) {
-        self.state_mut().xform = Transform::identity();
+    pub fn restore(&mut self) {
+        self.try_restore();
     }
 
-    pub fn translate(&mut self, tx: f32, ty: f32) {
-        self.transform(Transform::translate(tx, ty));
+    /// Number of states on the stack, 1 when every `save` has been restored. Handy for
+    /// asserting that code leaves the stack balanced.
+    pub fn save_count(&self) -> usize {
+        self.states.len()
     }
 
-    pub fn rotate(&mut self, angle: f32) {
-        self.transform(Transform::rotate(angle));
+    /// Runs `f` between a `save` and a matching restore. Any states the closure saves but
+    /// doesn't restore are popped as well.
+    pub fn with_state<F: FnOnce(&mut Self)>(&mut self, f: F) {
+        let depth = self.states.len();
+        self.save();
+        f(self);
+        self.states.truncate(depth);
     }
 
-    pub fn skew_x(&mut self, angle: f32) {
-        self.transform(Transform::skew_x(angle));
+    /// Runs `f` with the global alpha multiplied by `alpha`, restoring the state afterwards
+    pub fn with_alpha<F: FnOnce(&mut Self) -> Result<(), NonaError>>(
+        &mut self,
+        alpha: f32,
+        f: F,
+    ) -> Result<(), NonaError> {
+        let depth = self.states.len();
+        self.save();
+        self.state_mut().alpha *= alpha;
+        let res = f(self);
+        self.states.truncate(depth);
+        res
     }
 
-    pub fn skew_y(&mut self, angle: f32) {
-        self.transform(Transform::skew_y(angle));
+    /// Runs `f`, capturing every fill, stroke, text, rect and triangle draw into a
+    /// `DisplayList`. The calls are still drawn as usual. Coordinates are kept relative to the
+    /// current transform.
+    pub fn record<F: FnOnce(&mut Self) -> Result<(), NonaError>>(
+        &mut self,
+        f: F,
+    ) -> Result<DisplayList, NonaError> {
+        let previous = self.begin_recording();
+        let res = f(self);
+        let list = self.end_recording(previous);
+        res.map(|_| list)
     }
 
-    pub fn scale(&mut self, sx: f32, sy: f32) {
-        self.transform(Transform::scale(sx, sy));
+    fn begin_recording(&mut self) -> Option<(Transform, DisplayList)> {
+        let origin = self.state().xform.inverse();
+        self.recording.replace((origin, Default::default()))
     }
 
-    pub fn current_transform(&mut self) -> Transform {
-        self.state().xform
+    fn end_recording(&mut self, previous: Option<(Transform, DisplayList)>) -> DisplayList {
+        let (_, list) = std::mem::replace(&mut self.recording, previous).unwrap_or_default();
+        list
     }
 
-    pub fn stroke_paint<T: Into<Paint>>(&mut self, paint: T) {
-        let mut paint = paint.into();
-        paint.xform *= self.state().xform;
-        self.state_mut().stroke = paint;
+    /// Adds the op `kind` builds to the display list being recorded, if any
+    fn record_op<F: FnOnce() -> DisplayOpKind>(&mut self, kind: F) {
+        if let Some((origin, list)) = &mut self.recording {
+            let kind = kind();
+            let mut state = self.states.last().unwrap().clone();
+            state.xform *= *origin;
+            state.fill.xform *= *origin;
+            state.stroke.xform *= *origin;
+            let commands = match kind {
+                DisplayOpKind::Fill(_) | DisplayOpKind::Stroke => self
+                    .commands
+                    .iter()
+                    .map(|cmd| cmd.transformed(origin))
+                    .collect(),
+                _ => Vec::new(),
+            };
+            list.ops.push(DisplayOp {
+                state,
+                commands,
+                kind,
+            });
+        }
     }
 
-    pub fn fill_paint<T: Into<Paint>>(&mut self, paint: T) {
-        let mut paint = paint.into();
+    /// Draws a recorded `DisplayList` under the current transform, scissor and global alpha.
+    /// The current path is left untouched.
+    pub fn replay<R: Renderer>(
+        &mut self,
+        renderer: &mut R,
+        list: &DisplayList,
+    ) -> Result<(), NonaError> {
+        let commands = std::mem::take(&mut self.commands);
+        let current = self.state().clone();
+        let mut res = Ok(());
+
+        for op in &list.ops {
+            let mut state = op.state.clone();
+            state.xform *= current.xform;
+            state.fill.xform *= current.xform;
+            state.stroke.xform *= current.xform;
+            state.scissor = current.scissor;
+            state.clips = current.clips.clone();
+            state.alpha *= current.alpha;
+            self.states.push(state);
+
+            self.commands = op
+                .commands
+                .iter()
+                .map(|cmd| cmd.transformed(&current.xform))
+                .collect();
+            self.cache.clear();
+            res = match &op.kind {
+                DisplayOpKind::Fill(rule) => self.fill_with_rule(renderer, *rule),
+                DisplayOpKind::Stroke => self.stroke(renderer),
+                DisplayOpKind::Text(pt, text) => self.text(renderer, *pt, text),
+                DisplayOpKind::CachedText(cached, pt) => {
+                    self.draw_cached_text(renderer, cached, *pt)
+                }
+                DisplayOpKind::TextOnPath(points, text) => {
+                    self.text_on_path(renderer, points, text)
+                }
+                DisplayOpKind::FillRect(rect, color) => self.fill_rect(renderer, *rect, *color),
+                DisplayOpKind::ClearRect(rect, color) => self.clear_rect(renderer, *rect, *color),
+                DisplayOpKind::Triangles(paint, vertices) => {
+                    self.draw_triangles(renderer, *paint, vertices)
+                }
+            };
+
+            self.states.pop();
+            if res.is_err() {
+                break;
+            }
+        }
+
+        self.commands = commands;
+        self.cache.clear();
+        res
+    }
+
+    /// Like `restore`, but returns false when there was no saved state to pop
+    pub fn try_restore(&mut self) -> bool {
+        if self.states.len() <= 1 {
+            return false;
+        }
+        self.states.pop();
+        true
+    }
+
+    fn state(&mut self) -> &State {
+        self.states.last().unwrap()
+    }
+
+    fn state_mut(&mut self) -> &mut State {
+        self.states.last_mut().unwrap()
+    }
+
+    pub fn reset(&mut self) {
+        *self.state_mut() = Default::default();
+    }
+
+    /// Returns the context to its state after creation, e.g. to reuse it after a failed
+    /// frame: clears the current path, drops all saved states leaving a single default one,
+    /// drops the scissor stack, the clip applied to `renderer` and the tessellations kept by
+    /// `begin_cached_path`, and zeroes the frame counters. Fonts, images and the settings
+    /// made outside of the state, like `fast_fill` or `set_fringe_width`, are kept.
+    pub fn reset_all<R: Renderer>(&mut self, renderer: &mut R) -> Result<(), NonaError> {
+        self.begin_path();
+        self.last_position = Default::default();
+        self.states.clear();
+        self.states.push(Default::default());
+        self.scissor_stack.clear();
+        self.cached_paths.clear();
+        if !self.applied_clips.is_empty() {
+            renderer.reset_clip()?;
+            self.applied_clips.clear();
+        }
+        self.last_stroke_bounds = Default::default();
+        self.draw_call_count = 0;
+        self.fill_triangles_count = 0;
+        self.stroke_triangles_count = 0;
+        self.text_triangles_count = 0;
+        Ok(())
+    }
+
+    pub fn shape_antialias(&mut self, enabled: bool) {
+        self.state_mut().shape_antialias = enabled;
+    }
+
+    /// Max distance, in device pixels, of flattened curves from the true curve for paths built
+    /// or drawn from now on, overriding `ContextOptions::tessellation_tolerance` until `restore`.
+    /// Larger values give fewer segments, e.g. for a minimap.
+    pub fn tessellation_tolerance(&mut self, tolerance: f32) {
+        self.state_mut().tessellation_tolerance = Some(tolerance.max(f32::EPSILON));
+    }
+
+    pub fn stroke_width(&mut self, width: f32) {
+        self.state_mut().stroke_width = width;
+    }
+
+    pub fn miter_limit(&mut self, limit: f32) {
+        self.state_mut().miter_limit = limit;
+    }
+
+    pub fn line_cap(&mut self, cap: LineCap) {
+        self.state_mut().line_cap = cap;
+    }
+
+    pub fn line_join(&mut self, join: LineJoin) {
+        self.state_mut().line_join = join;
+    }
+
+    /// Places strokes of closed paths inside, centered on, or outside of the path
+    pub fn stroke_align(&mut self, align: StrokeAlign) {
+        self.state_mut().stroke_align = align;
+    }
+
+    /// When enabled, a gradient stroke paint runs from its inner color at the start of each
+    /// path to its outer color at the end, instead of being positioned in space
+    pub fn stroke_gradient_along_path(&mut self, enabled: bool) {
+        self.state_mut().stroke_gradient_along_path = enabled;
+    }
+
+    pub fn fill_rule(&mut self, rule: FillRule) {
+        self.state_mut().fill_rule = rule;
+    }
+
+    pub fn global_alpha(&mut self, alpha: f32) {
+        self.state_mut().alpha = alpha;
+    }
+
+    /// Alpha that fills, strokes, texts and image patterns are currently multiplied by
+    pub fn current_alpha(&self) -> f32 {
+        self.states.last().unwrap().alpha
+    }
+
+    /// Multiplies the colors of every fill, stroke and text paint, on top of `global_alpha`
+    pub fn global_tint(&mut self, color: Color) {
+        self.state_mut().tint = color;
+    }
+
+    /// Applies `xform` in the current user space, like NanoVG's `nvgTransform`: later paths
+    /// go through `xform` first and then through the transform set up so far. Same as
+    /// `pre_transform`.
+    pub fn transform(&mut self, xform: Transform) {
+        self.pre_transform(xform);
+    }
+
+    /// Makes `xform` apply before the current transform, so it acts in user space. This is how
+    /// `translate`, `rotate` and the other helpers compose, e.g. `translate` then `rotate`
+    /// rotates around the translated origin.
+    pub fn pre_transform(&mut self, xform: Transform) {
+        let state = self.state_mut();
+        state.xform = xform * state.xform;
+    }
+
+    /// Makes `xform` apply after the current transform, so it acts in screen space, e.g. to
+    /// pan or zoom a whole scene drawn with its own transforms
+    pub fn post_transform(&mut self, xform: Transform) {
+        self.state_mut().xform *= xform;
+    }
+
+    pub fn reset_transform(&mut self) {
+        self.state_mut().xform = Transform::identity();
+    }
+
+    pub fn translate(&mut self, tx: f32, ty: f32) {
+        self.transform(Transform::translate(tx, ty));
+    }
+
+    pub fn rotate(&mut self, angle: f32) {
+        self.transform(Transform::rotate(angle));
+    }
+
+    pub fn skew_x(&mut self, angle: f32) {
+        self.transform(Transform::skew_x(angle));
+    }
+
+    pub fn skew_y(&mut self, angle: f32) {
+        self.transform(Transform::skew_y(angle));
+    }
+
+    pub fn scale(&mut self, sx: f32, sy: f32) {
+        self.transform(Transform::scale(sx, sy));
+    }
+
+    /// Rotates by `angle` radians around `pivot` instead of the origin
+    pub fn rotate_about<P: Into<Point>>(&mut self, angle: f32, pivot: P) {
+        self.transform_about(Transform::rotate(angle), pivot.into());
+    }
+
+    /// Scales around `pivot` instead of the origin, which stays in place
+    pub fn scale_about<P: Into<Point>>(&mut self, sx: f32, sy: f32, pivot: P) {
+        self.transform_about(Transform::scale(sx, sy), pivot.into());
+    }
+
+    fn transform_about(&mut self, xform: Transform, pivot: Point) {
+        self.transform(
+            Transform::translate(-pivot.x, -pivot.y)
+                * xform
+                * Transform::translate(pivot.x, pivot.y),
+        );
+    }
+
+    pub fn current_transform(&mut self) -> Transform {
+        self.state().xform
+    }
+
+    /// Maps `pt` from user space to screen space using the current transform
+    pub fn transform_point<P: Into<Point>>(&self, pt: P) -> Point {
+        self.states.last().unwrap().xform.transform_point(pt.into())
+    }
+
+    /// Maps `pt` from screen space back to user space, e.g. to hit-test mouse coordinates
+    /// against shapes drawn under a rotation or zoom
+    pub fn inverse_transform_point<P: Into<Point>>(&self, pt: P) -> Point {
+        let xform = self.states.last().unwrap().xform;
+        xform.inverse().transform_point(pt.into())
+    }
+
+    pub fn stroke_paint<T: Into<Paint>>(&mut self, paint: T) {
+        let mut paint = paint.into();
+        paint.xform *= self.state().xform;
+        self.state_mut().stroke = paint;
+    }
+
+    pub fn fill_paint<T: Into<Paint>>(&mut self, paint: T) {
+        let mut paint = paint.into();
         paint.xform *= self.state().xform;
         self.state_mut().fill = paint;
     }
 
+    /// Paint that draws `img` stretched over `size`, with its top left corner at `center` and
+    /// rotated by `angle` around it, like `Paint::from(ImagePattern { .. })`
+    pub fn image_pattern<P: Into<Point>, E: Into<Extent>>(
+        &self,
+        center: P,
+        size: E,
+        angle: f32,
+        img: ImageId,
+        alpha: f32,
+    ) -> Paint {
+        ImagePattern {
+            center: center.into(),
+            size: size.into(),
+            angle,
+            img,
+            alpha,
+        }
+        .into()
+    }
+
     pub fn create_image<D: AsRef<[u8]>, R: Renderer>(
         &mut self,
         renderer: &mut R,
@@ -692,6 +1525,13 @@ impl Context {
             .map_err(|err| NonaError::Texture(err.to_string()))?;
         let img = img.to_rgba8();
         let dimensions = img.dimensions();
+        let max_size = renderer.max_texture_size();
+        if dimensions.0 as usize > max_size || dimensions.1 as usize > max_size {
+            return Err(NonaError::Image(format!(
+                "image {}×{} exceeds max texture size {}",
+                dimensions.0, dimensions.1, max_size
+            )));
+        }
         let img = renderer.create_texture(
             TextureType::RGBA,
             dimensions.0 as usize,
@@ -736,6 +1576,40 @@ impl Context {
         Ok(res)
     }
 
+    /// Sets the fill paint to `img` at its natural size, anchored at the origin. When `tile`
+    /// is true the image repeats in both directions, otherwise its edge pixels are stretched.
+    pub fn image_fill<R: Renderer>(
+        &mut self,
+        renderer: &mut R,
+        img: ImageId,
+        tile: bool,
+        alpha: f32,
+    ) -> Result<(), NonaError> {
+        let (width, height) = renderer.texture_size(img)?;
+        renderer.set_texture_repeat(img, tile, tile)?;
+        self.fill_paint(ImagePattern {
+            center: Point::new(0.0, 0.0),
+            size: Extent::new(width as f32, height as f32),
+            angle: 0.0,
+            img,
+            alpha,
+        });
+        Ok(())
+    }
+
+    /// Switches sampling of `img` between nearest-neighbor, for crisp pixel art, and linear
+    /// filtering. Unlike `ImageFlags::NEAREST`, this can change after the image is created.
+    /// Draws are batched until `end_frame`, so the last setting in a frame applies to all of
+    /// its draws.
+    pub fn image_filter<R: Renderer>(
+        &mut self,
+        renderer: &mut R,
+        img: ImageId,
+        nearest: bool,
+    ) -> Result<(), NonaError> {
+        renderer.set_texture_nearest(img, nearest)
+    }
+
     pub fn delete_image<R: Renderer>(
         &mut self,
         renderer: &mut R,
@@ -762,11 +1636,18 @@ impl Context {
 
     pub fn intersect_scissor<T: Into<Rect>>(&mut self, rect: T) {
         let rect = rect.into();
-        let state = self.state_mut();
+        match self.current_scissor() {
+            Some(current) => self.scissor(current.intersect(rect)),
+            None => self.scissor(rect),
+        }
+    }
 
+    /// The scissor rect in the current user space, or None when scissoring is off. A scissor
+    /// set under a rotation is returned as its axis aligned bounding rect.
+    pub fn current_scissor(&self) -> Option<Rect> {
+        let state = self.states.last().unwrap();
         if state.scissor.extent.width < 0.0 {
-            self.scissor(rect);
-            return;
+            return None;
         }
 
         let Extent {
@@ -777,13 +1658,24 @@ impl Context {
         let pxform = state.scissor.xform * invxorm;
         let tex = ex * pxform.0[0].abs() + ey * pxform.0[2].abs();
         let tey = ex * pxform.0[1].abs() + ey * pxform.0[3].abs();
-        self.scissor(
-            Rect::new(
-                Point::new(pxform.0[4] - tex, pxform.0[5] - tey),
-                Extent::new(tex * 2.0, tey * 2.0),
-            )
-            .intersect(rect),
-        );
+        Some(Rect::new(
+            Point::new(pxform.0[4] - tex, pxform.0[5] - tey),
+            Extent::new(tex * 2.0, tey * 2.0),
+        ))
+    }
+
+    /// Saves only the scissor, to be brought back by `pop_scissor` independently of
+    /// `save`/`restore`
+    pub fn push_scissor(&mut self) {
+        let scissor = self.state().scissor;
+        self.scissor_stack.push(scissor);
+    }
+
+    /// Restores the scissor saved by the last `push_scissor`, does nothing if there is none
+    pub fn pop_scissor(&mut self) {
+        if let Some(scissor) = self.scissor_stack.pop() {
+            self.state_mut().scissor = scissor;
+        }
     }
 
     pub fn reset_scissor(&mut self) {
@@ -793,10 +1685,119 @@ impl Context {
         state.scissor.extent.height = -1.0;
     }
 
+    /// Limits later drawing to the inside of the current path, filled with the current fill
+    /// rule, within any clip already set. Clips belong to the state, so `restore` drops the
+    /// ones added since the matching `save`. Clip edges are not antialiased.
+    pub fn clip_path(&mut self) {
+        self.clip_cache.clear();
+        self.clip_cache
+            .flatten_paths(&self.commands, self.dist_tol, self.tess_tol(), None);
+        self.clip_cache
+            .expand_fill(0.0, LineJoin::Miter, 2.4, self.fringe_width);
+        let clip = ClipPath {
+            fill_rule: self.state().fill_rule,
+            tessellation: self.clip_cache.save_tessellation(),
+        };
+        self.state_mut().clips.push(Rc::new(clip));
+    }
+
+    /// Removes all clip paths of the current state
+    pub fn reset_clip(&mut self) {
+        self.state_mut().clips.clear();
+    }
+
+    /// Brings the renderer's clip in line with the clip paths of the current state
+    fn apply_clip<R: Renderer>(&mut self, renderer: &mut R) -> Result<(), NonaError> {
+        let clips = &self.states.last().unwrap().clips;
+        let applied = self.applied_clips.len();
+        let extends_applied = applied <= clips.len()
+            && self
+                .applied_clips
+                .iter()
+                .zip(clips)
+                .all(|(a, b)| Rc::ptr_eq(a, b));
+        if extends_applied && applied == clips.len() {
+            return Ok(());
+        }
+
+        let clips = clips.clone();
+        let start = if extends_applied {
+            applied
+        } else {
+            renderer.reset_clip()?;
+            0
+        };
+        // the renderer clip is unknown until all clips are applied
+        self.applied_clips.clear();
+        for clip in &clips[start..] {
+            self.clip_cache.load_tessellation(&clip.tessellation);
+            renderer.clip(clip.fill_rule, &self.clip_cache.paths)?;
+        }
+        self.applied_clips = clips;
+        Ok(())
+    }
+
+    /// Resets the transform, the scissor and the clip paths of the current state, for a clean
+    /// slate before drawing overlays
+    pub fn reset_clip_and_transform(&mut self) {
+        self.reset_transform();
+        self.reset_scissor();
+        self.reset_clip();
+    }
+
     pub fn global_composite_operation(&mut self, op: CompositeOperation) {
         self.state_mut().composite_operation = op.into();
     }
 
+    /// Multiplies the colors, darkening. Blending only scales by factors, so this is
+    /// `src * dst + dst * (1 - src alpha)`: exact over opaque destinations, it loses the
+    /// `src * (1 - dst alpha)` term over transparent ones.
+    pub fn blend_multiply(&mut self) {
+        self.global_composite_operation(CompositeOperation::BlendFuncSeparate {
+            src_rgb: BlendFactor::DstColor,
+            dst_rgb: BlendFactor::OneMinusSrcAlpha,
+            src_alpha: BlendFactor::One,
+            dst_alpha: BlendFactor::OneMinusSrcAlpha,
+        });
+    }
+
+    /// Inverse of multiply, lightening: `src + dst * (1 - src)`. Exact for premultiplied
+    /// colors.
+    pub fn blend_screen(&mut self) {
+        self.global_composite_operation(CompositeOperation::BlendFuncSeparate {
+            src_rgb: BlendFactor::One,
+            dst_rgb: BlendFactor::OneMinusSrcColor,
+            src_alpha: BlendFactor::One,
+            dst_alpha: BlendFactor::OneMinusSrcAlpha,
+        });
+    }
+
+    /// Adds the colors, saturating at white, e.g. for glows and particles
+    pub fn blend_additive(&mut self) {
+        self.global_composite_operation(CompositeOperation::BlendFunc {
+            src: BlendFactor::One,
+            dst: BlendFactor::One,
+        });
+    }
+
+    /// Sets the equation combining source and destination, keeping the blend factors
+    pub fn blend_equation(&mut self, equation: BlendEquation) {
+        self.state_mut().composite_operation.equation = equation;
+    }
+
+    /// Keeps the darker of source and destination per channel. Colors are premultiplied,
+    /// so translucent and antialiased source pixels darken more than their color suggests.
+    pub fn blend_darken(&mut self) {
+        self.blend_additive();
+        self.blend_equation(BlendEquation::Min);
+    }
+
+    /// Keeps the lighter of source and destination per channel
+    pub fn blend_lighten(&mut self) {
+        self.blend_additive();
+        self.blend_equation(BlendEquation::Max);
+    }
+
     fn append_command(&mut self, cmd: Command) {
         let state = self.states.last().unwrap();
         let xform = &state.xform;
@@ -825,9 +1826,46 @@ impl Context {
         }
     }
 
+    /// Starts a new path, discarding all accumulated subpaths. Use `add_subpath` to append to
+    /// the current path instead.
     pub fn begin_path(&mut self) {
         self.commands.clear();
         self.cache.clear();
+        self.cached_path_key = None;
+    }
+
+    /// Returns the segments of the current path in user space, i.e. relative to the current
+    /// transform. Winding and line style changes are not part of the result.
+    pub fn export_path(&self) -> Vec<PathSegment> {
+        let xform = self.states.last().unwrap().xform.inverse();
+        self.commands
+            .iter()
+            .filter_map(|cmd| match cmd.transformed(&xform) {
+                Command::MoveTo(pt) => Some(PathSegment::MoveTo(pt)),
+                Command::LineTo(pt) => Some(PathSegment::LineTo(pt)),
+                Command::BezierTo(pt1, pt2, pt3) => Some(PathSegment::CubicTo(pt1, pt2, pt3)),
+                Command::Close => Some(PathSegment::Close),
+                Command::Solidity(_) | Command::SubpathStyle(..) => None,
+            })
+            .collect()
+    }
+
+    /// Starts a new path whose fill tessellation `fill_cached` keeps under `key`. The path still
+    /// has to be built every frame, only flattening and expansion are skipped.
+    pub fn begin_cached_path(&mut self, key: u64) {
+        self.begin_path();
+        self.cached_path_key = Some(key);
+    }
+
+    /// Drops the tessellation kept for `key`, e.g. after the shape built under it changed
+    pub fn invalidate_path(&mut self, key: u64) {
+        self.cached_paths.remove(&key);
+    }
+
+    /// Starts a new subpath at the current position while keeping the existing ones, so
+    /// several helpers can contribute to a single fill or stroke
+    pub fn add_subpath(&mut self) {
+        self.append_command(Command::MoveTo(self.last_position));
     }
 
     pub fn move_to<P: Into<Point>>(&mut self, pt: P) {
@@ -857,6 +1895,103 @@ impl Context {
         ));
     }
 
+    /// Like `move_to`, with `pt` relative to the current position
+    pub fn move_to_rel<P: Into<Point>>(&mut self, pt: P) {
+        let pt = pt.into();
+        self.move_to(self.last_position + pt);
+    }
+
+    /// Like `line_to`, with `pt` relative to the current position
+    pub fn line_to_rel<P: Into<Point>>(&mut self, pt: P) {
+        let pt = pt.into();
+        self.line_to(self.last_position + pt);
+    }
+
+    /// Like `bezier_to`, with all points relative to the current position
+    pub fn bezier_to_rel<P: Into<Point>>(&mut self, cp1: P, cp2: P, pt: P) {
+        let origin = self.last_position;
+        self.bezier_to(origin + cp1.into(), origin + cp2.into(), origin + pt.into());
+    }
+
+    /// Like `quad_to`, with all points relative to the current position
+    pub fn quad_to_rel<P: Into<Point>>(&mut self, cp: P, pt: P) {
+        let origin = self.last_position;
+        self.quad_to(origin + cp.into(), origin + pt.into());
+    }
+
+    /// Appends the path described by SVG path data, as found in the `d` attribute of a
+    /// `<path>` element. Supports the M, L, H, V, C, Q, A and Z commands in both absolute
+    /// and relative forms, including implicitly repeated commands.
+    pub fn path_svg(&mut self, d: &str) -> Result<(), NonaError> {
+        let mut parser = PathParser::new(d);
+        let mut command = None;
+        let mut start = self.last_position;
+
+        while !parser.is_end() {
+            let pos = parser.pos();
+            let cmd = match parser.command().or(command) {
+                Some(cmd) => cmd,
+                None => return Err(parser.error("expected a command", pos)),
+            };
+            let current = self.last_position;
+            let origin = if cmd.is_ascii_lowercase() {
+                current
+            } else {
+                Point::new(0.0, 0.0)
+            };
+            command = Some(cmd);
+
+            match cmd.to_ascii_uppercase() {
+                b'M' => {
+                    let pt = origin + parser.point()?;
+                    self.move_to(pt);
+                    start = pt;
+                    // coordinates following a move are implicit line commands
+                    command = Some(if cmd == b'm' { b'l' } else { b'L' });
+                }
+                b'L' => self.line_to(origin + parser.point()?),
+                b'H' => self.line_to(Point::new(origin.x + parser.number()?, current.y)),
+                b'V' => self.line_to(Point::new(current.x, origin.y + parser.number()?)),
+                b'C' => {
+                    let cp1 = origin + parser.point()?;
+                    let cp2 = origin + parser.point()?;
+                    let pt = origin + parser.point()?;
+                    self.bezier_to(cp1, cp2, pt);
+                }
+                b'Q' => {
+                    let cp = origin + parser.point()?;
+                    let pt = origin + parser.point()?;
+                    self.quad_to(cp, pt);
+                }
+                b'A' => {
+                    let radius = parser.point()?;
+                    let x_axis_rotation = parser.number()?;
+                    let large_arc = parser.flag()?;
+                    let sweep = parser.flag()?;
+                    let pt = origin + parser.point()?;
+                    if radius.x == 0.0 || radius.y == 0.0 {
+                        self.line_to(pt);
+                    } else if !current.equals(pt, 1e-6) {
+                        let segments =
+                            arc_to_beziers(current, radius, x_axis_rotation, large_arc, sweep, pt);
+                        for (cp1, cp2, end) in segments {
+                            self.bezier_to(cp1, cp2, end);
+                        }
+                    }
+                }
+                b'Z' => {
+                    self.close_path();
+                    self.last_position = start;
+                    // close takes no arguments, so it can't repeat
+                    command = None;
+                }
+                _ => return Err(parser.error("unsupported command", pos)),
+            }
+        }
+
+        Ok(())
+    }
+
     pub fn arc_to<P: Into<Point>>(&mut self, pt1: P, pt2: P, radius: f32) {
         let pt0 = self.last_position;
 
@@ -910,10 +2045,24 @@ impl Context {
         self.commands.push(Command::Close);
     }
 
+    /// Detects the winding of each subpath of the current path as drawn, before any
+    /// `path_solidity` fixups. Useful for checking that holes are wound opposite to their
+    /// outer contour.
+    pub fn compute_winding(&self) -> Vec<Solidity> {
+        let mut cache = PathCache::default();
+        cache.add_commands(&self.commands, self.dist_tol, self.tess_tol());
+        cache.path_windings()
+    }
+
     pub fn path_solidity(&mut self, dir: Solidity) {
         self.commands.push(Command::Solidity(dir));
     }
 
+    /// Overrides the state line cap and join for the current subpath when stroking
+    pub fn subpath_style(&mut self, cap: LineCap, join: LineJoin) {
+        self.commands.push(Command::SubpathStyle(cap, join));
+    }
+
     pub fn arc<P: Into<Point>>(&mut self, cp: P, radius: f32, a0: f32, a1: f32, dir: Solidity) {
         let cp = cp.into();
         let move_ = self.commands.is_empty();
@@ -937,7 +2086,8 @@ impl Context {
             }
         }
 
-        let ndivs = ((da.abs() / (PI * 0.5) + 0.5) as i32).min(5).max(1);
+        let scale = self.state().xform.average_scale();
+        let ndivs = arc_divs(radius * scale, da, self.tess_tol());
         let hda = (da / (ndivs as f32)) / 2.0;
         let mut kappa = (4.0 / 3.0 * (1.0 - hda.cos()) / hda.sin()).abs();
 
@@ -1115,45 +2265,338 @@ impl Context {
     }
 
     pub fn fill<R: Renderer>(&mut self, renderer: &mut R) -> Result<(), NonaError> {
-        let state = self.states.last_mut().unwrap();
-        let mut fill_paint = state.fill.clone();
+        let rule = self.state().fill_rule;
+        self.fill_with_rule(renderer, rule)
+    }
 
-        self.cache
-            .flatten_paths(&self.commands, self.dist_tol, self.tess_tol);
-        if renderer.edge_antialias() && state.shape_antialias {
-            self.cache
-                .expand_fill(self.fringe_width, LineJoin::Miter, 2.4, self.fringe_width);
-        } else {
-            self.cache
-                .expand_fill(0.0, LineJoin::Miter, 2.4, self.fringe_width);
+    /// Fills the current path using `rule` for this call only, leaving the state's fill rule unchanged
+    pub fn fill_with_rule<R: Renderer>(
+        &mut self,
+        renderer: &mut R,
+        rule: FillRule,
+    ) -> Result<(), NonaError> {
+        self.record_op(|| DisplayOpKind::Fill(rule));
+        if let Some(profiler) = &mut self.profiler {
+            profiler.on_fill_begin();
         }
 
-        fill_paint.inner_color.a *= state.alpha;
-        fill_paint.outer_color.a *= state.alpha;
-
-        renderer.fill(
-            &fill_paint,
-            state.composite_operation,
-            &state.scissor,
-            self.fringe_width,
-            self.cache.bounds,
-            &self.cache.paths,
-        )?;
+        let antialias = self.fill_antialias(renderer);
+        self.tessellate_fill(antialias);
+        self.submit_fill(renderer, rule)
+    }
 
-        for path in &self.cache.paths {
-            if path.num_fill > 2 {
-                self.fill_triangles_count += path.num_fill - 2;
-            }
-            if path.num_stroke > 2 {
-                self.fill_triangles_count += path.num_stroke - 2;
+    /// Replaces the current path with the outline of the glyph for `c` and fills it with the
+    /// even-odd rule, with the origin on the baseline. Unlike `text` this is not limited by
+    /// the glyph atlas, so it suits very large or transformed letters.
+    pub fn fill_glyph_outline<R: Renderer>(
+        &mut self,
+        renderer: &mut R,
+        font: FontId,
+        c: char,
+        size: f32,
+    ) -> Result<(), NonaError> {
+        self.begin_path();
+        for segment in self.fonts.glyph_outline(font, c, size) {
+            match segment {
+                PathSegment::MoveTo(pt) => self.move_to(pt),
+                PathSegment::LineTo(pt) => self.line_to(pt),
+                PathSegment::CubicTo(cp1, cp2, pt) => self.bezier_to(cp1, cp2, pt),
+                PathSegment::Close => self.close_path(),
+            }
+        }
+        self.fill_with_rule(renderer, FillRule::EvenOdd)
+    }
+
+    /// Like `fill`, but for a path started with `begin_cached_path` reuses the tessellation
+    /// kept under its key while the transform and antialiasing settings stay the same
+    pub fn fill_cached<R: Renderer>(&mut self, renderer: &mut R) -> Result<(), NonaError> {
+        let key = match self.cached_path_key {
+            Some(key) => key,
+            None => return self.fill(renderer),
+        };
+
+        let rule = self.state().fill_rule;
+        self.record_op(|| DisplayOpKind::Fill(rule));
+        if let Some(profiler) = &mut self.profiler {
+            profiler.on_fill_begin();
+        }
+
+        let antialias = self.fill_antialias(renderer);
+        let xform = self.state().xform;
+        let cached = self.cached_paths.get(&key).filter(|cached| {
+            cached.xform.0 == xform.0
+                && cached.antialias == antialias
+                && cached.fast_fill == self.fast_fill
+                && cached.fringe_width == self.fringe_width
+                && cached.tess_tol == self.tess_tol()
+        });
+        if let Some(cached) = cached {
+            self.cache.load_tessellation(&cached.tessellation);
+        } else {
+            self.tessellate_fill(antialias);
+            self.cached_paths.insert(
+                key,
+                CachedPath {
+                    xform,
+                    antialias,
+                    fast_fill: self.fast_fill,
+                    fringe_width: self.fringe_width,
+                    tess_tol: self.tess_tol(),
+                    tessellation: self.cache.save_tessellation(),
+                },
+            );
+        }
+
+        let res = self.submit_fill(renderer, rule);
+        // the loaded paths have no points, so a following stroke has to flatten again
+        self.cache.clear();
+        res
+    }
+
+    fn fill_antialias<R: Renderer>(&mut self, renderer: &R) -> bool {
+        // the fringe is drawn outside of the stencil, without one it would overlap the fill
+        renderer.edge_antialias()
+            && self.state().shape_antialias
+            && renderer.caps().supports_stencil
+    }
+
+    fn tessellate_fill(&mut self, antialias: bool) {
+        self.cache
+            .flatten_paths(&self.commands, self.dist_tol, self.tess_tol(), None);
+        if antialias {
+            self.cache
+                .expand_fill(self.fringe_width, LineJoin::Miter, 2.4, self.fringe_width);
+        } else if !(self.fast_fill && self.cache.expand_fill_fan()) {
+            self.cache
+                .expand_fill(0.0, LineJoin::Miter, 2.4, self.fringe_width);
+        }
+    }
+
+    fn submit_fill<R: Renderer>(
+        &mut self,
+        renderer: &mut R,
+        rule: FillRule,
+    ) -> Result<(), NonaError> {
+        self.apply_clip(renderer)?;
+        let state = self.states.last_mut().unwrap();
+        let mut fill_paint = state.fill.clone();
+        state.modulate_paint(&mut fill_paint);
+
+        renderer.fill(
+            &fill_paint,
+            state.composite_operation,
+            &state.scissor,
+            &FillParams {
+                fringe: self.fringe_width,
+                bounds: self.cache.bounds,
+                fill_rule: rule,
+            },
+            &self.cache.paths,
+        )?;
+
+        for path in &self.cache.paths {
+            if path.num_fill > 2 {
+                self.fill_triangles_count += path.num_fill - 2;
+            }
+            if path.num_stroke > 2 {
+                self.fill_triangles_count += path.num_stroke - 2;
             }
             self.draw_call_count += 2;
         }
 
+        if let Some(profiler) = &mut self.profiler {
+            let vertex_count = self
+                .cache
+                .paths
+                .iter()
+                .map(|path| path.num_fill + path.num_stroke)
+                .sum();
+            profiler.on_fill_end(self.cache.paths.len(), vertex_count);
+        }
+
         Ok(())
     }
 
+    /// Fills `rect` with an alternating pattern of `cell` sized squares, starting with `c0` at
+    /// the top left. Replaces the current path.
+    pub fn draw_checkerboard<T: Into<Rect>, R: Renderer>(
+        &mut self,
+        renderer: &mut R,
+        rect: T,
+        cell: f32,
+        c0: Color,
+        c1: Color,
+    ) -> Result<(), NonaError> {
+        let rect = rect.into();
+        if cell <= 0.0 {
+            return Ok(());
+        }
+
+        self.save();
+        let res = self.fill_checkerboard(renderer, rect, cell, c0, c1);
+        self.restore();
+        res
+    }
+
+    /// Draws a soft shadow of a rounded rect, fading from `color` to transparent over
+    /// `feather` centered on the rect edges. The inside of the rect is left out, so the shadow
+    /// doesn't show through translucent content drawn over it. Replaces the current path.
+    pub fn box_shadow<T: Into<Rect>, R: Renderer>(
+        &mut self,
+        renderer: &mut R,
+        rect: T,
+        radius: f32,
+        feather: f32,
+        color: Color,
+    ) -> Result<(), NonaError> {
+        let rect = rect.into();
+        self.save();
+        self.begin_path();
+        self.rect(rect.grow(feather * 2.0, feather * 2.0));
+        self.rounded_rect(rect, radius);
+        self.path_solidity(Solidity::Hole);
+        self.fill_paint(Gradient::Box {
+            rect,
+            radius,
+            feather,
+            inner_color: color,
+            outer_color: Color::rgba(color.r, color.g, color.b, 0.0),
+        });
+        let res = self.fill(renderer);
+        self.restore();
+        res
+    }
+
+    fn fill_checkerboard<R: Renderer>(
+        &mut self,
+        renderer: &mut R,
+        rect: Rect,
+        cell: f32,
+        c0: Color,
+        c1: Color,
+    ) -> Result<(), NonaError> {
+        self.intersect_scissor(rect);
+
+        self.begin_path();
+        self.rect(rect);
+        self.fill_paint(c0);
+        self.fill(renderer)?;
+
+        let cols = (rect.size.width / cell).ceil() as usize;
+        let rows = (rect.size.height / cell).ceil() as usize;
+        self.begin_path();
+        for row in 0..rows {
+            for col in ((row + 1) % 2..cols).step_by(2) {
+                self.rect(Rect::new(
+                    rect.xy.offset(col as f32 * cell, row as f32 * cell),
+                    Extent::new(cell, cell),
+                ));
+            }
+        }
+        self.fill_paint(c1);
+        self.fill(renderer)
+    }
+
+    /// Returns the outline of what `stroke` would draw for the current path, in user space and
+    /// without antialiasing fringes. Closed subpaths give an outer and an inner contour, so fill
+    /// the result with `FillRule::EvenOdd` to keep their holes.
+    pub fn stroke_to_fill(&mut self) -> Vec<PathSegment> {
+        self.expand_stroke_unfringed();
+
+        // stroke vertices form a strip alternating between the left and right side
+        let xform = self.states.last().unwrap().xform.inverse();
+        let mut segments = Vec::new();
+        for path in &self.cache.paths {
+            let strip = path.get_stroke();
+            if strip.is_empty() {
+                continue;
+            }
+            let side = |skip: usize| {
+                let mut pts: Vec<Point> = strip
+                    .iter()
+                    .skip(skip)
+                    .step_by(2)
+                    .map(|v| xform.transform_point(Point::new(v.x, v.y)))
+                    .collect();
+                pts.dedup();
+                pts
+            };
+            let left = side(0);
+            let mut right = side(1);
+            right.reverse();
+
+            segments.push(PathSegment::MoveTo(left[0]));
+            segments.extend(left[1..].iter().copied().map(PathSegment::LineTo));
+            if path.closed {
+                segments.push(PathSegment::Close);
+                segments.push(PathSegment::MoveTo(right[0]));
+                segments.extend(right[1..].iter().copied().map(PathSegment::LineTo));
+            } else {
+                segments.extend(right.into_iter().map(PathSegment::LineTo));
+            }
+            segments.push(PathSegment::Close);
+        }
+        segments
+    }
+
+    /// Whether `pt`, in user space, lies on what `stroke` would draw for the current path,
+    /// using the current stroke width, alignment, caps and joins
+    pub fn point_in_stroke<P: Into<Point>>(&mut self, pt: P) -> bool {
+        self.expand_stroke_unfringed();
+
+        let pt = self.states.last().unwrap().xform.transform_point(pt.into());
+        self.cache.paths.iter().any(|path| {
+            path.get_stroke().windows(3).any(|tri| {
+                let [a, b, c] = [tri[0], tri[1], tri[2]].map(|v| Point::new(v.x, v.y));
+                let side = |p0: Point, p1: Point| {
+                    Point::cross(p1.offset(-p0.x, -p0.y), pt.offset(-p0.x, -p0.y))
+                };
+                if Point::cross(b - a, c - a) == 0.0 {
+                    // collinear or repeated vertices cover nothing
+                    return false;
+                }
+                let d0 = side(a, b);
+                let d1 = side(b, c);
+                let d2 = side(c, a);
+                let has_neg = d0 < 0.0 || d1 < 0.0 || d2 < 0.0;
+                let has_pos = d0 > 0.0 || d1 > 0.0 || d2 > 0.0;
+                !(has_neg && has_pos)
+            })
+        })
+    }
+
+    /// Tessellates the current path's stroke into the cache without antialiasing fringes
+    fn expand_stroke_unfringed(&mut self) {
+        let state = self.states.last().unwrap();
+        let stroke_width = (state.stroke_width * state.xform.average_scale()).clamped(0.0, 200.0);
+
+        self.cache.clear();
+        let pixel_snap = self.pixel_snap.then_some(self.device_pixel_ratio);
+        self.cache
+            .flatten_paths(&self.commands, self.dist_tol, self.tess_tol(), pixel_snap);
+
+        match state.stroke_align {
+            StrokeAlign::Center => {}
+            StrokeAlign::Inner => self.cache.offset_closed_paths(-stroke_width * 0.5),
+            StrokeAlign::Outer => self.cache.offset_closed_paths(stroke_width * 0.5),
+        }
+
+        let style = StrokeStyle {
+            along_path: false,
+            ..state.stroke_style()
+        };
+        self.cache
+            .expand_stroke(stroke_width * 0.5, 0.0, style, self.tess_tol());
+    }
+
     pub fn stroke<R: Renderer>(&mut self, renderer: &mut R) -> Result<(), NonaError> {
+        self.record_op(|| DisplayOpKind::Stroke);
+        self.apply_clip(renderer)?;
+        if let Some(profiler) = &mut self.profiler {
+            profiler.on_stroke_begin();
+        }
+
+        let tess_tol = self.tess_tol();
         let state = self.states.last_mut().unwrap();
         let scale = state.xform.average_scale();
         let mut stroke_width = (state.stroke_width * scale).clamped(0.0, 200.0);
@@ -1166,38 +2609,45 @@ impl Context {
             stroke_width = self.fringe_width;
         }
 
-        stroke_paint.inner_color.a *= state.alpha;
-        stroke_paint.outer_color.a *= state.alpha;
+        state.modulate_paint(&mut stroke_paint);
 
+        let pixel_snap = self.pixel_snap.then_some(self.device_pixel_ratio);
         self.cache
-            .flatten_paths(&self.commands, self.dist_tol, self.tess_tol);
+            .flatten_paths(&self.commands, self.dist_tol, tess_tol, pixel_snap);
+
+        match state.stroke_align {
+            StrokeAlign::Center => {}
+            StrokeAlign::Inner => self.cache.offset_closed_paths(-stroke_width * 0.5),
+            StrokeAlign::Outer => self.cache.offset_closed_paths(stroke_width * 0.5),
+        }
+
+        let grow = stroke_width * 0.5 + self.fringe_width;
+        self.last_stroke_bounds = Bounds {
+            min: self.cache.bounds.min.offset(-grow, -grow),
+            max: self.cache.bounds.max.offset(grow, grow),
+        };
 
         if renderer.edge_antialias() && state.shape_antialias {
             self.cache.expand_stroke(
                 stroke_width * 0.5,
                 self.fringe_width,
-                state.line_cap,
-                state.line_join,
-                state.miter_limit,
-                self.tess_tol,
+                state.stroke_style(),
+                tess_tol,
             );
         } else {
-            self.cache.expand_stroke(
-                stroke_width * 0.5,
-                0.0,
-                state.line_cap,
-                state.line_join,
-                state.miter_limit,
-                self.tess_tol,
-            );
+            self.cache
+                .expand_stroke(stroke_width * 0.5, 0.0, state.stroke_style(), tess_tol);
         }
 
         renderer.stroke(
             &stroke_paint,
             state.composite_operation,
             &state.scissor,
-            self.fringe_width,
-            stroke_width,
+            &StrokeParams {
+                fringe: self.fringe_width,
+                stroke_width,
+                gradient_along_path: state.stroke_gradient_along_path,
+            },
             &self.cache.paths,
         )?;
 
@@ -1206,9 +2656,19 @@ impl Context {
             self.draw_call_count += 1;
         }
 
+        if let Some(profiler) = &mut self.profiler {
+            let vertex_count = self.cache.paths.iter().map(|path| path.num_stroke).sum();
+            profiler.on_stroke_end(self.cache.paths.len(), vertex_count);
+        }
+
         Ok(())
     }
 
+    /// Bounds of the last stroked path, grown by half the stroke width and the AA fringe
+    pub fn last_stroke_bounds(&self) -> Bounds {
+        self.last_stroke_bounds
+    }
+
     pub fn create_font_from_file<N: Into<String>, P: AsRef<std::path::Path>>(
         &mut self,
         name: N,
@@ -1233,6 +2693,33 @@ impl Context {
         self.fonts.find(name.as_ref())
     }
 
+    /// When enabled, chars missing from the font and all its fallbacks are drawn as the
+    /// font's `.notdef` glyph instead of being skipped, making missing fonts easy to spot.
+    /// Fonts with an empty `.notdef`, like Roboto, only leave its advance as a gap.
+    pub fn set_notdef_glyph(&mut self, enabled: bool) {
+        self.fonts.set_notdef(enabled);
+    }
+
+    /// Drops the text sizes remembered by `text_size`. They are also dropped
+    /// whenever fonts or fallbacks change, so this is only needed to free memory.
+    pub fn clear_text_measure_cache(&mut self) {
+        self.fonts.clear_measure_cache();
+    }
+
+    /// Reports which font, `base` or one of its fallbacks, provides each char of `text`.
+    /// Chars missing from all of them are left out.
+    pub fn text_font_coverage<S: AsRef<str>>(&self, base: FontId, text: S) -> Vec<(char, FontId)> {
+        self.fonts.coverage(base, text.as_ref())
+    }
+
+    pub fn font_name(&self, id: FontId) -> Option<&str> {
+        self.fonts.name(id)
+    }
+
+    pub fn fonts(&self) -> Vec<(FontId, &str)> {
+        self.fonts.fonts()
+    }
+
     pub fn add_fallback_fontid(&mut self, base: FontId, fallback: FontId) {
         self.fonts.add_fallback(base, fallback);
     }
@@ -1247,10 +2734,41 @@ impl Context {
         self.state_mut().font_size = size;
     }
 
+    /// Stretches text horizontally by `sx` and vertically by `sy`, on top of the font size
+    pub fn font_scale_xy(&mut self, sx: f32, sy: f32) {
+        self.state_mut().font_scale = (sx, sy);
+    }
+
     pub fn text_letter_spacing(&mut self, spacing: f32) {
         self.state_mut().letter_spacing = spacing;
     }
 
+    /// Extra gap between the columns of vertical text, on top of the font size
+    pub fn text_column_spacing(&mut self, spacing: f32) {
+        self.state_mut().column_spacing = spacing;
+    }
+
+    /// Switches `text` between horizontal and vertical layout. Letter spacing applies along
+    /// the flow direction in both. Vertical text starts at the top center of its first column
+    /// and ignores the text alignment.
+    pub fn text_direction(&mut self, direction: TextDirection) {
+        self.state_mut().text_direction = direction;
+    }
+
+    /// Paragraph direction horizontal lines are reordered in, placing right-to-left runs like
+    /// Arabic or Hebrew in visual order. `BaseDirection::Auto` picks it per line from the first
+    /// letter.
+    #[cfg(feature = "bidi")]
+    pub fn base_direction(&mut self, direction: BaseDirection) {
+        self.state_mut().base_direction = direction;
+    }
+
+    /// Distance between the tab stops `\t` advances to, counted from the start of each line.
+    /// Zero, the default, uses the width of four spaces.
+    pub fn text_tab_size(&mut self, size: f32) {
+        self.state_mut().tab_size = size;
+    }
+
     pub fn text_line_height(&mut self, line_height: f32) {
         self.state_mut().line_height = line_height;
     }
@@ -1275,81 +2793,3246 @@ impl Context {
         pt: P,
         text: S,
     ) -> Result<(), NonaError> {
+        let pt = pt.into();
+        self.record_op(|| DisplayOpKind::Text(pt, text.as_ref().to_string()));
+        self.layout_text_quads(renderer, pt, text.as_ref())?;
+        self.render_text(renderer)
+    }
+
+    /// Lays out `text` once, for drawing repeatedly with `draw_cached_text` without redoing
+    /// layout. The result is tied to the current font, size and scale; prepare it again when
+    /// those change.
+    pub fn prepare_text<S: AsRef<str>, R: Renderer>(
+        &mut self,
+        renderer: &mut R,
+        text: S,
+    ) -> Result<CachedText, NonaError> {
+        self.layout_text_quads(renderer, Point::new(0.0, 0.0), text.as_ref())?;
+        Ok(CachedText {
+            vertexes: self.cache.vertexes.clone(),
+        })
+    }
+
+    pub fn draw_cached_text<P: Into<Point>, R: Renderer>(
+        &mut self,
+        renderer: &mut R,
+        cached: &CachedText,
+        pt: P,
+    ) -> Result<(), NonaError> {
+        let pt = pt.into();
+        self.record_op(|| DisplayOpKind::CachedText(cached.clone(), pt));
+        self.cache.vertexes.clear();
+        self.cache.vertexes.extend(
+            cached
+                .vertexes
+                .iter()
+                .map(|v| Vertex::new(v.x + pt.x, v.y + pt.y, v.u, v.v)),
+        );
+        self.render_text(renderer)
+    }
+
+    /// Lays out `text` at `pt` into glyph quads in `self.cache.vertexes`
+    fn layout_text_quads<R: Renderer>(
+        &mut self,
+        renderer: &mut R,
+        pt: Point,
+        text: &str,
+    ) -> Result<(), NonaError> {
+        if self.states.last().unwrap().text_direction == TextDirection::Vertical {
+            return self.layout_vertical_text_quads(renderer, pt, text);
+        }
+
+        let line_height = self.line_height_px();
         let state = self.states.last().unwrap();
-        let scale = state.xform.font_scale() * self.device_pixel_ratio;
+        let scale = self.text_scale();
         let invscale = 1.0 / scale;
-        let pt = pt.into();
 
-        self.fonts.layout_text(
-            renderer,
-            text.as_ref(),
-            state.font_id,
-            (pt.x * scale, pt.y * scale).into(),
-            state.font_size * scale,
-            state.text_align,
-            state.letter_spacing * scale,
-            true,
-            &mut self.layout_chars,
-        )?;
+        self.cache.vertexes.clear();
+        self.layout_chars.clear();
+
+        // each line is laid out on its own, so alignment applies per line
+        let mut line_chars = Vec::new();
+        for (i, line) in text.split('\n').enumerate() {
+            let line = line.strip_suffix('\r').unwrap_or(line);
+            #[cfg(feature = "bidi")]
+            let line = &*visual_order(line, state.base_direction);
+            let y = pt.y + i as f32 * line_height;
+
+            self.fonts.layout_glyphs(
+                line,
+                &state.text_layout(scale, Point::new(pt.x * scale, y * scale)),
+                &mut line_chars,
+            );
+            self.layout_chars.append(&mut line_chars);
+        }
+
+        self.fonts.cache_glyphs(renderer, &mut self.layout_chars)?;
+        for lc in &self.layout_chars {
+            let lt = Point::new(lc.bounds.min.x * invscale, lc.bounds.min.y * invscale);
+            let rt = Point::new(lc.bounds.max.x * invscale, lc.bounds.min.y * invscale);
+            let lb = Point::new(lc.bounds.min.x * invscale, lc.bounds.max.y * invscale);
+            let rb = Point::new(lc.bounds.max.x * invscale, lc.bounds.max.y * invscale);
+            push_glyph_quad(&mut self.cache.vertexes, [lt, rt, lb, rb], lc.uv);
+        }
+
+        Ok(())
+    }
+
+    /// Lays out `text` as columns of glyphs, one per line, into `self.cache.vertexes`
+    fn layout_vertical_text_quads<R: Renderer>(
+        &mut self,
+        renderer: &mut R,
+        pt: Point,
+        text: &str,
+    ) -> Result<(), NonaError> {
+        let state = self.states.last().unwrap();
+        let scale = self.text_scale();
+        let invscale = 1.0 / scale;
+        let px_size = state.font_px_size(scale);
+        let metrics = self.fonts.text_metrics(state.font_id, px_size.y);
+        let advance = px_size.y + state.letter_spacing * scale;
+        let column_pitch = px_size.x + state.column_spacing * scale;
 
         self.cache.vertexes.clear();
+        self.layout_chars.clear();
+
+        let mut glyph_chars = Vec::new();
+        for (i, line) in text.split('\n').enumerate() {
+            let line = line.strip_suffix('\r').unwrap_or(line);
+            let x = pt.x * scale - i as f32 * column_pitch;
+            let mut y = pt.y * scale + metrics.ascender;
+
+            for (idx, c) in line.char_indices() {
+                // each glyph is centered on the column on its own
+                let layout = TextLayout {
+                    align: Align::CENTER | Align::BASELINE,
+                    spacing: 0.0,
+                    ..state.text_layout(scale, Point::new(x, y))
+                };
+                self.fonts
+                    .layout_glyphs(&line[idx..idx + c.len_utf8()], &layout, &mut glyph_chars);
+                self.layout_chars.append(&mut glyph_chars);
+                y += advance;
+            }
+        }
 
+        self.fonts.cache_glyphs(renderer, &mut self.layout_chars)?;
         for lc in &self.layout_chars {
             let lt = Point::new(lc.bounds.min.x * invscale, lc.bounds.min.y * invscale);
             let rt = Point::new(lc.bounds.max.x * invscale, lc.bounds.min.y * invscale);
             let lb = Point::new(lc.bounds.min.x * invscale, lc.bounds.max.y * invscale);
             let rb = Point::new(lc.bounds.max.x * invscale, lc.bounds.max.y * invscale);
+            push_glyph_quad(&mut self.cache.vertexes, [lt, rt, lb, rb], lc.uv);
+        }
 
-            self.cache
-                .vertexes
-                .push(Vertex::new(lt.x, lt.y, lc.uv.min.x, lc.uv.min.y));
-            self.cache
-                .vertexes
-                .push(Vertex::new(rb.x, rb.y, lc.uv.max.x, lc.uv.max.y));
-            self.cache
-                .vertexes
-                .push(Vertex::new(rt.x, rt.y, lc.uv.max.x, lc.uv.min.y));
+        Ok(())
+    }
 
-            self.cache
-                .vertexes
-                .push(Vertex::new(lt.x, lt.y, lc.uv.min.x, lc.uv.min.y));
-            self.cache
-                .vertexes
-                .push(Vertex::new(lb.x, lb.y, lc.uv.min.x, lc.uv.max.y));
-            self.cache
-                .vertexes
-                .push(Vertex::new(rb.x, rb.y, lc.uv.max.x, lc.uv.max.y));
+    /// Draws `text` along the polyline `path_points`, placing each glyph by arc length and
+    /// rotating it to the local tangent. Glyphs past the end of the path are not drawn.
+    pub fn text_on_path<S: AsRef<str>, R: Renderer>(
+        &mut self,
+        renderer: &mut R,
+        path_points: &[Point],
+        text: S,
+    ) -> Result<(), NonaError> {
+        self.record_op(|| {
+            DisplayOpKind::TextOnPath(path_points.to_vec(), text.as_ref().to_string())
+        });
+        self.cache.vertexes.clear();
+        if path_points.len() < 2 {
+            return Ok(());
         }
 
-        let mut paint = state.fill.clone();
-        paint.image = Some(self.fonts.img.clone());
-        paint.inner_color.a *= state.alpha;
-        paint.outer_color.a *= state.alpha;
+        let state = self.states.last().unwrap();
+        let scale = self.text_scale();
+        let invscale = 1.0 / scale;
+
+        self.fonts.layout_text(
+            renderer,
+            text.as_ref(),
+            &state.text_layout(scale, Point::new(0.0, 0.0)),
+            true,
+            &mut self.layout_chars,
+        )?;
+
+        let mut lengths = Vec::with_capacity(path_points.len());
+        let mut total = 0.0;
+        lengths.push(total);
+        for pts in path_points.windows(2) {
+            let dx = pts[1].x - pts[0].x;
+            let dy = pts[1].y - pts[0].y;
+            total += (dx * dx + dy * dy).sqrt();
+            lengths.push(total);
+        }
+
+        for lc in &self.layout_chars {
+            // glyphs are positioned by the middle of their advance, and rotated around it
+            let mid = (lc.x + lc.next_x) * 0.5;
+            let (origin, angle) = match point_at_length(path_points, &lengths, mid * invscale) {
+                Some(res) => res,
+                None => continue,
+            };
+            let xform = Transform::rotate(angle) * Transform::translate(origin.x, origin.y);
+            let corner = |x: f32, y: f32| {
+                xform.transform_point(Point::new((x - mid) * invscale, y * invscale))
+            };
+
+            let lt = corner(lc.bounds.min.x, lc.bounds.min.y);
+            let rt = corner(lc.bounds.max.x, lc.bounds.min.y);
+            let lb = corner(lc.bounds.min.x, lc.bounds.max.y);
+            let rb = corner(lc.bounds.max.x, lc.bounds.max.y);
+            push_glyph_quad(&mut self.cache.vertexes, [lt, rt, lb, rb], lc.uv);
+        }
+
+        self.render_text(renderer)
+    }
+
+    /// Sets the winding of front-facing triangles passed to `draw_triangles`, counter-clockwise
+    /// by default. Back-facing triangles may be culled by the renderer.
+    pub fn set_front_face(&mut self, ccw: bool) {
+        self.state_mut().front_face_ccw = ccw;
+    }
+
+    /// Fills `rect` with `color` as two plain triangles, skipping path tessellation and the
+    /// stencil. The current path is left alone. Edges are not antialiased, so this suits
+    /// rects aligned to the pixel grid.
+    pub fn fill_rect<T: Into<Rect>, R: Renderer>(
+        &mut self,
+        renderer: &mut R,
+        rect: T,
+        color: Color,
+    ) -> Result<(), NonaError> {
+        let rect = rect.into();
+        self.record_op(|| DisplayOpKind::FillRect(rect, color));
+        self.draw_rect(renderer, rect, color)
+    }
+
+    /// Draws `rect` for `fill_rect` and `clear_rect`
+    fn draw_rect<R: Renderer>(
+        &mut self,
+        renderer: &mut R,
+        rect: Rect,
+        color: Color,
+    ) -> Result<(), NonaError> {
+        self.apply_clip(renderer)?;
+        let state = self.states.last().unwrap();
+        let mut paint = Paint::from(color);
+        state.modulate_paint(&mut paint);
+
+        // counter-clockwise like the fill of `rect`, flipped when the transform mirrors
+        let Rect { xy, size } = rect;
+        let mut corners = [
+            xy,
+            xy.offset(0.0, size.height),
+            xy.offset(size.width, size.height),
+            xy.offset(size.width, 0.0),
+        ];
+        let t = &state.xform.0;
+        if t[0] * t[3] - t[1] * t[2] < 0.0 {
+            corners.reverse();
+        }
+        let vertex = |i: usize| {
+            let pt = state.xform.transform_point(corners[i]);
+            Vertex::new(pt.x, pt.y, 0.5, 1.0)
+        };
 
+        self.cache.vertexes.clear();
+        self.cache
+            .vertexes
+            .extend([0, 1, 2, 0, 2, 3].iter().map(|i| vertex(*i)));
         renderer.triangles(
             &paint,
             state.composite_operation,
             &state.scissor,
             &self.cache.vertexes,
         )?;
-        Ok(())
-    }
 
-    pub fn text_metrics(&self) -> TextMetrics {
-        let state = self.states.last().unwrap();
-        let scale = state.xform.font_scale() * self.device_pixel_ratio;
-        self.fonts
-            .text_metrics(state.font_id, state.font_size * scale)
+        self.draw_call_count += 1;
+        self.fill_triangles_count += 2;
+        Ok(())
     }
 
-    pub fn text_size<S: AsRef<str>>(&self, text: S) -> Extent {
-        let state = self.states.last().unwrap();
+    /// Overwrites `rect` with `color`, e.g. transparent to erase it, instead of blending over
+    /// what is there. Global alpha and tint don't apply, the current transform and scissor do.
+    /// A scissor set under a rotation limits clearing to its bounding rect.
+    pub fn clear_rect<T: Into<Rect>, R: Renderer>(
+        &mut self,
+        renderer: &mut R,
+        rect: T,
+        color: Color,
+    ) -> Result<(), NonaError> {
+        let rect = rect.into();
+        self.record_op(|| DisplayOpKind::ClearRect(rect, color));
+
+        // the scissor only scales coverage, which would still overwrite pixels outside of it
+        let mut rect = rect;
+        if let Some(scissor) = self.current_scissor() {
+            rect = rect.intersect(scissor);
+        }
+
+        self.save();
+        self.reset_scissor();
+        let state = self.state_mut();
+        state.alpha = 1.0;
+        state.tint = Color::rgb(1.0, 1.0, 1.0);
+        state.composite_operation = CompositeOperation::Basic(BasicCompositeOperation::Copy).into();
+        let res = self.draw_rect(renderer, rect, color);
+        self.restore();
+        res
+    }
+
+    /// Submits custom triangles, three vertices each, drawn with `paint` and the current
+    /// transform, scissor, composite operation, alpha and tint. Vertex `u`/`v` are texture
+    /// coordinates into `paint.image`, modulated by its inner color.
+    pub fn draw_triangles<R: Renderer>(
+        &mut self,
+        renderer: &mut R,
+        paint: Paint,
+        vertices: &[Vertex],
+    ) -> Result<(), NonaError> {
+        self.record_op(|| DisplayOpKind::Triangles(paint, vertices.to_vec()));
+        self.apply_clip(renderer)?;
+        let state = self.states.last().unwrap();
+        let mut paint = paint;
+        paint.xform *= state.xform;
+        state.modulate_paint(&mut paint);
+
+        self.cache.vertexes.clear();
+        self.cache.vertexes.extend(vertices.iter().map(|v| {
+            let pt = state.xform.transform_point(Point::new(v.x, v.y));
+            Vertex::new(pt.x, pt.y, v.u, v.v)
+        }));
+
+        // renderers cull back faces assuming counter-clockwise fronts, so flip clockwise meshes
+        if !state.front_face_ccw {
+            for tri in self.cache.vertexes.chunks_exact_mut(3) {
+                tri.swap(1, 2);
+            }
+        }
+
+        renderer.triangles(
+            &paint,
+            state.composite_operation,
+            &state.scissor,
+            &self.cache.vertexes,
+        )?;
+
+        self.draw_call_count += 1;
+        self.fill_triangles_count += vertices.len() / 3;
+        Ok(())
+    }
+
+    fn render_text<R: Renderer>(&mut self, renderer: &mut R) -> Result<(), NonaError> {
+        self.apply_clip(renderer)?;
+        let state = self.states.last().unwrap();
+        let mut paint = state.fill.clone();
+        paint.image = Some(self.fonts.img.clone());
+        state.modulate_paint(&mut paint);
+
+        renderer.triangles(
+            &paint,
+            state.composite_operation,
+            &state.scissor,
+            &self.cache.vertexes,
+        )?;
+        Ok(())
+    }
+
+    pub fn text_metrics(&self) -> TextMetrics {
+        let state = self.states.last().unwrap();
+        let scale = state.xform.font_scale() * self.device_pixel_ratio;
+        self.fonts
+            .text_metrics(state.font_id, state.font_px_size(scale).y)
+    }
+
+    /// Multiplies the resolution glyphs are rasterized at, independently of the drawing
+    /// transform. Quads are scaled back down, so text keeps its size on screen.
+    pub fn text_dpi_scale(&mut self, scale: f32) {
+        self.text_dpi_scale = scale;
+    }
+
+    /// Ratio between glyph atlas pixels and user space units for the current state
+    fn text_scale(&self) -> f32 {
+        let state = self.states.last().unwrap();
+        state.xform.font_scale() * self.device_pixel_ratio * self.text_dpi_scale
+    }
+
+    /// Distance between baselines of consecutive lines in user space, including the
+    /// `text_line_height` multiplier
+    pub fn line_height_px(&self) -> f32 {
+        let state = self.states.last().unwrap();
+        let scale = self.text_scale();
+        let metrics = self
+            .fonts
+            .text_metrics(state.font_id, state.font_px_size(scale).y);
+        metrics.line_height() * state.line_height / scale
+    }
+
+    /// Tight bounds of the ink of `text` drawn at `pt`, in user space. Whitespace advances
+    /// the pen but has no ink, so trailing spaces don't extend the bounds. Empty or
+    /// ink-less text yields zero size bounds at `pt`.
+    pub fn text_bounds<P: Into<Point>>(&mut self, pt: P, text: &str) -> Bounds {
+        let line_height = self.line_height_px();
+        let state = self.states.last().unwrap();
+        let scale = self.text_scale();
+        let invscale = 1.0 / scale;
+        let pt = pt.into();
+
+        let mut bounds: Option<Bounds> = None;
+        for (i, line) in text.split('\n').enumerate() {
+            let line = line.strip_suffix('\r').unwrap_or(line);
+            #[cfg(feature = "bidi")]
+            let line = &*visual_order(line, state.base_direction);
+            let y = pt.y + i as f32 * line_height;
+
+            self.fonts.layout_glyphs(
+                line,
+                &state.text_layout(scale, Point::new(pt.x * scale, y * scale)),
+                &mut self.layout_chars,
+            );
+
+            for lc in &self.layout_chars {
+                let min = Point::new(lc.bounds.min.x * invscale, lc.bounds.min.y * invscale);
+                let max = Point::new(lc.bounds.max.x * invscale, lc.bounds.max.y * invscale);
+                bounds = Some(match bounds {
+                    Some(b) => Bounds {
+                        min: Point::new(b.min.x.min(min.x), b.min.y.min(min.y)),
+                        max: Point::new(b.max.x.max(max.x), b.max.y.max(max.y)),
+                    },
+                    None => Bounds { min, max },
+                });
+            }
+        }
+
+        bounds.unwrap_or(Bounds { min: pt, max: pt })
+    }
+
+    /// Thin rectangle for a text caret placed before the char at `index` of `text` drawn at
+    /// `pt`, following the current text alignment. Indices past the end place it after the
+    /// last char.
+    pub fn caret_rect<S: AsRef<str>, P: Into<Point>>(&self, pt: P, text: S, index: usize) -> Rect {
+        let state = self.states.last().unwrap();
+        let scale = state.xform.font_scale() * self.device_pixel_ratio;
+        let invscale = 1.0 / scale;
+        let pt = pt.into();
+        let text = text.as_ref();
+        let px_size = state.font_px_size(scale);
+        let spacing = state.letter_spacing * scale;
+        let tab_size = state.tab_size * scale;
+
+        let end = text
+            .char_indices()
+            .nth(index)
+            .map(|(idx, _)| idx)
+            .unwrap_or_else(|| text.len());
+        // measured up to the caret in the order the text is drawn
+        #[cfg(feature = "bidi")]
+        let visual = visual_caret(text, state.base_direction, end);
+        #[cfg(feature = "bidi")]
+        let (text, end) = match &visual {
+            Some((visual, offset)) => (visual.as_str(), *offset),
+            None => (text, end),
+        };
+        let x = self
+            .fonts
+            .text_size(&text[..end], state.font_id, px_size, spacing, tab_size)
+            .width;
+
+        let mut offset_x = 0.0;
+        if state.text_align.contains(Align::CENTER) || state.text_align.contains(Align::RIGHT) {
+            let width = self
+                .fonts
+                .text_size(text, state.font_id, px_size, spacing, tab_size)
+                .width;
+            offset_x = if state.text_align.contains(Align::CENTER) {
+                -width / 2.0
+            } else {
+                -width
+            };
+        }
+
+        // same baseline placement as `Fonts::layout_text`
+        let metrics = self.fonts.text_metrics(state.font_id, px_size.y);
+        let offset_y = if state.text_align.contains(Align::MIDDLE) {
+            metrics.descender + metrics.line_height() / 2.0
+        } else if state.text_align.contains(Align::BOTTOM) {
+            metrics.descender
+        } else if state.text_align.contains(Align::TOP) {
+            metrics.ascender
+        } else {
+            0.0
+        };
+
+        Rect::new(
+            Point::new(
+                pt.x + (x + offset_x) * invscale,
+                pt.y + (offset_y - metrics.ascender) * invscale,
+            ),
+            Extent::new(1.0, metrics.line_height() * state.line_height * invscale),
+        )
+    }
+
+    /// Wraps `text` to `break_width` without drawing it, returning the start byte, end byte
+    /// and width of each line. Whitespace at line breaks is left out of the ranges.
+    pub fn text_break_lines(&self, text: &str, break_width: f32) -> Vec<(usize, usize, f32)> {
+        let state = self.states.last().unwrap();
+        let scale = self.text_scale();
+        let invscale = 1.0 / scale;
+        let mut lines = self.fonts.break_lines(
+            text,
+            state.font_id,
+            state.font_px_size(scale),
+            state.letter_spacing * scale,
+            state.tab_size * scale,
+            break_width * scale,
+        );
+        for line in &mut lines {
+            // reordering changes the kerning pairs, so widths are of the line as drawn
+            #[cfg(feature = "bidi")]
+            if let std::borrow::Cow::Owned(visual) =
+                visual_order(&text[line.0..line.1], state.base_direction)
+            {
+                line.2 = self
+                    .fonts
+                    .text_size(
+                        &visual,
+                        state.font_id,
+                        state.font_px_size(scale),
+                        state.letter_spacing * scale,
+                        state.tab_size * scale,
+                    )
+                    .width;
+            }
+            line.2 *= invscale;
+        }
+        lines
+    }
+
+    pub fn text_size<S: AsRef<str>>(&self, text: S) -> Extent {
+        let state = self.states.last().unwrap();
         let scale = state.xform.font_scale() * self.device_pixel_ratio;
+        #[cfg(feature = "bidi")]
+        let text = visual_order(text.as_ref(), state.base_direction);
         self.fonts.text_size(
             text.as_ref(),
             state.font_id,
-            state.font_size * scale,
+            state.font_px_size(scale),
             state.letter_spacing * scale,
+            state.tab_size * scale,
+        )
+    }
+}
+
+fn push_glyph_quad(vertexes: &mut Vec<Vertex>, corners: [Point; 4], uv: Bounds) {
+    let [lt, rt, lb, rb] = corners;
+    vertexes.push(Vertex::new(lt.x, lt.y, uv.min.x, uv.min.y));
+    vertexes.push(Vertex::new(rb.x, rb.y, uv.max.x, uv.max.y));
+    vertexes.push(Vertex::new(rt.x, rt.y, uv.max.x, uv.min.y));
+
+    vertexes.push(Vertex::new(lt.x, lt.y, uv.min.x, uv.min.y));
+    vertexes.push(Vertex::new(lb.x, lb.y, uv.min.x, uv.max.y));
+    vertexes.push(Vertex::new(rb.x, rb.y, uv.max.x, uv.max.y));
+}
+
+/// Returns the point at arc length `s` along the polyline, and the tangent angle there.
+/// `lengths` holds the cumulative length at each point of `points`.
+fn point_at_length(points: &[Point], lengths: &[f32], s: f32) -> Option<(Point, f32)> {
+    if s < 0.0 || s > *lengths.last()? {
+        return None;
+    }
+    for i in 1..points.len() {
+        if s <= lengths[i] {
+            let (p0, p1) = (points[i - 1], points[i]);
+            let seg = lengths[i] - lengths[i - 1];
+            let t = if seg > 0.0 {
+                (s - lengths[i - 1]) / seg
+            } else {
+                0.0
+            };
+            let pt = Point::new(p0.x + (p1.x - p0.x) * t, p0.y + (p1.y - p0.y) * t);
+            return Some((pt, (p1.y - p0.y).atan2(p1.x - p0.x)));
+        }
+    }
+    None
+}
+
+/// Number of bezier segments needed to approximate an arc of angle `da` so that the distance
+/// to the true circle stays below `tol`.
+fn arc_divs(radius: f32, da: f32, tol: f32) -> i32 {
+    const MAX_DIVS: i32 = 64;
+
+    // error of a cubic bezier approximating an arc of angle `a` is r * 4/27 * sin^6(a/4) / cos^2(a/4)
+    let error = |divs: i32| {
+        let a = da.abs() / divs as f32 / 4.0;
+        radius.abs() * 4.0 / 27.0 * a.sin().powi(6) / (a.cos() * a.cos())
+    };
+
+    let mut ndivs = ((da.abs() / (PI * 0.5) + 0.5) as i32).max(1);
+    if tol > 0.0 {
+        while ndivs < MAX_DIVS && error(ndivs) > tol {
+            ndivs += 1;
+        }
+    }
+    ndivs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SoftwareRenderer;
+
+    fn setup(width: usize, height: usize) -> (SoftwareRenderer, Context) {
+        let mut renderer = SoftwareRenderer::new(width, height);
+        let mut ctx = Context::create(&mut renderer).unwrap();
+        ctx.begin_frame(&mut renderer, Some(Color::rgba(0.0, 0.0, 0.0, 0.0)))
+            .unwrap();
+        (renderer, ctx)
+    }
+
+    /// Draws with a `SoftwareRenderer` while keeping what was submitted
+    struct Recorder {
+        inner: SoftwareRenderer,
+        triangles: Vec<(Paint, Vec<Vertex>)>,
+        /// Fill and stroke calls, with the vertexes of all their paths
+        paths: Vec<(Paint, Vec<Vertex>)>,
+    }
+
+    impl Recorder {
+        fn new(width: usize, height: usize) -> Recorder {
+            Recorder {
+                inner: SoftwareRenderer::new(width, height),
+                triangles: Vec::new(),
+                paths: Vec::new(),
+            }
+        }
+    }
+
+    impl Renderer for Recorder {
+        fn edge_antialias(&self) -> bool {
+            self.inner.edge_antialias()
+        }
+
+        fn caps(&self) -> crate::renderer::RendererCaps {
+            self.inner.caps()
+        }
+
+        fn view_size(&self) -> (f32, f32) {
+            self.inner.view_size()
+        }
+
+        fn device_pixel_ratio(&self) -> f32 {
+            self.inner.device_pixel_ratio()
+        }
+
+        fn max_texture_size(&self) -> usize {
+            self.inner.max_texture_size()
+        }
+
+        fn create_texture(
+            &mut self,
+            texture_type: TextureType,
+            width: usize,
+            height: usize,
+            flags: ImageFlags,
+            data: Option<&[u8]>,
+        ) -> Result<ImageId, NonaError> {
+            self.inner
+                .create_texture(texture_type, width, height, flags, data)
+        }
+
+        fn delete_texture(&mut self, img: ImageId) -> Result<(), NonaError> {
+            self.inner.delete_texture(img)
+        }
+
+        fn update_texture(
+            &mut self,
+            img: ImageId,
+            x: usize,
+            y: usize,
+            width: usize,
+            height: usize,
+            data: &[u8],
+        ) -> Result<(), NonaError> {
+            self.inner.update_texture(img, x, y, width, height, data)
+        }
+
+        fn texture_size(&self, img: ImageId) -> Result<(usize, usize), NonaError> {
+            self.inner.texture_size(img)
+        }
+
+        fn set_texture_repeat(
+            &mut self,
+            img: ImageId,
+            repeat_x: bool,
+            repeat_y: bool,
+        ) -> Result<(), NonaError> {
+            self.inner.set_texture_repeat(img, repeat_x, repeat_y)
+        }
+
+        fn set_texture_nearest(&mut self, img: ImageId, nearest: bool) -> Result<(), NonaError> {
+            self.inner.set_texture_nearest(img, nearest)
+        }
+
+        fn viewport(&mut self, extent: Extent, device_pixel_ratio: f32) -> Result<(), NonaError> {
+            self.inner.viewport(extent, device_pixel_ratio)
+        }
+
+        fn set_gamma_correct(&mut self, enabled: bool) -> Result<(), NonaError> {
+            self.inner.set_gamma_correct(enabled)
+        }
+
+        fn clear_screen(&mut self, color: Color) {
+            self.inner.clear_screen(color)
+        }
+
+        fn flush(&mut self) -> Result<(), NonaError> {
+            self.inner.flush()
+        }
+
+        fn read_pixels(
+            &mut self,
+            x: usize,
+            y: usize,
+            width: usize,
+            height: usize,
+        ) -> Result<Vec<u8>, NonaError> {
+            self.inner.read_pixels(x, y, width, height)
+        }
+
+        fn fill(
+            &mut self,
+            paint: &Paint,
+            composite_operation: CompositeOperationState,
+            scissor: &Scissor,
+            params: &FillParams,
+            paths: &[Path],
+        ) -> Result<(), NonaError> {
+            let vertexes = paths.iter().flat_map(|p| p.get_fill().to_vec());
+            self.paths.push((*paint, vertexes.collect()));
+            self.inner
+                .fill(paint, composite_operation, scissor, params, paths)
+        }
+
+        fn stroke(
+            &mut self,
+            paint: &Paint,
+            composite_operation: CompositeOperationState,
+            scissor: &Scissor,
+            params: &StrokeParams,
+            paths: &[Path],
+        ) -> Result<(), NonaError> {
+            let vertexes = paths.iter().flat_map(|p| p.get_stroke().to_vec());
+            self.paths.push((*paint, vertexes.collect()));
+            self.inner
+                .stroke(paint, composite_operation, scissor, params, paths)
+        }
+
+        fn triangles(
+            &mut self,
+            paint: &Paint,
+            composite_operation: CompositeOperationState,
+            scissor: &Scissor,
+            vertexes: &[Vertex],
+        ) -> Result<(), NonaError> {
+            self.triangles.push((*paint, vertexes.to_vec()));
+            self.inner
+                .triangles(paint, composite_operation, scissor, vertexes)
+        }
+
+        fn clip(&mut self, fill_rule: FillRule, paths: &[Path]) -> Result<(), NonaError> {
+            self.inner.clip(fill_rule, paths)
+        }
+
+        fn reset_clip(&mut self) -> Result<(), NonaError> {
+            self.inner.reset_clip()
+        }
+    }
+
+    fn recorder(width: usize, height: usize) -> (Recorder, Context) {
+        let mut renderer = Recorder::new(width, height);
+        let mut ctx = Context::create(&mut renderer).unwrap();
+        ctx.begin_frame(&mut renderer, None).unwrap();
+        (renderer, ctx)
+    }
+
+    fn roboto(ctx: &mut Context) -> FontId {
+        ctx.create_font(
+            "roboto",
+            &include_bytes!("../../nonaquad/examples/Roboto-Bold.ttf")[..],
         )
+        .unwrap()
+    }
+
+    fn pixel(renderer: &SoftwareRenderer, x: usize, y: usize) -> [u8; 4] {
+        let idx = (y * renderer.width() + x) * 4;
+        let mut res = [0; 4];
+        res.copy_from_slice(&renderer.pixels()[idx..idx + 4]);
+        res
+    }
+
+    fn png(width: u32, height: u32, color: [u8; 4]) -> Vec<u8> {
+        let img = image::RgbaImage::from_pixel(width, height, image::Rgba(color));
+        let mut data = Vec::new();
+        image::DynamicImage::ImageRgba8(img)
+            .write_to(&mut data, image::ImageOutputFormat::Png)
+            .unwrap();
+        data
+    }
+
+    #[test]
+    fn begin_frame_clears_to_color() {
+        let (mut renderer, mut ctx) = setup(8, 8);
+        ctx.begin_frame(&mut renderer, Some(Color::rgb(0.0, 0.0, 0.0)))
+            .unwrap();
+        ctx.end_frame(&mut renderer).unwrap();
+        for (x, y) in [(0, 0), (7, 0), (4, 4), (7, 7)] {
+            assert_eq!(pixel(&renderer, x, y), [0, 0, 0, 255]);
+        }
+    }
+
+    #[test]
+    fn text_on_path_rotates_glyphs_along_the_tangent() {
+        let (mut renderer, mut ctx) = recorder(200, 200);
+        roboto(&mut ctx);
+        ctx.font_size(20.0);
+
+        // quarter circle, from heading right to heading down
+        let points: Vec<Point> = (0..=32)
+            .map(|i| {
+                let a = -PI * 0.5 + i as f32 / 32.0 * PI * 0.5;
+                Point::new(50.0 + 100.0 * a.cos(), 150.0 + 100.0 * a.sin())
+            })
+            .collect();
+        ctx.text_on_path(&mut renderer, &points, "WWWWWWWW")
+            .unwrap();
+
+        let (_, vertexes) = renderer.triangles.last().unwrap();
+        // each glyph quad starts with its top left corner, then bottom right and top right
+        let angles: Vec<f32> = vertexes
+            .chunks(6)
+            .map(|quad| (quad[2].y - quad[0].y).atan2(quad[2].x - quad[0].x))
+            .collect();
+        assert_eq!(angles.len(), 8);
+        assert!(angles[0].abs() < 0.2);
+        for pair in angles.windows(2) {
+            assert!(pair[1] > pair[0]);
+        }
+    }
+
+    #[test]
+    fn reset_clip_and_transform_clears_transform_scissor_and_clips() {
+        let (mut renderer, mut ctx) = setup(40, 40);
+        ctx.translate(10.0, 5.0);
+        ctx.scissor((0.0, 0.0, 10.0, 10.0));
+        ctx.begin_path();
+        ctx.rect((0.0, 0.0, 5.0, 5.0));
+        ctx.clip_path();
+
+        ctx.reset_clip_and_transform();
+        assert!(ctx.current_transform().is_identity());
+        assert!(ctx.current_scissor().is_none());
+
+        ctx.begin_path();
+        ctx.rect((0.0, 0.0, 40.0, 40.0));
+        ctx.fill_paint(Color::rgb(1.0, 0.0, 0.0));
+        ctx.fill(&mut renderer).unwrap();
+        ctx.end_frame(&mut renderer).unwrap();
+        assert_eq!(pixel(&renderer, 30, 30), [255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn create_image_rejects_images_over_max_texture_size() {
+        let (mut renderer, mut ctx) = setup(8, 8);
+        let max = renderer.max_texture_size();
+        let err = ctx
+            .create_image(
+                &mut renderer,
+                ImageFlags::empty(),
+                png(max as u32 + 1, 1, [0; 4]),
+            )
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "ERR_IMAGE: image {}×1 exceeds max texture size {}",
+                max + 1,
+                max
+            )
+        );
+        assert!(ctx
+            .create_image(
+                &mut renderer,
+                ImageFlags::empty(),
+                png(max as u32, 1, [0; 4])
+            )
+            .is_ok());
+    }
+
+    #[test]
+    fn large_arc_stays_close_to_the_circle() {
+        let (_, mut ctx) = setup(8, 8);
+        let center = Point::new(400.0, 400.0);
+        let radius = 300.0;
+        ctx.begin_path();
+        ctx.arc(center, radius, 0.0, PI * 2.0, Solidity::Hole);
+
+        let mut last = Point::default();
+        let mut max_error: f32 = 0.0;
+        for segment in ctx.export_path() {
+            match segment {
+                PathSegment::MoveTo(pt) => last = pt,
+                PathSegment::CubicTo(c1, c2, pt) => {
+                    for i in 0..=16 {
+                        let t = i as f32 / 16.0;
+                        let mt = 1.0 - t;
+                        let at = last * (mt * mt * mt)
+                            + c1 * (3.0 * mt * mt * t)
+                            + c2 * (3.0 * mt * t * t)
+                            + pt * (t * t * t);
+                        max_error = max_error.max(((at - center).length() - radius).abs());
+                    }
+                    last = pt;
+                }
+                _ => {}
+            }
+        }
+        assert!(max_error < 0.25, "max curve error {}", max_error);
+
+        // the flattened polyline, whose chords cut inside the circle
+        ctx.cache.clear();
+        ctx.cache
+            .flatten_paths(&ctx.commands, ctx.dist_tol, ctx.tess_tol(), None);
+        let points: Vec<Point> = ctx.cache.points.iter().map(|pt| pt.xy).collect();
+        let max_chord_error = points
+            .iter()
+            .zip(points.iter().cycle().skip(1))
+            .map(|(p0, p1)| radius - ((*p0 + *p1) * 0.5 - center).length())
+            .fold(0.0, f32::max);
+        assert!(
+            max_chord_error < 0.25,
+            "max chord error {}",
+            max_chord_error
+        );
+    }
+
+    fn outline_points(segments: &[PathSegment]) -> Vec<Point> {
+        segments
+            .iter()
+            .filter_map(|segment| match segment {
+                PathSegment::MoveTo(pt) | PathSegment::LineTo(pt) => Some(*pt),
+                PathSegment::CubicTo(_, _, pt) => Some(*pt),
+                PathSegment::Close => None,
+            })
+            .collect()
+    }
+
+    fn stroke_end_points(ctx: &mut Context, cap: LineCap) -> Vec<Point> {
+        ctx.begin_path();
+        ctx.move_to((10.0, 20.0));
+        ctx.line_to((50.0, 20.0));
+        ctx.stroke_width(10.0);
+        ctx.line_cap(cap);
+        outline_points(&ctx.stroke_to_fill())
+            .into_iter()
+            .filter(|pt| pt.x > 50.01)
+            .collect()
+    }
+
+    #[test]
+    fn square_cap_extends_the_end_by_half_the_width() {
+        let (_, mut ctx) = setup(8, 8);
+        let end = stroke_end_points(&mut ctx, LineCap::Square);
+        let near = |x: f32, y: f32| {
+            end.iter()
+                .any(|pt| (*pt - Point::new(x, y)).length() < 0.01)
+        };
+        assert!(near(55.0, 15.0) && near(55.0, 25.0), "{:?}", end);
+        assert!(end.iter().all(|pt| (pt.x - 55.0).abs() < 0.01));
+    }
+
+    #[test]
+    fn round_cap_ends_in_a_half_circle() {
+        let (_, mut ctx) = setup(8, 8);
+        let end = stroke_end_points(&mut ctx, LineCap::Round);
+        assert!(end.len() > 2, "{:?}", end);
+        let center = Point::new(50.0, 20.0);
+        assert!(end
+            .iter()
+            .all(|pt| ((*pt - center).length() - 5.0).abs() < 0.01));
+        assert!(end.iter().any(|pt| pt.x > 54.5), "{:?}", end);
+    }
+
+    #[test]
+    fn butt_cap_ends_at_the_end_point() {
+        let (_, mut ctx) = setup(8, 8);
+        assert!(stroke_end_points(&mut ctx, LineCap::Butt).is_empty());
+    }
+
+    #[test]
+    fn subpath_style_overrides_the_cap_per_subpath() {
+        let (_, mut ctx) = setup(8, 8);
+        ctx.begin_path();
+        ctx.move_to((10.0, 20.0));
+        ctx.line_to((50.0, 20.0));
+        ctx.subpath_style(LineCap::Round, LineJoin::Miter);
+        ctx.move_to((10.0, 60.0));
+        ctx.line_to((50.0, 60.0));
+        ctx.subpath_style(LineCap::Square, LineJoin::Miter);
+        ctx.stroke_width(10.0);
+        ctx.line_cap(LineCap::Butt);
+        let end: Vec<Point> = outline_points(&ctx.stroke_to_fill())
+            .into_iter()
+            .filter(|pt| pt.x > 50.01)
+            .collect();
+
+        let round: Vec<_> = end.iter().filter(|pt| pt.y < 40.0).collect();
+        assert!(!round.is_empty());
+        for pt in round {
+            assert!(((*pt - Point::new(50.0, 20.0)).length() - 5.0).abs() < 0.01);
+        }
+        let near = |x: f32, y: f32| {
+            end.iter()
+                .any(|pt| (*pt - Point::new(x, y)).length() < 0.01)
+        };
+        assert!(near(55.0, 55.0) && near(55.0, 65.0), "{:?}", end);
+    }
+
+    fn nested_squares(ctx: &mut Context) {
+        ctx.begin_path();
+        ctx.rect((0.0, 0.0, 30.0, 30.0));
+        ctx.rect((10.0, 10.0, 10.0, 10.0));
+        ctx.fill_paint(Color::rgb(1.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn even_odd_fill_leaves_nested_subpaths_empty() {
+        let (mut renderer, mut ctx) = setup(32, 32);
+        ctx.shape_antialias(false);
+        nested_squares(&mut ctx);
+        ctx.fill_with_rule(&mut renderer, FillRule::EvenOdd)
+            .unwrap();
+        ctx.end_frame(&mut renderer).unwrap();
+        assert_eq!(pixel(&renderer, 5, 5), [255, 0, 0, 255]);
+        assert_eq!(pixel(&renderer, 15, 15), [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn non_zero_fill_covers_nested_subpaths() {
+        let (mut renderer, mut ctx) = setup(32, 32);
+        ctx.shape_antialias(false);
+        nested_squares(&mut ctx);
+        ctx.fill(&mut renderer).unwrap();
+        ctx.end_frame(&mut renderer).unwrap();
+        assert_eq!(pixel(&renderer, 5, 5), [255, 0, 0, 255]);
+        assert_eq!(pixel(&renderer, 15, 15), [255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn last_stroke_bounds_grow_by_half_the_width_and_the_fringe() {
+        let (mut renderer, mut ctx) = setup(64, 64);
+        ctx.begin_path();
+        ctx.move_to((10.0, 20.0));
+        ctx.line_to((50.0, 30.0));
+        ctx.stroke_width(4.0);
+        ctx.stroke(&mut renderer).unwrap();
+        let bounds = ctx.last_stroke_bounds();
+        let grow = 2.0 + ctx.fringe_width;
+        assert!((bounds.min.x - (10.0 - grow)).abs() < 1e-4);
+        assert!((bounds.min.y - (20.0 - grow)).abs() < 1e-4);
+        assert!((bounds.max.x - (50.0 + grow)).abs() < 1e-4);
+        assert!((bounds.max.y - (30.0 + grow)).abs() < 1e-4);
+
+        ctx.end_frame(&mut renderer).unwrap();
+        for y in 0..64 {
+            for x in 0..64 {
+                if pixel(&renderer, x, y)[3] > 0 {
+                    assert!(bounds.contains_point(Point::new(x as f32, y as f32)));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn draw_checkerboard_alternates_cells_inside_the_rect() {
+        let (mut renderer, mut ctx) = setup(48, 48);
+        let (white, blue) = (Color::rgb(1.0, 1.0, 1.0), Color::rgb(0.0, 0.0, 1.0));
+        ctx.draw_checkerboard(&mut renderer, (0.0, 0.0, 35.0, 35.0), 10.0, white, blue)
+            .unwrap();
+        assert!(ctx.current_scissor().is_none());
+        ctx.end_frame(&mut renderer).unwrap();
+
+        assert_eq!(pixel(&renderer, 5, 5), [255, 255, 255, 255]);
+        assert_eq!(pixel(&renderer, 15, 5), [0, 0, 255, 255]);
+        assert_eq!(pixel(&renderer, 15, 15), [255, 255, 255, 255]);
+        assert_eq!(pixel(&renderer, 32, 25), [0, 0, 255, 255]);
+        // the last partial cells are cut at the rect
+        assert_eq!(pixel(&renderer, 38, 25), [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn font_scale_xy_stretches_text_per_axis() {
+        let (_, mut ctx) = setup(8, 8);
+        roboto(&mut ctx);
+        ctx.font_size(20.0);
+        let plain = ctx.text_size("Hello");
+        ctx.font_scale_xy(2.0, 1.0);
+        let wide = ctx.text_size("Hello");
+        assert!(
+            (wide.width - plain.width * 2.0).abs() < 0.5,
+            "{:?} {:?}",
+            plain,
+            wide
+        );
+        assert!((wide.height - plain.height).abs() < 1e-3);
+
+        ctx.font_scale_xy(1.0, 3.0);
+        let tall = ctx.text_size("Hello");
+        assert!((tall.width - plain.width).abs() < 1e-3);
+        assert!((tall.height - plain.height * 3.0).abs() < 0.5);
+    }
+
+    #[test]
+    fn line_height_px_spans_ascender_to_descender_plus_gap() {
+        let (_, mut ctx) = setup(8, 8);
+        roboto(&mut ctx);
+        ctx.font_size(20.0);
+        let metrics = ctx.text_metrics();
+        assert!(metrics.ascender > 0.0 && metrics.descender < 0.0);
+        assert!(metrics.line_height() > metrics.ascender - metrics.descender - 1e-3);
+        assert!((ctx.line_height_px() - metrics.line_height()).abs() < 1e-3);
+
+        ctx.text_line_height(1.5);
+        assert!((ctx.line_height_px() - metrics.line_height() * 1.5).abs() < 1e-3);
+    }
+
+    struct EventLog(Rc<std::cell::RefCell<Vec<String>>>);
+
+    impl crate::Profiler for EventLog {
+        fn on_fill_begin(&mut self) {
+            self.0.borrow_mut().push("fill".to_string());
+        }
+
+        fn on_fill_end(&mut self, path_count: usize, vertex_count: usize) {
+            self.0
+                .borrow_mut()
+                .push(format!("fill end {} {}", path_count, vertex_count > 0));
+        }
+
+        fn on_stroke_begin(&mut self) {
+            self.0.borrow_mut().push("stroke".to_string());
+        }
+
+        fn on_stroke_end(&mut self, path_count: usize, vertex_count: usize) {
+            self.0
+                .borrow_mut()
+                .push(format!("stroke end {} {}", path_count, vertex_count > 0));
+        }
+
+        fn on_flush_begin(&mut self, _draw_call_count: usize) {
+            self.0.borrow_mut().push("flush".to_string());
+        }
+
+        fn on_flush_end(&mut self, _draw_call_count: usize) {
+            self.0.borrow_mut().push("flush end".to_string());
+        }
+    }
+
+    #[test]
+    fn profiler_hooks_see_fills_strokes_and_flushes() {
+        let (mut renderer, mut ctx) = setup(16, 16);
+        let events = Rc::new(std::cell::RefCell::new(Vec::new()));
+        ctx.set_profiler(Box::new(EventLog(events.clone())));
+        ctx.begin_path();
+        ctx.rect((1.0, 1.0, 8.0, 8.0));
+        ctx.fill(&mut renderer).unwrap();
+        ctx.begin_path();
+        ctx.rect((1.0, 1.0, 8.0, 8.0));
+        ctx.stroke(&mut renderer).unwrap();
+        ctx.end_frame(&mut renderer).unwrap();
+        assert_eq!(
+            *events.borrow(),
+            [
+                "fill",
+                "fill end 1 true",
+                "stroke",
+                "stroke end 1 true",
+                "flush",
+                "flush end"
+            ]
+        );
+
+        ctx.clear_profiler();
+        ctx.fill(&mut renderer).unwrap();
+        assert_eq!(events.borrow().len(), 6);
+    }
+
+    #[test]
+    fn text_breaks_lines_on_newlines() {
+        let (mut renderer, mut ctx) = recorder(200, 200);
+        roboto(&mut ctx);
+        ctx.font_size(20.0);
+        ctx.text(&mut renderer, (10.0, 30.0), "a\nbb\r\nccc")
+            .unwrap();
+
+        let (_, vertexes) = renderer.triangles.last().unwrap();
+        let bottoms: Vec<f32> = vertexes
+            .chunks(6)
+            .map(|quad| quad.iter().map(|v| v.y).fold(f32::MIN, f32::max))
+            .collect();
+        assert_eq!(bottoms.len(), 6);
+        let line_height = ctx.line_height_px();
+        for (quad, line) in [0, 1, 1, 2, 2, 2].iter().enumerate() {
+            let expected = bottoms[0] + *line as f32 * line_height;
+            assert!((bottoms[quad] - expected).abs() < 1.0, "{:?}", bottoms);
+        }
+    }
+
+    fn font_error(ctx: &mut Context, data: &[u8]) -> String {
+        match ctx.create_font("broken", data) {
+            Err(NonaError::Font(msg)) => msg,
+            res => panic!("expected a font error, got {:?}", res.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn create_font_describes_bad_font_data() {
+        let (_, mut ctx) = setup(8, 8);
+        assert_eq!(font_error(&mut ctx, &[]), "Font data is empty");
+        assert_eq!(
+            font_error(&mut ctx, &[0, 1]),
+            "Font data is truncated (2 bytes)"
+        );
+        assert_eq!(
+            font_error(&mut ctx, b"wOFF2000"),
+            "Unsupported font format (magic 77 4f 46 46)"
+        );
+        let roboto = include_bytes!("../../nonaquad/examples/Roboto-Bold.ttf");
+        assert_eq!(
+            font_error(&mut ctx, &roboto[..64]),
+            "TrueType font data is truncated or corrupt (64 bytes)"
+        );
+    }
+
+    /// 4x4 texture, red in the left half and blue in the right half
+    fn two_tone_image<R: Renderer>(renderer: &mut R) -> ImageId {
+        let data: Vec<u8> = (0..16)
+            .flat_map(|i| {
+                if i % 4 < 2 {
+                    [255, 0, 0, 255]
+                } else {
+                    [0, 0, 255, 255]
+                }
+            })
+            .collect();
+        renderer
+            .create_texture(TextureType::RGBA, 4, 4, ImageFlags::NEAREST, Some(&data))
+            .unwrap()
+    }
+
+    fn image_fill_row(tile: bool) -> Vec<[u8; 4]> {
+        let (mut renderer, mut ctx) = setup(16, 4);
+        let img = two_tone_image(&mut renderer);
+        ctx.image_fill(&mut renderer, img, tile, 1.0).unwrap();
+        ctx.begin_path();
+        ctx.rect((0.0, 0.0, 16.0, 4.0));
+        ctx.fill(&mut renderer).unwrap();
+        ctx.end_frame(&mut renderer).unwrap();
+        (0..16).map(|x| pixel(&renderer, x, 2)).collect()
+    }
+
+    #[test]
+    fn image_fill_tiles_the_image() {
+        let (red, blue) = ([255, 0, 0, 255], [0, 0, 255, 255]);
+        let row = image_fill_row(true);
+        for (x, color) in row.iter().enumerate() {
+            assert_eq!(*color, if x % 4 < 2 { red } else { blue }, "x = {}", x);
+        }
+    }
+
+    #[test]
+    fn image_fill_without_tiling_stretches_the_edge() {
+        let row = image_fill_row(false);
+        assert_eq!(row[0], [255, 0, 0, 255]);
+        assert!(row[4..].iter().all(|color| *color == [0, 0, 255, 255]));
+    }
+
+    fn glyph_lefts(spacing: f32) -> (Vec<f32>, f32) {
+        let (mut renderer, mut ctx) = recorder(200, 50);
+        roboto(&mut ctx);
+        ctx.font_size(20.0);
+        ctx.text_letter_spacing(spacing);
+        ctx.text(&mut renderer, (10.0, 30.0), "abc").unwrap();
+        let (_, vertexes) = renderer.triangles.last().unwrap();
+        let lefts = vertexes.chunks(6).map(|quad| quad[0].x).collect();
+        (lefts, ctx.text_size("abc").width)
+    }
+
+    #[test]
+    fn letter_spacing_moves_glyphs_and_widens_text_size_alike() {
+        let (tight, tight_width) = glyph_lefts(0.0);
+        let (loose, loose_width) = glyph_lefts(5.0);
+        for (i, (a, b)) in tight.iter().zip(&loose).enumerate() {
+            assert!(
+                (b - a - 5.0 * i as f32).abs() < 0.01,
+                "{:?} {:?}",
+                tight,
+                loose
+            );
+        }
+        assert!((loose_width - tight_width - 15.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn text_break_lines_wraps_at_whitespace_and_newlines() {
+        let (_, mut ctx) = setup(8, 8);
+        roboto(&mut ctx);
+        ctx.font_size(20.0);
+        let text = "hello world  foo\nbar";
+        let width = ctx.text_size("hello world").width;
+        let lines = ctx.text_break_lines(text, width + 1.0);
+        let ranges: Vec<&str> = lines.iter().map(|(s, e, _)| &text[*s..*e]).collect();
+        assert_eq!(ranges, ["hello world", "foo", "bar"]);
+        assert!((lines[0].2 - width).abs() < 0.01);
+        assert!(lines.iter().all(|line| line.2 <= width + 1.0));
+    }
+
+    #[test]
+    fn text_break_lines_splits_words_wider_than_the_line() {
+        let (_, mut ctx) = setup(8, 8);
+        roboto(&mut ctx);
+        ctx.font_size(20.0);
+        let width = ctx.text_size("mmm").width;
+        let lines = ctx.text_break_lines("mmmmmmm", width + 0.5);
+        let lens: Vec<usize> = lines.iter().map(|(s, e, _)| e - s).collect();
+        assert_eq!(lens, [3, 3, 1]);
+    }
+
+    /// Width of the quad of a single glyph and of its region in the atlas
+    fn glyph_quad_and_uv_width(dpi_scale: f32) -> (f32, f32) {
+        let (mut renderer, mut ctx) = recorder(200, 100);
+        roboto(&mut ctx);
+        ctx.font_size(40.0);
+        ctx.text_dpi_scale(dpi_scale);
+        ctx.text(&mut renderer, (10.0, 60.0), "W").unwrap();
+        let (_, quad) = renderer.triangles.last().unwrap();
+        (quad[2].x - quad[0].x, quad[2].u - quad[0].u)
+    }
+
+    #[test]
+    fn text_dpi_scale_rasterizes_larger_without_growing_the_text() {
+        let (width, uv_width) = glyph_quad_and_uv_width(1.0);
+        let (hidpi_width, hidpi_uv_width) = glyph_quad_and_uv_width(2.0);
+        assert!(
+            (hidpi_width - width).abs() < 1.0,
+            "{} {}",
+            width,
+            hidpi_width
+        );
+        let ratio = hidpi_uv_width / uv_width;
+        assert!((ratio - 2.0).abs() < 0.1, "{}", ratio);
+    }
+
+    #[test]
+    fn try_restore_reports_whether_a_state_was_popped() {
+        let (_, mut ctx) = setup(8, 8);
+        ctx.save();
+        ctx.global_alpha(0.5);
+        ctx.save();
+        assert!(ctx.try_restore());
+        assert!(ctx.try_restore());
+        assert!(!ctx.try_restore());
+        assert_eq!(ctx.states.len(), 1);
+        assert_eq!(ctx.states[0].alpha, 1.0);
+    }
+
+    #[test]
+    fn global_tint_multiplies_fill_and_stroke_colors() {
+        let (mut renderer, mut ctx) = setup(32, 16);
+        ctx.shape_antialias(false);
+        ctx.global_tint(Color::rgb(1.0, 0.5, 0.0));
+        ctx.begin_path();
+        ctx.rect((0.0, 0.0, 16.0, 16.0));
+        ctx.fill_paint(Color::rgb(1.0, 1.0, 1.0));
+        ctx.fill(&mut renderer).unwrap();
+        ctx.begin_path();
+        ctx.move_to((24.0, 0.0));
+        ctx.line_to((24.0, 16.0));
+        ctx.stroke_width(4.0);
+        ctx.stroke_paint(Color::rgb(0.0, 1.0, 1.0));
+        ctx.stroke(&mut renderer).unwrap();
+        ctx.end_frame(&mut renderer).unwrap();
+
+        let fill = pixel(&renderer, 8, 8);
+        assert_eq!(fill[0], 255);
+        assert!((fill[1] as i32 - 128).abs() <= 1, "{:?}", fill);
+        assert_eq!(fill[2], 0);
+        let stroke = pixel(&renderer, 24, 8);
+        assert_eq!(stroke[0], 0);
+        assert!((stroke[1] as i32 - 128).abs() <= 1, "{:?}", stroke);
+        assert_eq!(stroke[2], 0);
+    }
+
+    fn stroke_there_and_back(along_path: bool) -> (SoftwareRenderer, Context) {
+        let (mut renderer, mut ctx) = setup(100, 40);
+        ctx.begin_path();
+        ctx.move_to((10.0, 10.0));
+        ctx.line_to((90.0, 10.0));
+        ctx.line_to((90.0, 30.0));
+        ctx.line_to((10.0, 30.0));
+        ctx.stroke_width(6.0);
+        ctx.stroke_gradient_along_path(along_path);
+        ctx.stroke_paint(Gradient::Linear {
+            start: Point::new(0.0, 0.0),
+            end: Point::new(100.0, 0.0),
+            start_color: Color::rgb(1.0, 0.0, 0.0),
+            end_color: Color::rgb(0.0, 0.0, 1.0),
+        });
+        ctx.stroke(&mut renderer).unwrap();
+        ctx.end_frame(&mut renderer).unwrap();
+        (renderer, ctx)
+    }
+
+    #[test]
+    fn stroke_gradient_along_path_follows_the_arc_length() {
+        let (renderer, _) = stroke_there_and_back(true);
+        let start = pixel(&renderer, 12, 10);
+        let end = pixel(&renderer, 12, 30);
+        assert!(start[0] > 200 && start[2] < 50, "{:?}", start);
+        assert!(end[0] < 50 && end[2] > 200, "{:?}", end);
+
+        // without it, the gradient runs along x on both legs
+        let (renderer, _) = stroke_there_and_back(false);
+        assert_eq!(pixel(&renderer, 12, 10), pixel(&renderer, 12, 30));
+    }
+
+    #[test]
+    fn with_state_restores_even_unbalanced_saves() {
+        let (_, mut ctx) = setup(8, 8);
+        ctx.with_state(|ctx| {
+            ctx.global_alpha(0.5);
+            ctx.translate(3.0, 4.0);
+            ctx.save();
+            ctx.save();
+        });
+        assert_eq!(ctx.states.len(), 1);
+        assert_eq!(ctx.states[0].alpha, 1.0);
+        assert!(ctx.current_transform().is_identity());
+    }
+
+    #[test]
+    fn paint_builder_builds_color_and_image_paints() {
+        let (mut renderer, mut ctx) = setup(16, 4);
+        ctx.shape_antialias(false);
+        ctx.begin_path();
+        ctx.rect((0.0, 0.0, 8.0, 4.0));
+        ctx.fill_paint(Paint::builder().color(Color::rgb(0.0, 1.0, 0.0)).build());
+        ctx.fill(&mut renderer).unwrap();
+
+        let img = two_tone_image(&mut renderer);
+        ctx.begin_path();
+        ctx.rect((8.0, 0.0, 4.0, 4.0));
+        ctx.fill_paint(
+            Paint::builder()
+                .image(img)
+                .xform(Transform::translate(8.0, 0.0))
+                .extent((4.0, 4.0))
+                .build(),
+        );
+        ctx.fill(&mut renderer).unwrap();
+        ctx.end_frame(&mut renderer).unwrap();
+
+        assert_eq!(pixel(&renderer, 4, 2), [0, 255, 0, 255]);
+        assert_eq!(pixel(&renderer, 9, 2), [255, 0, 0, 255]);
+        assert_eq!(pixel(&renderer, 11, 2), [0, 0, 255, 255]);
+    }
+
+    #[test]
+    fn caret_rect_sits_after_the_measured_prefix() {
+        let (_, mut ctx) = setup(8, 8);
+        roboto(&mut ctx);
+        ctx.font_size(20.0);
+        let prefix = ctx.text_size("he").width;
+        let caret = ctx.caret_rect((10.0, 30.0), "hello", 2);
+        assert!((caret.xy.x - (10.0 + prefix)).abs() < 0.01);
+        assert!((caret.size.height - ctx.line_height_px()).abs() < 0.01);
+        let metrics = ctx.text_metrics();
+        assert!((caret.xy.y - (30.0 - metrics.ascender)).abs() < 0.01);
+
+        let past_end = ctx.caret_rect((10.0, 30.0), "hello", 99);
+        assert!((past_end.xy.x - (10.0 + ctx.text_size("hello").width)).abs() < 0.01);
+
+        ctx.text_align(Align::RIGHT | Align::BASELINE);
+        let right = ctx.caret_rect((10.0, 30.0), "hello", 5);
+        assert!((right.xy.x - 10.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn relative_commands_offset_from_the_last_point() {
+        let (_, mut ctx) = setup(8, 8);
+        ctx.begin_path();
+        ctx.move_to((5.0, 5.0));
+        ctx.move_to_rel((5.0, 5.0));
+        ctx.line_to_rel((10.0, 0.0));
+        ctx.bezier_to_rel((1.0, 1.0), (2.0, 3.0), (4.0, 5.0));
+        ctx.quad_to_rel((2.0, 0.0), (4.0, 4.0));
+        let relative = ctx.export_path();
+
+        ctx.begin_path();
+        ctx.move_to((5.0, 5.0));
+        ctx.move_to((10.0, 10.0));
+        ctx.line_to((20.0, 10.0));
+        ctx.bezier_to((21.0, 11.0), (22.0, 13.0), (24.0, 15.0));
+        ctx.quad_to((26.0, 15.0), (28.0, 19.0));
+        assert_eq!(relative, ctx.export_path());
+    }
+
+    #[test]
+    fn add_subpath_continues_from_the_current_position() {
+        let (mut renderer, mut ctx) = setup(8, 8);
+        ctx.begin_path();
+        ctx.move_to((0.0, 0.0));
+        ctx.line_to((10.0, 0.0));
+        ctx.add_subpath();
+        ctx.line_to((10.0, 10.0));
+        // a trailing subpath without segments adds nothing
+        ctx.add_subpath();
+        ctx.stroke(&mut renderer).unwrap();
+
+        assert_eq!(ctx.cache.paths.len(), 2);
+        let second = &ctx.cache.paths[1];
+        let start = ctx.cache.points[second.first].xy;
+        assert_eq!(start, Point::new(10.0, 0.0));
+        assert!(!second.closed);
+    }
+
+    #[test]
+    fn path_svg_parses_absolute_relative_and_repeated_commands() {
+        let (_, mut ctx) = setup(8, 8);
+        ctx.begin_path();
+        ctx.path_svg("M10 10h20v10l-5,5Z m5-2 L1 2 3,4 q1 0 2 2 C0 0 1 1 2 2")
+            .unwrap();
+        let parsed = ctx.export_path();
+
+        ctx.begin_path();
+        ctx.move_to((10.0, 10.0));
+        ctx.line_to((30.0, 10.0));
+        ctx.line_to((30.0, 20.0));
+        ctx.line_to((25.0, 25.0));
+        ctx.close_path();
+        ctx.move_to((15.0, 8.0));
+        ctx.line_to((1.0, 2.0));
+        ctx.line_to((3.0, 4.0));
+        ctx.quad_to((4.0, 4.0), (5.0, 6.0));
+        ctx.bezier_to((0.0, 0.0), (1.0, 1.0), (2.0, 2.0));
+        assert_eq!(parsed, ctx.export_path());
+    }
+
+    #[test]
+    fn path_svg_arcs_stay_on_the_ellipse() {
+        let (_, mut ctx) = setup(8, 8);
+        ctx.begin_path();
+        ctx.path_svg("M0 0 A10 10 0 0 1 20 0").unwrap();
+        let points = outline_points(&ctx.export_path());
+        assert_eq!(*points.last().unwrap(), Point::new(20.0, 0.0));
+        for pt in points {
+            assert!(((pt - Point::new(10.0, 0.0)).length() - 10.0).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn path_svg_reports_the_offset_of_errors() {
+        let (_, mut ctx) = setup(8, 8);
+        for (d, message) in [
+            ("10 10", "expected a command at offset 0"),
+            ("M0 0 X1 2", "unsupported command at offset 5"),
+        ] {
+            match ctx.path_svg(d) {
+                Err(NonaError::Svg(msg)) => assert_eq!(msg, message),
+                res => panic!("expected an SVG error for {:?}, got {:?}", d, res),
+            }
+        }
+        assert!(ctx.path_svg("M0").is_err());
+    }
+
+    #[test]
+    fn premultiplied_target_premultiplies_the_clear_color() {
+        let (mut renderer, mut ctx) = setup(8, 8);
+        ctx.premultiplied_target(true);
+        ctx.begin_frame(&mut renderer, Some(Color::rgba(1.0, 0.0, 0.0, 0.5)))
+            .unwrap();
+        let cleared = ctx
+            .end_frame_capture(&mut renderer, (0.0, 0.0, 1.0, 1.0).into())
+            .unwrap();
+        assert_eq!(cleared, [128, 0, 0, 128]);
+    }
+
+    #[test]
+    fn half_transparent_red_reads_back_premultiplied() {
+        let (mut renderer, mut ctx) = setup(8, 8);
+        ctx.premultiplied_target(true);
+        ctx.begin_frame(&mut renderer, Some(Color::rgba(0.0, 0.0, 0.0, 0.0)))
+            .unwrap();
+        ctx.begin_path();
+        ctx.rect((0.0, 0.0, 8.0, 8.0));
+        ctx.fill_paint(Color::rgba(1.0, 0.0, 0.0, 0.5));
+        ctx.fill(&mut renderer).unwrap();
+        ctx.end_frame(&mut renderer).unwrap();
+        let pixels = renderer.read_pixels(4, 4, 1, 1).unwrap();
+        assert_eq!(pixels, [128, 0, 0, 128]);
+    }
+
+    #[test]
+    fn end_frame_capture_reads_rows_top_to_bottom() {
+        let (mut renderer, mut ctx) = setup(8, 8);
+        ctx.fill_rect(
+            &mut renderer,
+            (0.0, 0.0, 8.0, 4.0),
+            Color::rgb(1.0, 0.0, 0.0),
+        )
+        .unwrap();
+        ctx.fill_rect(
+            &mut renderer,
+            (0.0, 4.0, 8.0, 4.0),
+            Color::rgb(0.0, 0.0, 1.0),
+        )
+        .unwrap();
+        let pixels = ctx
+            .end_frame_capture(&mut renderer, (2.0, 3.0, 1.0, 2.0).into())
+            .unwrap();
+        assert_eq!(pixels, [255, 0, 0, 255, 0, 0, 255, 255]);
+
+        let outside = ctx.end_frame_capture(&mut renderer, (4.0, 4.0, 8.0, 8.0).into());
+        assert!(matches!(outside, Err(NonaError::Image(_))));
+    }
+
+    #[test]
+    fn compute_winding_reports_each_subpath() {
+        let (_, mut ctx) = setup(8, 8);
+        ctx.begin_path();
+        ctx.rect((0.0, 0.0, 30.0, 30.0));
+        ctx.move_to((10.0, 10.0));
+        ctx.line_to((20.0, 10.0));
+        ctx.line_to((20.0, 20.0));
+        ctx.line_to((10.0, 20.0));
+        ctx.close_path();
+        // marking the subpath solid doesn't change how it was drawn
+        ctx.path_solidity(Solidity::Solid);
+        assert_eq!(ctx.compute_winding(), [Solidity::Solid, Solidity::Hole]);
+    }
+
+    /// Top left corners of the glyph quads of vertical text
+    fn vertical_glyph_corners(letter_spacing: f32, column_spacing: f32) -> Vec<Point> {
+        let (mut renderer, mut ctx) = recorder(200, 200);
+        roboto(&mut ctx);
+        ctx.font_size(20.0);
+        ctx.text_direction(TextDirection::Vertical);
+        ctx.text_letter_spacing(letter_spacing);
+        ctx.text_column_spacing(column_spacing);
+        ctx.text(&mut renderer, (150.0, 10.0), "HH\nHH").unwrap();
+        let (_, vertexes) = renderer.triangles.last().unwrap();
+        vertexes
+            .chunks(6)
+            .map(|quad| Point::new(quad[0].x, quad[0].y))
+            .collect()
+    }
+
+    #[test]
+    fn vertical_text_stacks_glyphs_in_columns() {
+        let glyphs = vertical_glyph_corners(0.0, 0.0);
+        assert_eq!(glyphs.len(), 4);
+        // the second glyph is below the first, the second column to the left
+        assert_eq!(glyphs[1].x, glyphs[0].x);
+        assert!(
+            (glyphs[1].y - glyphs[0].y - 20.0).abs() < 1.0,
+            "{:?}",
+            glyphs
+        );
+        assert!(
+            (glyphs[0].x - glyphs[2].x - 20.0).abs() < 1.0,
+            "{:?}",
+            glyphs
+        );
+        assert_eq!(glyphs[2].y, glyphs[0].y);
+    }
+
+    #[test]
+    fn column_spacing_moves_the_second_column_further_in_x() {
+        let tight = vertical_glyph_corners(0.0, 0.0);
+        let loose = vertical_glyph_corners(0.0, 8.0);
+        let gap = |glyphs: &[Point]| glyphs[0].x - glyphs[2].x;
+        assert!((gap(&loose) - gap(&tight) - 8.0).abs() < 1.0);
+        assert_eq!(loose[1].y - loose[0].y, tight[1].y - tight[0].y);
+
+        // letter spacing runs along the column instead
+        let spaced = vertical_glyph_corners(5.0, 0.0);
+        assert!((spaced[1].y - spaced[0].y - (tight[1].y - tight[0].y) - 5.0).abs() < 1.0);
+        assert_eq!(gap(&spaced), gap(&tight));
+    }
+
+    #[test]
+    fn with_alpha_nests_multiplicatively_and_restores() {
+        let (mut renderer, mut ctx) = setup(8, 8);
+        ctx.global_alpha(0.5);
+        ctx.with_alpha(0.5, |ctx| {
+            ctx.fill_rect(
+                &mut renderer,
+                (0.0, 0.0, 8.0, 8.0),
+                Color::rgb(1.0, 1.0, 1.0),
+            )
+        })
+        .unwrap();
+        assert_eq!(ctx.states.len(), 1);
+        assert_eq!(ctx.states[0].alpha, 0.5);
+        ctx.end_frame(&mut renderer).unwrap();
+        let alpha = pixel(&renderer, 4, 4)[3];
+        assert!((alpha as i32 - 64).abs() <= 1, "{}", alpha);
+    }
+
+    #[test]
+    fn draw_triangles_applies_the_transform() {
+        let (mut renderer, mut ctx) = setup(16, 16);
+        ctx.translate(8.0, 0.0);
+        let triangle = [
+            Vertex::new(0.0, 0.0, 0.5, 0.5),
+            Vertex::new(8.0, 0.0, 0.5, 0.5),
+            Vertex::new(0.0, 8.0, 0.5, 0.5),
+        ];
+        ctx.draw_triangles(&mut renderer, Color::rgb(0.0, 1.0, 0.0).into(), &triangle)
+            .unwrap();
+        ctx.end_frame(&mut renderer).unwrap();
+        assert_eq!(pixel(&renderer, 9, 1), [0, 255, 0, 255]);
+        assert_eq!(pixel(&renderer, 1, 1), [0, 0, 0, 0]);
+        assert_eq!(pixel(&renderer, 15, 7), [0, 0, 0, 0]);
+    }
+
+    fn stretched_two_pixel_row(nearest: bool) -> Vec<[u8; 4]> {
+        let (mut renderer, mut ctx) = setup(16, 2);
+        let data = [255, 0, 0, 255, 0, 0, 255, 255];
+        let img = renderer
+            .create_texture(TextureType::RGBA, 2, 1, ImageFlags::empty(), Some(&data))
+            .unwrap();
+        ctx.image_filter(&mut renderer, img, nearest).unwrap();
+        ctx.begin_path();
+        ctx.rect((0.0, 0.0, 16.0, 2.0));
+        ctx.fill_paint(Paint::builder().image(img).extent((16.0, 2.0)).build());
+        ctx.fill(&mut renderer).unwrap();
+        ctx.end_frame(&mut renderer).unwrap();
+        (0..16).map(|x| pixel(&renderer, x, 1)).collect()
+    }
+
+    #[test]
+    fn image_filter_switches_to_nearest_sampling() {
+        let row = stretched_two_pixel_row(true);
+        assert!(row[..8].iter().all(|c| *c == [255, 0, 0, 255]), "{:?}", row);
+        assert!(row[8..].iter().all(|c| *c == [0, 0, 255, 255]), "{:?}", row);
+
+        let row = stretched_two_pixel_row(false);
+        assert!(row[7][0] > 0 && row[7][2] > 0, "{:?}", row);
+    }
+
+    #[test]
+    fn set_front_face_reorders_clockwise_triangles() {
+        let (mut renderer, mut ctx) = recorder(16, 16);
+        let triangle = [
+            Vertex::new(0.0, 0.0, 0.0, 0.0),
+            Vertex::new(8.0, 0.0, 0.0, 0.0),
+            Vertex::new(0.0, 8.0, 0.0, 0.0),
+        ];
+        let paint: Paint = Color::rgb(1.0, 1.0, 1.0).into();
+        ctx.draw_triangles(&mut renderer, paint, &triangle).unwrap();
+        assert_eq!(renderer.triangles.last().unwrap().1, triangle);
+
+        ctx.set_front_face(false);
+        ctx.draw_triangles(&mut renderer, paint, &triangle).unwrap();
+        assert_eq!(
+            renderer.triangles.last().unwrap().1,
+            [triangle[0], triangle[2], triangle[1]]
+        );
+    }
+
+    #[test]
+    fn cached_text_draws_the_same_quads_as_text() {
+        let (mut renderer, mut ctx) = recorder(200, 50);
+        roboto(&mut ctx);
+        ctx.font_size(20.0);
+        ctx.text(&mut renderer, (10.0, 30.0), "Hello").unwrap();
+        let direct = renderer.triangles.last().unwrap().1.clone();
+
+        let cached = ctx.prepare_text(&mut renderer, "Hello").unwrap();
+        ctx.draw_cached_text(&mut renderer, &cached, (10.0, 30.0))
+            .unwrap();
+        let replayed = &renderer.triangles.last().unwrap().1;
+        assert_eq!(direct.len(), replayed.len());
+        for (a, b) in direct.iter().zip(replayed) {
+            assert!((a.x - b.x).abs() < 1e-4 && (a.y - b.y).abs() < 1e-4);
+            assert_eq!((a.u, a.v), (b.u, b.v));
+        }
+    }
+
+    #[test]
+    fn fonts_are_listed_and_found_by_name() {
+        let (_, mut ctx) = setup(8, 8);
+        let regular = roboto(&mut ctx);
+        let bold = ctx
+            .create_font(
+                "roboto-bold",
+                &include_bytes!("../../nonaquad/examples/Roboto-Bold.ttf")[..],
+            )
+            .unwrap();
+        assert_eq!(ctx.fonts(), [(regular, "roboto"), (bold, "roboto-bold")]);
+        assert_eq!(ctx.font_name(bold), Some("roboto-bold"));
+        assert_eq!(ctx.find_font("roboto-bold"), Some(bold));
+        assert_eq!(ctx.font_name(bold + 1), None);
+        assert_eq!(ctx.find_font("missing"), None);
+    }
+
+    #[test]
+    fn text_font_coverage_maps_chars_and_skips_missing_ones() {
+        let (_, mut ctx) = setup(8, 8);
+        let base = roboto(&mut ctx);
+        let fallback = roboto(&mut ctx);
+        ctx.add_fallback_fontid(base, fallback);
+        assert_eq!(
+            ctx.text_font_coverage(base, "A\u{4e16}b"),
+            vec![('A', base), ('b', base)]
+        );
+    }
+
+    #[test]
+    fn font_fallback_cycles_terminate() {
+        let (_, mut ctx) = setup(8, 8);
+        let a = roboto(&mut ctx);
+        let b = roboto(&mut ctx);
+        let c = roboto(&mut ctx);
+        ctx.add_fallback_fontid(a, b);
+        ctx.add_fallback_fontid(b, c);
+        ctx.add_fallback_fontid(c, a);
+        assert_eq!(ctx.text_font_coverage(a, "\u{4e16}x"), vec![('x', a)]);
+        assert_eq!(ctx.text_font_coverage(b, "\u{4e16}"), vec![]);
+    }
+
+    #[test]
+    fn notdef_glyph_takes_space_for_missing_chars() {
+        // Roboto's .notdef has no outline, so only its advance shows
+        let b_left = |notdef: bool| {
+            let (mut renderer, mut ctx) = recorder(200, 50);
+            roboto(&mut ctx);
+            ctx.font_size(20.0);
+            ctx.set_notdef_glyph(notdef);
+            ctx.text(&mut renderer, (10.0, 30.0), "\u{4e16}b").unwrap();
+            let (_, vertexes) = renderer.triangles.last().unwrap();
+            assert_eq!(vertexes.len(), 6);
+            (vertexes[0].x, ctx.text_size("\u{4e16}b").width)
+        };
+        let (plain_left, plain_width) = b_left(false);
+        let (notdef_left, notdef_width) = b_left(true);
+        assert!(
+            notdef_left > plain_left + 1.0,
+            "{} {}",
+            plain_left,
+            notdef_left
+        );
+        assert!(notdef_width > plain_width + 1.0);
+    }
+
+    #[test]
+    fn text_bounds_cover_only_the_ink() {
+        let (_, mut ctx) = setup(200, 50);
+        roboto(&mut ctx);
+        ctx.font_size(20.0);
+        let ink = ctx.text_bounds((10.0, 30.0), "ab");
+        let trailing = ctx.text_bounds((10.0, 30.0), "ab   ");
+        assert_eq!((ink.min, ink.max), (trailing.min, trailing.max));
+        assert!(ink.min.x >= 10.0 && ink.max.x <= 10.0 + ctx.text_size("ab").width + 1.0);
+        assert!(ink.min.y < 30.0 && ink.max.y <= 31.0);
+
+        let empty = ctx.text_bounds((10.0, 30.0), " ");
+        assert_eq!(
+            (empty.min, empty.max),
+            (Point::new(10.0, 30.0), Point::new(10.0, 30.0))
+        );
+    }
+
+    #[test]
+    fn glyph_atlas_grows_to_fit_large_glyphs() {
+        let mut renderer = SoftwareRenderer::new(100, 100);
+        let options = ContextOptions {
+            font_atlas_width: 16,
+            font_atlas_height: 16,
+            ..Default::default()
+        };
+        let mut ctx = Context::create_with_options(&mut renderer, options).unwrap();
+        ctx.begin_frame(&mut renderer, Some(Color::rgba(0.0, 0.0, 0.0, 0.0)))
+            .unwrap();
+        roboto(&mut ctx);
+        ctx.font_size(80.0);
+        ctx.fill_paint(Color::rgb(1.0, 1.0, 1.0));
+        ctx.text(&mut renderer, (10.0, 80.0), "H").unwrap();
+        ctx.end_frame(&mut renderer).unwrap();
+
+        let (width, height) = renderer.texture_size(ctx.fonts.img).unwrap();
+        assert!(width >= 64 && height >= 64, "{}x{}", width, height);
+        // the left stem of the H
+        assert_eq!(pixel(&renderer, 20, 50)[3], 255);
+    }
+
+    #[test]
+    fn context_options_set_atlas_size_and_tolerance() {
+        let circle_fill_vertexes = |tessellation_tolerance: f32| {
+            let mut renderer = SoftwareRenderer::new(100, 100);
+            let options = ContextOptions {
+                font_atlas_width: 256,
+                font_atlas_height: 128,
+                tessellation_tolerance,
+                ..Default::default()
+            };
+            let mut ctx = Context::create_with_options(&mut renderer, options).unwrap();
+            assert_eq!(renderer.texture_size(ctx.fonts.img).unwrap(), (256, 128));
+
+            ctx.begin_frame(&mut renderer, None).unwrap();
+            ctx.begin_path();
+            ctx.circle((50.0, 50.0), 40.0);
+            ctx.fill(&mut renderer).unwrap();
+            ctx.cache.paths[0].num_fill
+        };
+        assert!(circle_fill_vertexes(4.0) < circle_fill_vertexes(0.25));
+    }
+
+    #[test]
+    fn device_pixel_ratio_override_applies_from_the_next_frame() {
+        let (mut renderer, mut ctx) = setup(8, 8);
+        assert_eq!(ctx.fringe_width(), 1.0);
+        ctx.set_device_pixel_ratio_override(Some(2.0));
+        assert_eq!(ctx.fringe_width(), 1.0);
+        ctx.begin_frame(&mut renderer, None).unwrap();
+        assert_eq!(ctx.fringe_width(), 0.5);
+        ctx.set_device_pixel_ratio_override(None);
+        ctx.begin_frame(&mut renderer, None).unwrap();
+        assert_eq!(ctx.fringe_width(), 1.0);
+    }
+
+    #[test]
+    fn fringe_width_widens_the_antialiased_edge() {
+        let edge_alpha = |fringe: Option<f32>| {
+            let (mut renderer, mut ctx) = setup(40, 40);
+            if let Some(fringe) = fringe {
+                ctx.set_fringe_width(fringe);
+                // the override outlives frames
+                ctx.begin_frame(&mut renderer, None).unwrap();
+            }
+            ctx.begin_path();
+            ctx.rect((10.0, 10.0, 20.0, 20.0));
+            ctx.fill_paint(Color::rgb(1.0, 1.0, 1.0));
+            ctx.fill(&mut renderer).unwrap();
+            ctx.end_frame(&mut renderer).unwrap();
+            pixel(&renderer, 30, 20)[3]
+        };
+        assert_eq!(edge_alpha(None), 0);
+        assert!(edge_alpha(Some(4.0)) > 0);
+
+        let (mut renderer, mut ctx) = setup(8, 8);
+        ctx.set_fringe_width(4.0);
+        ctx.reset_fringe_width();
+        ctx.begin_frame(&mut renderer, None).unwrap();
+        assert_eq!(ctx.fringe_width(), 1.0);
+    }
+
+    #[test]
+    fn fast_fill_matches_plain_fill_of_convex_paths() {
+        let draw = |fast: bool, concave: bool| {
+            let (mut renderer, mut ctx) = setup(40, 40);
+            ctx.fast_fill(fast);
+            ctx.shape_antialias(false);
+            ctx.begin_path();
+            if concave {
+                ctx.move_to((5.0, 5.0));
+                ctx.line_to((35.0, 5.0));
+                ctx.line_to((20.0, 15.0));
+                ctx.line_to((35.0, 35.0));
+                ctx.line_to((5.0, 35.0));
+            } else {
+                ctx.rect((5.0, 5.0, 30.0, 20.0));
+            }
+            ctx.fill_paint(Color::rgb(1.0, 1.0, 1.0));
+            ctx.fill(&mut renderer).unwrap();
+            let num_fill = ctx.cache.paths[0].num_fill;
+            ctx.end_frame(&mut renderer).unwrap();
+            (renderer.pixels().to_vec(), num_fill)
+        };
+
+        let (fast, fast_count) = draw(true, false);
+        let (plain, _) = draw(false, false);
+        assert_eq!(fast, plain);
+        assert_eq!(fast_count, 4);
+
+        // concave paths fall back to the stencil fill
+        assert_eq!(draw(true, true).0, draw(false, true).0);
+    }
+
+    #[test]
+    fn reset_all_yields_a_pristine_context() {
+        let (mut renderer, mut ctx) = setup(20, 20);
+        ctx.save();
+        ctx.save();
+        ctx.translate(3.0, 4.0);
+        ctx.scissor((0.0, 0.0, 5.0, 5.0));
+        ctx.push_scissor();
+        ctx.begin_path();
+        ctx.rect((0.0, 0.0, 5.0, 5.0));
+        ctx.clip_path();
+        ctx.begin_cached_path(7);
+        ctx.rect((0.0, 0.0, 5.0, 5.0));
+        ctx.fill(&mut renderer).unwrap();
+        ctx.begin_path();
+        ctx.move_to((1.0, 1.0));
+
+        ctx.reset_all(&mut renderer).unwrap();
+        assert_eq!(ctx.save_count(), 1);
+        assert!(ctx.commands.is_empty());
+        assert!(ctx.scissor_stack.is_empty());
+        assert!(ctx.cached_paths.is_empty());
+        assert!(ctx.state().xform.is_identity());
+        assert_eq!(ctx.draw_call_count, 0);
+
+        // neither the clip nor the scissor limit drawing anymore
+        ctx.begin_path();
+        ctx.rect((0.0, 0.0, 20.0, 20.0));
+        ctx.fill_paint(Color::rgb(1.0, 1.0, 1.0));
+        ctx.fill(&mut renderer).unwrap();
+        ctx.end_frame(&mut renderer).unwrap();
+        assert_eq!(pixel(&renderer, 15, 15), [255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn canvas_renderer_accessors_borrow_the_attached_renderer() {
+        let (mut renderer, mut ctx) = setup(16, 8);
+        let size = ctx.attach_renderer(&mut renderer, |canvas| {
+            canvas
+                .renderer_mut()
+                .clear_screen(Color::rgb(1.0, 0.0, 0.0));
+            canvas.renderer().view_size()
+        });
+        assert_eq!(size, (16.0, 8.0));
+        // the renderer is back with its owner once the call returns
+        assert_eq!(pixel(&renderer, 3, 3), [255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn software_renderer_draws_triangles_and_manages_textures() {
+        let mut renderer = SoftwareRenderer::new(8, 8);
+        renderer.clear_screen(Color::rgba(0.0, 0.0, 0.0, 0.0));
+
+        // right triangle over the top left half, covering pixel centers below the diagonal
+        let vertexes = [
+            Vertex::new(0.0, 0.0, 0.0, 0.0),
+            Vertex::new(0.0, 8.0, 0.0, 0.0),
+            Vertex::new(8.0, 8.0, 0.0, 0.0),
+        ];
+        let scissor = Scissor {
+            xform: Transform::identity(),
+            extent: Extent::new(-1.0, -1.0),
+        };
+        let op: CompositeOperationState =
+            CompositeOperation::Basic(BasicCompositeOperation::SrcOver).into();
+        renderer
+            .triangles(&Color::rgb(0.0, 1.0, 0.0).into(), op, &scissor, &vertexes)
+            .unwrap();
+        assert_eq!(pixel(&renderer, 1, 6), [0, 255, 0, 255]);
+        assert_eq!(pixel(&renderer, 6, 1), [0, 0, 0, 0]);
+
+        let img = renderer
+            .create_texture(TextureType::RGBA, 2, 2, ImageFlags::empty(), None)
+            .unwrap();
+        renderer
+            .update_texture(img, 1, 1, 1, 1, &[1, 2, 3, 4])
+            .unwrap();
+        assert_eq!(renderer.texture_size(img).unwrap(), (2, 2));
+        assert!(renderer.update_texture(img, 1, 1, 2, 1, &[0; 8]).is_err());
+        assert!(renderer.update_texture(img, 0, 0, 2, 2, &[0; 4]).is_err());
+        renderer.delete_texture(img).unwrap();
+        assert!(renderer.texture_size(img).is_err());
+
+        assert_eq!(renderer.read_pixels(1, 6, 1, 1).unwrap(), [0, 255, 0, 255]);
+        assert!(renderer.read_pixels(4, 4, 5, 1).is_err());
+    }
+
+    /// A backend implementing only the required `Renderer` methods
+    #[derive(Default)]
+    struct MinimalRenderer {
+        textures: usize,
+        fills: usize,
+    }
+
+    impl Renderer for MinimalRenderer {
+        fn edge_antialias(&self) -> bool {
+            false
+        }
+
+        fn view_size(&self) -> (f32, f32) {
+            (100.0, 100.0)
+        }
+
+        fn device_pixel_ratio(&self) -> f32 {
+            1.0
+        }
+
+        fn create_texture(
+            &mut self,
+            _texture_type: TextureType,
+            _width: usize,
+            _height: usize,
+            _flags: ImageFlags,
+            _data: Option<&[u8]>,
+        ) -> Result<ImageId, NonaError> {
+            self.textures += 1;
+            Ok(self.textures)
+        }
+
+        fn delete_texture(&mut self, _img: ImageId) -> Result<(), NonaError> {
+            Ok(())
+        }
+
+        fn update_texture(
+            &mut self,
+            _img: ImageId,
+            _x: usize,
+            _y: usize,
+            _width: usize,
+            _height: usize,
+            _data: &[u8],
+        ) -> Result<(), NonaError> {
+            Ok(())
+        }
+
+        fn texture_size(&self, _img: ImageId) -> Result<(usize, usize), NonaError> {
+            Ok((16, 16))
+        }
+
+        fn viewport(&mut self, _extent: Extent, _device_pixel_ratio: f32) -> Result<(), NonaError> {
+            Ok(())
+        }
+
+        fn clear_screen(&mut self, _color: Color) {}
+
+        fn flush(&mut self) -> Result<(), NonaError> {
+            Ok(())
+        }
+
+        fn fill(
+            &mut self,
+            _paint: &Paint,
+            _composite_operation: CompositeOperationState,
+            _scissor: &Scissor,
+            _params: &FillParams,
+            _paths: &[Path],
+        ) -> Result<(), NonaError> {
+            self.fills += 1;
+            Ok(())
+        }
+
+        fn stroke(
+            &mut self,
+            _paint: &Paint,
+            _composite_operation: CompositeOperationState,
+            _scissor: &Scissor,
+            _params: &StrokeParams,
+            _paths: &[Path],
+        ) -> Result<(), NonaError> {
+            Ok(())
+        }
+
+        fn triangles(
+            &mut self,
+            _paint: &Paint,
+            _composite_operation: CompositeOperationState,
+            _scissor: &Scissor,
+            _vertexes: &[Vertex],
+        ) -> Result<(), NonaError> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn minimal_renderer_draws_and_reports_unsupported_features() {
+        let mut renderer = MinimalRenderer::default();
+        let mut ctx = Context::create(&mut renderer).unwrap();
+        ctx.begin_frame(&mut renderer, None).unwrap();
+        assert_eq!(renderer.caps().max_texture_size, 4096);
+
+        ctx.begin_path();
+        ctx.rect((10.0, 10.0, 20.0, 20.0));
+        ctx.fill(&mut renderer).unwrap();
+        assert_eq!(renderer.fills, 1);
+
+        ctx.clip_path();
+        assert!(matches!(
+            ctx.fill(&mut renderer),
+            Err(NonaError::Unsupported(_))
+        ));
+        assert!(matches!(
+            renderer.read_pixels(0, 0, 1, 1),
+            Err(NonaError::Unsupported(_))
+        ));
+        ctx.end_frame(&mut renderer).unwrap();
+
+        let options = ContextOptions {
+            gamma_correct: true,
+            ..Default::default()
+        };
+        assert!(matches!(
+            Context::create_with_options(&mut renderer, options),
+            Err(NonaError::Unsupported(_))
+        ));
+    }
+
+    #[test]
+    fn glyph_coverage_scales_the_paint_alpha() {
+        let (mut renderer, mut ctx) = setup(60, 40);
+        roboto(&mut ctx);
+        ctx.font_size(30.0);
+        ctx.fill_paint(Color::rgba(1.0, 0.0, 0.0, 0.5));
+        ctx.text(&mut renderer, (5.0, 30.0), "H").unwrap();
+        ctx.end_frame(&mut renderer).unwrap();
+
+        let alphas: Vec<u8> = renderer.pixels().chunks(4).map(|p| p[3]).collect();
+        let max = *alphas.iter().max().unwrap();
+        // full coverage inside the stems, never more than the paint
+        assert!((126..=129).contains(&max), "{}", max);
+        // antialiased edges have partial coverage
+        assert!(alphas.iter().any(|&a| a > 0 && a < max - 10));
+    }
+
+    /// Pixel of `color` drawn with `blend` over opaque gray
+    fn blended_pixel(blend: fn(&mut Context), color: Color) -> [u8; 4] {
+        let mut renderer = SoftwareRenderer::new(8, 8);
+        let mut ctx = Context::create(&mut renderer).unwrap();
+        ctx.begin_frame(&mut renderer, Some(Color::rgb(0.5, 0.5, 0.5)))
+            .unwrap();
+        blend(&mut ctx);
+        ctx.begin_path();
+        ctx.rect((0.0, 0.0, 8.0, 8.0));
+        ctx.fill_paint(color);
+        ctx.fill(&mut renderer).unwrap();
+        ctx.end_frame(&mut renderer).unwrap();
+        pixel(&renderer, 4, 4)
+    }
+
+    fn blended_gray(blend: fn(&mut Context)) -> u8 {
+        blended_pixel(blend, Color::rgb(0.5, 0.5, 0.5))[0]
+    }
+
+    #[test]
+    fn blend_helpers_over_an_opaque_background() {
+        let near = |value: u8, expected: u8| (value as i32 - expected as i32).abs() <= 1;
+        let multiply = blended_gray(Context::blend_multiply);
+        assert!(near(multiply, 64), "{}", multiply);
+        let screen = blended_gray(Context::blend_screen);
+        assert!(near(screen, 191), "{}", screen);
+        assert_eq!(blended_gray(Context::blend_additive), 255);
+    }
+
+    #[test]
+    fn blend_equations_pick_or_subtract_channels() {
+        let red = Color::rgb(1.0, 0.0, 0.0);
+        assert_eq!(blended_pixel(Context::blend_darken, red), [128, 0, 0, 255]);
+        assert_eq!(
+            blended_pixel(Context::blend_lighten, red),
+            [255, 128, 128, 255]
+        );
+        let reverse_subtract = |ctx: &mut Context| {
+            ctx.blend_additive();
+            ctx.blend_equation(BlendEquation::ReverseSubtract);
+        };
+        assert_eq!(blended_pixel(reverse_subtract, red)[..3], [0, 128, 128]);
+    }
+
+    #[test]
+    fn stroke_align_moves_closed_strokes_inside_or_outside() {
+        let left_edge = |align: StrokeAlign| {
+            let (mut renderer, mut ctx) = setup(40, 40);
+            ctx.begin_path();
+            ctx.rect((10.0, 10.0, 20.0, 20.0));
+            ctx.stroke_width(4.0);
+            ctx.stroke_align(align);
+            ctx.stroke_paint(Color::rgb(1.0, 1.0, 1.0));
+            ctx.stroke(&mut renderer).unwrap();
+            ctx.end_frame(&mut renderer).unwrap();
+            (7..14)
+                .map(|x| pixel(&renderer, x, 20)[3] > 128)
+                .collect::<Vec<_>>()
+        };
+        //                    x: 7      8      9      10     11     12     13
+        let center = [false, true, true, true, true, false, false];
+        let inner = [false, false, false, true, true, true, true];
+        let outer = [true, true, true, false, false, false, false];
+        assert_eq!(left_edge(StrokeAlign::Center), center);
+        assert_eq!(left_edge(StrokeAlign::Inner), inner);
+        assert_eq!(left_edge(StrokeAlign::Outer), outer);
+    }
+
+    #[test]
+    fn miter_clip_cuts_sharp_joins_at_the_limit() {
+        let join_reach = |join: LineJoin, limit: f32| {
+            let (mut renderer, mut ctx) = setup(100, 100);
+            ctx.begin_path();
+            ctx.move_to((10.0, 50.0));
+            ctx.line_to((50.0, 40.0));
+            ctx.line_to((10.0, 30.0));
+            ctx.stroke_width(10.0);
+            ctx.line_join(join);
+            ctx.miter_limit(limit);
+            ctx.stroke(&mut renderer).unwrap();
+            ctx.cache.paths[0]
+                .get_stroke()
+                .iter()
+                .map(|v| v.x)
+                .fold(f32::MIN, f32::max)
+        };
+        let bevel = join_reach(LineJoin::Bevel, 2.0);
+        assert!(bevel < 52.0, "{}", bevel);
+        // a miter over the limit falls back to a bevel
+        assert!((join_reach(LineJoin::Miter, 2.0) - bevel).abs() < 0.01);
+        // the clipped miter reaches limit * half the width past the corner, where the
+        // half width includes half of the 1px fringe
+        let clipped = join_reach(LineJoin::MiterClip, 2.0);
+        assert!((clipped - 61.0).abs() < 0.1, "{}", clipped);
+        // under the limit it is a plain miter, 5.5 / sin(atan(1 / 4)) past the corner
+        let miter = join_reach(LineJoin::MiterClip, 10.0);
+        assert!(
+            (miter - (50.0 + 5.5 * 17f32.sqrt())).abs() < 0.1,
+            "{}",
+            miter
+        );
+    }
+
+    #[test]
+    fn replay_repeats_the_recorded_renderer_calls() {
+        let (mut renderer, mut ctx) = recorder(200, 100);
+        roboto(&mut ctx);
+        ctx.font_size(20.0);
+        let list = ctx
+            .record(|ctx| {
+                ctx.begin_path();
+                ctx.rect((10.0, 10.0, 50.0, 30.0));
+                ctx.fill_paint(Color::rgb(1.0, 0.0, 0.0));
+                ctx.fill(&mut renderer)?;
+                ctx.begin_path();
+                ctx.circle((100.0, 30.0), 20.0);
+                ctx.stroke(&mut renderer)?;
+                ctx.text(&mut renderer, (10.0, 80.0), "Hi")
+            })
+            .unwrap();
+        assert_eq!(list.len(), 3);
+        let paths = std::mem::take(&mut renderer.paths);
+        let triangles = std::mem::take(&mut renderer.triangles);
+
+        ctx.begin_path();
+        ctx.replay(&mut renderer, &list).unwrap();
+        assert_eq!(renderer.paths.len(), 2);
+        assert_eq!(renderer.triangles.len(), 1);
+        for (a, b) in paths
+            .iter()
+            .chain(&triangles)
+            .zip(renderer.paths.iter().chain(&renderer.triangles))
+        {
+            assert_eq!(a.0.inner_color.r, b.0.inner_color.r);
+            assert_eq!(a.1.len(), b.1.len());
+            for (va, vb) in a.1.iter().zip(&b.1) {
+                assert!((va.x - vb.x).abs() < 1e-4 && (va.y - vb.y).abs() < 1e-4);
+            }
+        }
+
+        // replaying under a translation moves every vertex along
+        ctx.translate(5.0, 7.0);
+        ctx.replay(&mut renderer, &list).unwrap();
+        let (a, b) = (&paths[0].1[0], &renderer.paths[2].1[0]);
+        assert!((b.x - a.x - 5.0).abs() < 1e-4 && (b.y - a.y - 7.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn fill_cached_tessellates_a_key_once() {
+        let (mut renderer, mut ctx) = recorder(200, 200);
+        let fill_keyed = |renderer: &mut Recorder, ctx: &mut Context, radius: f32| {
+            ctx.begin_cached_path(1);
+            ctx.circle((100.0, 100.0), radius);
+            ctx.fill_cached(renderer).unwrap();
+            renderer.paths.last().unwrap().1.clone()
+        };
+        let first = fill_keyed(&mut renderer, &mut ctx, 50.0);
+        assert_eq!(ctx.cached_paths.len(), 1);
+
+        // the second circle is smaller, but the tessellation of the first one is reused
+        let second = fill_keyed(&mut renderer, &mut ctx, 10.0);
+        assert_eq!(first, second);
+
+        ctx.invalidate_path(1);
+        let third = fill_keyed(&mut renderer, &mut ctx, 10.0);
+        let max_x = third.iter().map(|v| v.x).fold(f32::MIN, f32::max);
+        assert!(max_x < 112.0, "{}", max_x);
+
+        // a new transform tessellates again
+        ctx.translate(20.0, 0.0);
+        let moved = fill_keyed(&mut renderer, &mut ctx, 10.0);
+        let max_x = moved.iter().map(|v| v.x).fold(f32::MIN, f32::max);
+        assert!(max_x > 128.0, "{}", max_x);
+    }
+
+    #[test]
+    fn image_pattern_matches_the_manual_paint() {
+        let (_, ctx) = setup(8, 8);
+        let helper = ctx.image_pattern((10.0, 20.0), (30.0, 40.0), 0.5, 3, 0.25);
+        let manual = Paint::from(ImagePattern {
+            center: Point::new(10.0, 20.0),
+            size: Extent::new(30.0, 40.0),
+            angle: 0.5,
+            img: 3,
+            alpha: 0.25,
+        });
+        assert_eq!(helper.xform.0, manual.xform.0);
+        assert_eq!(
+            (helper.extent.width, helper.extent.height),
+            (manual.extent.width, manual.extent.height)
+        );
+        assert_eq!(helper.image, Some(3));
+        assert_eq!(helper.inner_color.a, 0.25);
+        assert_eq!(helper.outer_color.a, 0.25);
+    }
+
+    #[test]
+    fn tinted_image_pattern_colors_a_white_mask() {
+        let (mut renderer, mut ctx) = setup(8, 8);
+        let img = ctx
+            .create_image(
+                &mut renderer,
+                ImageFlags::empty(),
+                png(8, 8, [255, 255, 255, 255]),
+            )
+            .unwrap();
+        let pattern = ImagePattern {
+            center: Point::new(0.0, 0.0),
+            size: Extent::new(8.0, 8.0),
+            angle: 0.0,
+            img,
+            alpha: 1.0,
+        };
+        ctx.begin_path();
+        ctx.rect((0.0, 0.0, 8.0, 8.0));
+        ctx.fill_paint(pattern.with_tint(Color::rgb(1.0, 0.0, 0.0)));
+        ctx.fill(&mut renderer).unwrap();
+        ctx.end_frame(&mut renderer).unwrap();
+        assert_eq!(pixel(&renderer, 4, 4), [255, 0, 0, 255]);
+
+        let plain = Paint::from(pattern);
+        assert_eq!(plain.inner_color.g, 1.0);
+        assert_eq!(
+            plain.xform.0,
+            pattern.with_tint(Color::rgb(1.0, 1.0, 1.0)).xform.0
+        );
+    }
+
+    #[test]
+    fn scissor_is_read_back_intersected_and_restored() {
+        let (_, mut ctx) = setup(100, 100);
+        let rect_of = |ctx: &Context| {
+            let r = ctx.current_scissor().unwrap();
+            (r.xy.x, r.xy.y, r.size.width, r.size.height)
+        };
+        assert!(ctx.current_scissor().is_none());
+
+        ctx.scissor((10.0, 20.0, 30.0, 40.0));
+        assert_eq!(rect_of(&ctx), (10.0, 20.0, 30.0, 40.0));
+
+        ctx.push_scissor();
+        ctx.intersect_scissor((20.0, 0.0, 100.0, 30.0));
+        assert_eq!(rect_of(&ctx), (20.0, 20.0, 20.0, 10.0));
+
+        // read back in the current user space
+        ctx.save();
+        ctx.translate(5.0, 5.0);
+        assert_eq!(rect_of(&ctx), (15.0, 15.0, 20.0, 10.0));
+        ctx.restore();
+
+        ctx.pop_scissor();
+        assert_eq!(rect_of(&ctx), (10.0, 20.0, 30.0, 40.0));
+        // an extra pop keeps the scissor
+        ctx.pop_scissor();
+        assert_eq!(rect_of(&ctx), (10.0, 20.0, 30.0, 40.0));
+
+        ctx.reset_scissor();
+        assert!(ctx.current_scissor().is_none());
+    }
+
+    #[test]
+    fn clip_path_limits_fills_to_the_circle() {
+        let (mut renderer, mut ctx) = setup(100, 100);
+        ctx.begin_path();
+        ctx.circle((50.0, 50.0), 20.0);
+        ctx.clip_path();
+
+        let fill_all = |renderer: &mut SoftwareRenderer, ctx: &mut Context, color: Color| {
+            ctx.begin_path();
+            ctx.rect((0.0, 0.0, 100.0, 100.0));
+            ctx.fill_paint(color);
+            ctx.fill(renderer).unwrap();
+        };
+        fill_all(&mut renderer, &mut ctx, Color::rgb(1.0, 0.0, 0.0));
+
+        // a nested clip to the right half lasts until restore
+        ctx.save();
+        ctx.begin_path();
+        ctx.rect((50.0, 0.0, 50.0, 100.0));
+        ctx.clip_path();
+        fill_all(&mut renderer, &mut ctx, Color::rgb(0.0, 0.0, 1.0));
+        ctx.restore();
+        ctx.end_frame(&mut renderer).unwrap();
+
+        assert_eq!(pixel(&renderer, 40, 50), [255, 0, 0, 255]);
+        assert_eq!(pixel(&renderer, 60, 50), [0, 0, 255, 255]);
+        // inside the rect, outside of the circle
+        for (x, y) in [(5, 5), (35, 35), (65, 65), (50, 75), (90, 50)] {
+            assert_eq!(pixel(&renderer, x, y), [0, 0, 0, 0], "{} {}", x, y);
+        }
+
+        // after restore only the circle clips again
+        ctx.begin_frame(&mut renderer, None).unwrap();
+        ctx.begin_path();
+        ctx.circle((50.0, 50.0), 20.0);
+        ctx.clip_path();
+        ctx.save();
+        ctx.begin_path();
+        ctx.rect((50.0, 0.0, 50.0, 100.0));
+        ctx.clip_path();
+        ctx.restore();
+        fill_all(&mut renderer, &mut ctx, Color::rgb(0.0, 1.0, 0.0));
+        ctx.end_frame(&mut renderer).unwrap();
+        assert_eq!(pixel(&renderer, 40, 50), [0, 255, 0, 255]);
+        assert_eq!(pixel(&renderer, 35, 35), [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn export_path_lists_the_rounded_rect_segments() {
+        let (_, mut ctx) = setup(8, 8);
+        ctx.translate(100.0, 200.0);
+        ctx.begin_path();
+        ctx.rounded_rect((10.0, 10.0, 40.0, 30.0), 5.0);
+        let segments = ctx.export_path();
+
+        let kinds: Vec<&str> = segments
+            .iter()
+            .map(|s| match s {
+                PathSegment::MoveTo(_) => "M",
+                PathSegment::LineTo(_) => "L",
+                PathSegment::CubicTo(..) => "C",
+                PathSegment::Close => "Z",
+            })
+            .collect();
+        assert_eq!(kinds, ["M", "L", "C", "L", "C", "L", "C", "L", "C", "Z"]);
+
+        // in user space, despite the translation
+        let close_to =
+            |a: Point, b: (f32, f32)| (a.x - b.0).abs() < 1e-3 && (a.y - b.1).abs() < 1e-3;
+        assert!(matches!(segments[0], PathSegment::MoveTo(pt) if close_to(pt, (10.0, 15.0))));
+        assert!(matches!(segments[1], PathSegment::LineTo(pt) if close_to(pt, (10.0, 35.0))));
+        assert!(
+            matches!(segments[2], PathSegment::CubicTo(_, _, pt) if close_to(pt, (15.0, 40.0)))
+        );
+        assert!(matches!(segments[5], PathSegment::LineTo(pt) if close_to(pt, (50.0, 15.0))));
+        assert!(
+            matches!(segments[8], PathSegment::CubicTo(_, _, pt) if close_to(pt, (10.0, 15.0)))
+        );
+    }
+
+    #[test]
+    fn glyph_outline_fill_leaves_the_hole_of_o_empty() {
+        let (mut renderer, mut ctx) = setup(120, 120);
+        let font = roboto(&mut ctx);
+        ctx.translate(10.0, 100.0);
+        ctx.fill_paint(Color::rgb(1.0, 1.0, 1.0));
+        ctx.fill_glyph_outline(&mut renderer, font, 'O', 100.0)
+            .unwrap();
+        let bounds = ctx.cache.bounds;
+        ctx.end_frame(&mut renderer).unwrap();
+
+        let mid_x = ((bounds.min.x + bounds.max.x) * 0.5) as usize;
+        let mid_y = ((bounds.min.y + bounds.max.y) * 0.5) as usize;
+        assert!(bounds.height() > 40.0, "{:?}", bounds);
+        // the hole in the middle
+        assert_eq!(pixel(&renderer, mid_x, mid_y), [0, 0, 0, 0]);
+        // the ring left, right, above and below of it
+        for (x, y) in [
+            (bounds.min.x as usize + 4, mid_y),
+            (bounds.max.x as usize - 4, mid_y),
+            (mid_x, bounds.min.y as usize + 4),
+            (mid_x, bounds.max.y as usize - 4),
+        ] {
+            assert_eq!(pixel(&renderer, x, y), [255, 255, 255, 255], "{} {}", x, y);
+        }
+    }
+
+    #[test]
+    fn stroke_to_fill_of_a_line_is_a_closed_quad() {
+        let (_, mut ctx) = setup(8, 8);
+        ctx.begin_path();
+        ctx.move_to((10.0, 20.0));
+        ctx.line_to((50.0, 20.0));
+        ctx.stroke_width(6.0);
+        let segments = ctx.stroke_to_fill();
+        assert_eq!(segments.len(), 5);
+        assert!(matches!(segments[0], PathSegment::MoveTo(_)));
+        assert_eq!(segments[4], PathSegment::Close);
+
+        let mut pts: Vec<(i32, i32)> = outline_points(&segments)
+            .iter()
+            .map(|pt| (pt.x.round() as i32, pt.y.round() as i32))
+            .collect();
+        pts.sort();
+        assert_eq!(pts, [(10, 17), (10, 23), (50, 17), (50, 23)]);
+
+        // a closed path gives an outer and an inner contour
+        ctx.begin_path();
+        ctx.rect((10.0, 10.0, 20.0, 20.0));
+        let closes = ctx
+            .stroke_to_fill()
+            .iter()
+            .filter(|s| **s == PathSegment::Close)
+            .count();
+        assert_eq!(closes, 2);
+    }
+
+    #[test]
+    fn transform_point_round_trips_under_translate_and_scale() {
+        let (_, mut ctx) = setup(8, 8);
+        ctx.translate(10.0, 20.0);
+        ctx.scale(2.0, 4.0);
+        assert_eq!(ctx.transform_point((3.0, 5.0)), Point::new(16.0, 40.0));
+        assert_eq!(
+            ctx.inverse_transform_point((16.0, 40.0)),
+            Point::new(3.0, 5.0)
+        );
+
+        ctx.rotate(0.7);
+        let pt = Point::new(-7.0, 11.0);
+        let back = ctx.inverse_transform_point(ctx.transform_point(pt));
+        assert!((back - pt).length() < 1e-4, "{:?}", back);
+    }
+
+    #[test]
+    fn global_alpha_halves_image_paints() {
+        let (mut renderer, mut ctx) = recorder(8, 8);
+        let img = ctx
+            .create_image(
+                &mut renderer,
+                ImageFlags::empty(),
+                png(8, 8, [255, 255, 255, 255]),
+            )
+            .unwrap();
+        let mut paint = ctx.image_pattern((0.0, 0.0), (8.0, 8.0), 0.0, img, 1.0);
+        assert_eq!(paint.alpha(), 1.0);
+        paint.set_alpha(0.8);
+        assert_eq!((paint.inner_color.a, paint.outer_color.a), (0.8, 0.8));
+        paint.set_alpha(1.0);
+
+        ctx.global_alpha(0.5);
+        assert_eq!(ctx.current_alpha(), 0.5);
+        ctx.begin_path();
+        ctx.rect((0.0, 0.0, 8.0, 8.0));
+        ctx.fill_paint(paint);
+        ctx.fill(&mut renderer).unwrap();
+        ctx.end_frame(&mut renderer).unwrap();
+
+        let (drawn, _) = renderer.paths.last().unwrap();
+        assert_eq!(drawn.image, Some(img));
+        assert_eq!(drawn.alpha(), 0.5);
+        let alpha = pixel(&renderer.inner, 4, 4)[3];
+        assert!((127..=128).contains(&alpha), "{}", alpha);
+    }
+
+    #[test]
+    fn pixel_snap_centers_a_1px_line_on_the_pixel_row() {
+        let (mut renderer, mut ctx) = setup(60, 40);
+        ctx.pixel_snap(true);
+        ctx.begin_path();
+        ctx.move_to((10.3, 20.2));
+        ctx.line_to((50.7, 20.2));
+        ctx.stroke_width(1.0);
+        ctx.stroke(&mut renderer).unwrap();
+
+        for pt in &ctx.cache.points {
+            assert_eq!(pt.xy.y.fract(), 0.5, "{:?}", pt.xy);
+        }
+        let ys: Vec<f32> = ctx.cache.paths[0]
+            .get_stroke()
+            .iter()
+            .map(|v| v.y)
+            .collect();
+        let min = ys.iter().cloned().fold(f32::MAX, f32::min);
+        let max = ys.iter().cloned().fold(f32::MIN, f32::max);
+        assert!(((min + max) * 0.5 - 20.5).abs() < 1e-4, "{} {}", min, max);
+    }
+
+    #[test]
+    fn pixel_snap_merges_points_landing_on_the_same_center() {
+        let (mut renderer, mut ctx) = setup(40, 40);
+        ctx.pixel_snap(true);
+        ctx.begin_path();
+        ctx.circle((10.2, 10.2), 3.0);
+        ctx.move_to((20.0, 20.0));
+        ctx.line_to((20.2, 20.1));
+        ctx.line_to((30.0, 20.0));
+        ctx.stroke(&mut renderer).unwrap();
+
+        for path in &ctx.cache.paths {
+            let pts = &ctx.cache.points[path.first..path.first + path.count];
+            for pair in pts.windows(2) {
+                assert!(pair[0].xy != pair[1].xy, "{:?}", pair[0].xy);
+            }
+            assert!(path
+                .get_stroke()
+                .iter()
+                .all(|v| v.x.is_finite() && v.y.is_finite()));
+        }
+        assert_eq!(ctx.cache.paths[1].count, 2);
+    }
+
+    #[test]
+    fn gamma_correct_interpolates_and_blends_in_linear_light() {
+        let render = |gamma_correct: bool| {
+            let mut renderer = SoftwareRenderer::new(100, 4);
+            let options = ContextOptions {
+                gamma_correct,
+                ..Default::default()
+            };
+            let mut ctx = Context::create_with_options(&mut renderer, options).unwrap();
+            ctx.begin_frame(&mut renderer, Some(Color::rgb(0.0, 0.0, 0.0)))
+                .unwrap();
+            ctx.begin_path();
+            ctx.rect((0.0, 0.0, 100.0, 2.0));
+            ctx.fill_paint(Gradient::Linear {
+                start: Point::new(0.0, 0.0),
+                end: Point::new(100.0, 0.0),
+                start_color: Color::rgb(0.0, 0.0, 0.0),
+                end_color: Color::rgb(1.0, 1.0, 1.0),
+            });
+            ctx.fill(&mut renderer).unwrap();
+            ctx.begin_path();
+            ctx.rect((0.0, 2.0, 100.0, 2.0));
+            ctx.fill_paint(Color::rgba(1.0, 1.0, 1.0, 0.5));
+            ctx.fill(&mut renderer).unwrap();
+            ctx.end_frame(&mut renderer).unwrap();
+            (pixel(&renderer, 50, 0)[0], pixel(&renderer, 50, 3)[0])
+        };
+
+        // in sRGB the midpoints get half the encoded value, in linear light half the intensity
+        let (gradient, blend) = render(false);
+        assert!((127..=130).contains(&gradient), "{}", gradient);
+        assert!((127..=129).contains(&blend), "{}", blend);
+        let (gradient, blend) = render(true);
+        assert!((185..=190).contains(&gradient), "{}", gradient);
+        assert!((185..=190).contains(&blend), "{}", blend);
+    }
+
+    #[test]
+    fn save_count_follows_save_and_restore() {
+        let (_, mut ctx) = setup(8, 8);
+        assert_eq!(ctx.save_count(), 1);
+        ctx.save();
+        ctx.save();
+        assert_eq!(ctx.save_count(), 3);
+        ctx.restore();
+        assert_eq!(ctx.save_count(), 2);
+        ctx.restore();
+        assert_eq!(ctx.save_count(), 1);
+
+        // over-restoring keeps the base state and is reported by try_restore
+        ctx.restore();
+        assert_eq!(ctx.save_count(), 1);
+        assert!(!ctx.try_restore());
+        assert_eq!(ctx.save_count(), 1);
+    }
+
+    #[test]
+    fn box_shadow_fades_out_over_the_feather() {
+        let (mut renderer, mut ctx) = setup(100, 100);
+        ctx.box_shadow(
+            &mut renderer,
+            (30.0, 30.0, 40.0, 40.0),
+            0.0,
+            10.0,
+            Color::rgb(0.0, 0.0, 0.0),
+        )
+        .unwrap();
+        ctx.end_frame(&mut renderer).unwrap();
+
+        let alpha = |x: usize| pixel(&renderer, x, 50)[3];
+        // the inside is left out
+        assert_eq!(alpha(50), 0);
+        // half way at the rect edge, decreasing outwards and gone past half the feather
+        assert!((100..=155).contains(&alpha(70)), "{}", alpha(70));
+        for x in 70..74 {
+            assert!(alpha(x + 1) < alpha(x), "{} {}", x, alpha(x));
+        }
+        assert_eq!(alpha(76), 0);
+    }
+
+    #[test]
+    fn rotate_and_scale_about_keep_the_pivot_in_place() {
+        let (_, mut ctx) = setup(8, 8);
+        let close = |a: Point, x: f32, y: f32| (a - Point::new(x, y)).length() < 1e-4;
+
+        ctx.rotate_about(PI * 0.5, (10.0, 10.0));
+        assert!(close(ctx.transform_point((10.0, 10.0)), 10.0, 10.0));
+        // a quarter turn clockwise in screen space, y pointing down
+        let pt = ctx.transform_point((20.0, 10.0));
+        assert!(close(pt, 10.0, 20.0), "{:?}", pt);
+
+        ctx.reset_transform();
+        ctx.scale_about(2.0, 3.0, (10.0, 10.0));
+        assert!(close(ctx.transform_point((10.0, 10.0)), 10.0, 10.0));
+        let pt = ctx.transform_point((12.0, 11.0));
+        assert!(close(pt, 14.0, 13.0), "{:?}", pt);
+    }
+
+    #[test]
+    fn translate_then_rotate_matches_nanovg() {
+        let (_, mut ctx) = setup(8, 8);
+        let (s, c) = 0.3f32.sin_cos();
+        ctx.translate(10.0, 20.0);
+        ctx.rotate(0.3);
+        // nvgTranslate(10, 20) then nvgRotate(0.3) leave this matrix
+        let expected = [c, s, -s, c, 10.0, 20.0];
+        let xform = ctx.current_transform();
+        for (a, b) in xform.0.iter().zip(&expected) {
+            assert!((a - b).abs() < 1e-5, "{:?}", xform);
+        }
+
+        // the same through pre_transform
+        ctx.reset_transform();
+        ctx.pre_transform(Transform::translate(10.0, 20.0));
+        ctx.pre_transform(Transform::rotate(0.3));
+        assert_eq!(ctx.current_transform().0, xform.0);
+
+        // post_transform acts in screen space, after the scale
+        ctx.reset_transform();
+        ctx.scale(2.0, 2.0);
+        ctx.post_transform(Transform::translate(5.0, 0.0));
+        assert_eq!(ctx.transform_point((1.0, 1.0)), Point::new(7.0, 2.0));
+        ctx.reset_transform();
+        ctx.scale(2.0, 2.0);
+        ctx.pre_transform(Transform::translate(5.0, 0.0));
+        assert_eq!(ctx.transform_point((1.0, 1.0)), Point::new(12.0, 2.0));
+    }
+
+    #[test]
+    fn attach_renderer_runs_a_once_closure_and_returns_its_result() {
+        let (mut renderer, mut ctx) = setup(20, 20);
+        let label = String::from("moved into the closure");
+        let res = ctx.attach_renderer(&mut renderer, move |canvas| {
+            canvas.begin_path();
+            canvas.rect((0.0, 0.0, 10.0, 10.0));
+            canvas.fill_paint(Color::rgb(0.0, 1.0, 0.0));
+            canvas.fill().map(|_| label)
+        });
+        assert_eq!(res.unwrap(), "moved into the closure");
+
+        // the renderer is returned to the caller and keeps what the canvas drew
+        ctx.end_frame(&mut renderer).unwrap();
+        assert_eq!(pixel(&renderer, 5, 5), [0, 255, 0, 255]);
+        assert_eq!(pixel(&renderer, 15, 15), [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn text_size_hits_the_measure_cache() {
+        let (_, mut ctx) = setup(8, 8);
+        roboto(&mut ctx);
+        ctx.font_size(20.0);
+        let first = ctx.text_size("Static label");
+        assert_eq!(ctx.fonts.measure_cache_misses(), 1);
+        let second = ctx.text_size("Static label");
+        assert_eq!(ctx.fonts.measure_cache_misses(), 1);
+        assert_eq!(first.width, second.width);
+
+        // a different size is measured again
+        ctx.font_size(30.0);
+        ctx.text_size("Static label");
+        assert_eq!(ctx.fonts.measure_cache_misses(), 2);
+
+        // adding a font or clearing the cache invalidates it
+        ctx.font_size(20.0);
+        ctx.clear_text_measure_cache();
+        ctx.text_size("Static label");
+        assert_eq!(ctx.fonts.measure_cache_misses(), 3);
+        roboto(&mut ctx);
+        ctx.text_size("Static label");
+        assert_eq!(ctx.fonts.measure_cache_misses(), 4);
+    }
+
+    #[cfg(feature = "bidi")]
+    #[test]
+    fn right_to_left_base_direction_reorders_the_drawn_runs() {
+        let quads = |text: &str, direction: BaseDirection| {
+            let (mut renderer, mut ctx) = recorder(200, 50);
+            roboto(&mut ctx);
+            ctx.font_size(20.0);
+            ctx.base_direction(direction);
+            ctx.text(&mut renderer, (10.0, 30.0), text).unwrap();
+            renderer.triangles.last().unwrap().1.clone()
+        };
+        let rtl = quads("12 ab", BaseDirection::RightToLeft);
+        // the letters are placed left of the number run, each keeping its own order
+        assert_eq!(rtl, quads("ab 12", BaseDirection::LeftToRight));
+        assert_ne!(rtl, quads("12 ab", BaseDirection::LeftToRight));
+    }
+
+    #[test]
+    fn combining_accent_overlaps_its_base_glyph() {
+        let quads = |text: &str| {
+            let (mut renderer, mut ctx) = recorder(200, 50);
+            roboto(&mut ctx);
+            ctx.font_size(30.0);
+            ctx.text(&mut renderer, (10.0, 40.0), text).unwrap();
+            let (_, vertexes) = renderer.triangles.last().unwrap();
+            let quads: Vec<(f32, f32)> = vertexes
+                .chunks(6)
+                .map(|quad| (quad[0].x, quad[1].x))
+                .collect();
+            (quads, ctx.text_size(text).width)
+        };
+        let (plain, plain_width) = quads("ex");
+        let (accented, accented_width) = quads("e\u{301}x");
+        assert_eq!(accented.len(), 3);
+
+        // the accent sits over the e, and the x doesn't move
+        let (e, accent, x) = (accented[0], accented[1], accented[2]);
+        assert!(accent.0 < e.1 && accent.1 > e.0, "{:?} {:?}", e, accent);
+        assert_eq!(x, plain[1]);
+        assert_eq!(accented_width, plain_width);
+    }
+
+    #[test]
+    fn tab_advances_to_the_next_tab_stop() {
+        let b_left = |text: &str, tab_size: f32| {
+            let (mut renderer, mut ctx) = recorder(300, 50);
+            roboto(&mut ctx);
+            ctx.font_size(20.0);
+            ctx.text_tab_size(tab_size);
+            ctx.text(&mut renderer, (10.0, 30.0), text).unwrap();
+            let (_, vertexes) = renderer.triangles.last().unwrap();
+            vertexes[vertexes.len() - 6].x
+        };
+        // b starts at the same stop whatever the width before the tab
+        let after_a = b_left("a\tb", 50.0);
+        assert_eq!(after_a, b_left("WW\tb", 50.0));
+        assert_eq!(after_a, b_left("\tb", 50.0));
+        // the next stop once the text passes the first one
+        assert_eq!(b_left("WWWW\tb", 50.0), after_a + 50.0);
+        assert_eq!(b_left("a\tb", 80.0), after_a + 30.0);
+    }
+
+    #[test]
+    fn fill_rect_is_two_triangles_matching_a_filled_rect_path() {
+        let (mut recorder, mut ctx) = recorder(40, 40);
+        ctx.fill_rect(
+            &mut recorder,
+            (5.0, 6.0, 20.0, 10.0),
+            Color::rgb(1.0, 0.0, 0.0),
+        )
+        .unwrap();
+        assert!(recorder.paths.is_empty());
+        assert_eq!(recorder.triangles.len(), 1);
+        assert_eq!(recorder.triangles[0].1.len(), 6);
+
+        let (mut direct, mut ctx) = setup(40, 40);
+        ctx.fill_rect(
+            &mut direct,
+            (5.0, 6.0, 20.0, 10.0),
+            Color::rgb(1.0, 0.0, 0.0),
+        )
+        .unwrap();
+        ctx.end_frame(&mut direct).unwrap();
+
+        let (mut path, mut ctx) = setup(40, 40);
+        ctx.begin_path();
+        ctx.rect((5.0, 6.0, 20.0, 10.0));
+        ctx.fill_paint(Color::rgb(1.0, 0.0, 0.0));
+        ctx.fill(&mut path).unwrap();
+        ctx.end_frame(&mut path).unwrap();
+
+        assert_eq!(pixel(&direct, 5, 6), [255, 0, 0, 255]);
+        assert_eq!(direct.pixels(), path.pixels());
+    }
+
+    #[test]
+    fn clear_rect_erases_inside_the_scissor_only() {
+        let (mut renderer, mut ctx) = setup(40, 40);
+        ctx.fill_rect(
+            &mut renderer,
+            (0.0, 0.0, 40.0, 40.0),
+            Color::rgb(1.0, 1.0, 1.0),
+        )
+        .unwrap();
+        ctx.global_alpha(0.5);
+        ctx.clear_rect(
+            &mut renderer,
+            (10.0, 10.0, 20.0, 20.0),
+            Color::rgba(0.0, 0.0, 0.0, 0.0),
+        )
+        .unwrap();
+
+        // the scissor limits clearing to its left half
+        ctx.scissor((0.0, 0.0, 20.0, 40.0));
+        ctx.clear_rect(
+            &mut renderer,
+            (0.0, 32.0, 40.0, 8.0),
+            Color::rgb(0.0, 0.0, 1.0),
+        )
+        .unwrap();
+        ctx.end_frame(&mut renderer).unwrap();
+
+        assert_eq!(pixel(&renderer, 20, 20), [0, 0, 0, 0]);
+        assert_eq!(pixel(&renderer, 10, 10), [0, 0, 0, 0]);
+        assert_eq!(pixel(&renderer, 5, 20), [255, 255, 255, 255]);
+        assert_eq!(pixel(&renderer, 30, 20), [255, 255, 255, 255]);
+        assert_eq!(pixel(&renderer, 10, 35), [0, 0, 255, 255]);
+        assert_eq!(pixel(&renderer, 30, 35), [255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn point_in_stroke_covers_the_stroke_width_only() {
+        let (_, mut ctx) = setup(8, 8);
+        ctx.begin_path();
+        ctx.move_to((10.0, 20.0));
+        ctx.line_to((50.0, 20.0));
+        ctx.stroke_width(6.0);
+
+        // on the line, just inside the half width, just outside of it
+        assert!(ctx.point_in_stroke((30.0, 20.0)));
+        assert!(ctx.point_in_stroke((30.0, 22.9)));
+        assert!(ctx.point_in_stroke((30.0, 17.1)));
+        assert!(!ctx.point_in_stroke((30.0, 23.1)));
+        assert!(!ctx.point_in_stroke((30.0, 16.9)));
+        // a corner of the outline counts as on it
+        assert!(ctx.point_in_stroke((10.0, 17.0)));
+
+        // past the end only the square cap reaches
+        assert!(!ctx.point_in_stroke((52.0, 20.0)));
+        ctx.line_cap(LineCap::Square);
+        assert!(ctx.point_in_stroke((52.0, 20.0)));
+        assert!(!ctx.point_in_stroke((53.1, 20.0)));
+    }
+
+    #[test]
+    fn coarse_tessellation_tolerance_flattens_a_circle_to_fewer_points() {
+        let circle_points = |tolerance: Option<f32>| {
+            let (mut renderer, mut ctx) = setup(100, 100);
+            ctx.begin_frame(&mut renderer, None).unwrap();
+            if let Some(tolerance) = tolerance {
+                ctx.tessellation_tolerance(tolerance);
+            }
+            ctx.begin_path();
+            ctx.circle((50.0, 50.0), 40.0);
+            ctx.fill(&mut renderer).unwrap();
+            ctx.cache.paths[0].count
+        };
+        let default = circle_points(None);
+        let coarse = circle_points(Some(8.0));
+        assert!(coarse < default, "{} vs {}", coarse, default);
+        // a finer tolerance than the default only adds points
+        assert!(circle_points(Some(0.01)) > default);
+    }
+
+    #[test]
+    fn tiny_round_caps_and_joins_keep_the_vertex_count_bounded() {
+        let (mut renderer, mut ctx) = recorder(100, 100);
+        ctx.tessellation_tolerance(1e-6);
+        ctx.stroke_width(0.01);
+        ctx.line_cap(LineCap::Round);
+        ctx.line_join(LineJoin::Round);
+        ctx.begin_path();
+        ctx.move_to((10.0, 10.0));
+        ctx.line_to((50.0, 10.0));
+        ctx.line_to((50.0, 50.0));
+        ctx.stroke(&mut renderer).unwrap();
+
+        let (_, vertexes) = renderer.paths.last().unwrap();
+        // two caps and a join, each of at most 64 divisions with a few vertexes apiece
+        assert!(!vertexes.is_empty());
+        assert!(vertexes.len() < 1000, "{} vertexes", vertexes.len());
+    }
+
+    #[test]
+    fn growing_the_glyph_atlas_keeps_the_lines_laid_out_before() {
+        let draw = |atlas_size: usize| {
+            let mut renderer = SoftwareRenderer::new(200, 200);
+            let options = ContextOptions {
+                font_atlas_width: atlas_size,
+                font_atlas_height: atlas_size,
+                ..Default::default()
+            };
+            let mut ctx = Context::create_with_options(&mut renderer, options).unwrap();
+            ctx.begin_frame(&mut renderer, Some(Color::rgba(0.0, 0.0, 0.0, 0.0)))
+                .unwrap();
+            roboto(&mut ctx);
+            ctx.font_size(30.0);
+            ctx.fill_paint(Color::rgb(0.0, 1.0, 0.0));
+            let first_atlas = ctx.fonts.img;
+            ctx.text(&mut renderer, (10.0, 60.0), "ab\nWM").unwrap();
+            if atlas_size < 1024 {
+                // the atlas grew, the old one lives until the frame is flushed
+                assert_ne!(ctx.fonts.img, first_atlas);
+                assert!(renderer.texture_size(first_atlas).is_ok());
+            }
+            ctx.end_frame(&mut renderer).unwrap();
+            if atlas_size < 1024 {
+                assert!(renderer.texture_size(first_atlas).is_err());
+            }
+
+            let green_in_rows = |rows: std::ops::Range<usize>| {
+                rows.flat_map(|y| (0..200).map(move |x| (x, y)))
+                    .filter(|&(x, y)| pixel(&renderer, x, y)[1] > 0)
+                    .count()
+            };
+            (green_in_rows(0..70), green_in_rows(70..200))
+        };
+
+        let grown = draw(32);
+        assert!(grown.0 > 0 && grown.1 > 0, "{:?}", grown);
+        assert_eq!(grown, draw(1024));
+    }
+
+    #[test]
+    fn growing_the_glyph_atlas_keeps_the_clip() {
+        let mut renderer = SoftwareRenderer::new(100, 100);
+        let options = ContextOptions {
+            font_atlas_width: 16,
+            font_atlas_height: 16,
+            ..Default::default()
+        };
+        let mut ctx = Context::create_with_options(&mut renderer, options).unwrap();
+        ctx.begin_frame(&mut renderer, Some(Color::rgba(0.0, 0.0, 0.0, 0.0)))
+            .unwrap();
+        roboto(&mut ctx);
+        ctx.begin_path();
+        ctx.rect((20.0, 20.0, 20.0, 20.0));
+        ctx.clip_path();
+        ctx.fill_paint(Color::rgb(1.0, 1.0, 1.0));
+        // applies the clip before the atlas grows
+        ctx.font_size(8.0);
+        ctx.text(&mut renderer, (22.0, 30.0), "i").unwrap();
+        let first_atlas = ctx.fonts.img;
+        ctx.font_size(60.0);
+        ctx.text(&mut renderer, (0.0, 60.0), "HW\nMH").unwrap();
+        assert_ne!(ctx.fonts.img, first_atlas);
+        ctx.text(&mut renderer, (0.0, 90.0), "WW").unwrap();
+        ctx.end_frame(&mut renderer).unwrap();
+
+        let (mut inside, mut outside) = (0, 0);
+        for y in 0..100 {
+            for x in 0..100 {
+                if pixel(&renderer, x, y)[3] > 0 {
+                    if (20..40).contains(&x) && (20..40).contains(&y) {
+                        inside += 1;
+                    } else {
+                        outside += 1;
+                    }
+                }
+            }
+        }
+        assert!(inside > 0);
+        assert_eq!(outside, 0);
+    }
+
+    #[test]
+    fn u32_index_backends_fill_paths_of_over_65k_vertexes() {
+        let (mut renderer, mut ctx) = recorder(20, 20);
+        assert!(renderer.caps().supports_u32_indices);
+        let count = 70_000;
+        // a star alternating between two radii, so no points are merged as too close
+        ctx.begin_path();
+        ctx.move_to((10.0, 2.0));
+        for i in 1..count {
+            let angle = i as f32 / count as f32 * std::f32::consts::PI * 2.0;
+            let radius = if i % 2 == 0 { 8.0 } else { 6.0 };
+            ctx.line_to((10.0 + radius * angle.sin(), 10.0 - radius * angle.cos()));
+        }
+        ctx.close_path();
+        ctx.fill_paint(Color::rgb(1.0, 1.0, 1.0));
+        ctx.fill(&mut renderer).unwrap();
+        ctx.end_frame(&mut renderer).unwrap();
+
+        let (_, vertexes) = renderer.paths.last().unwrap();
+        assert!(vertexes.len() > u16::MAX as usize, "{}", vertexes.len());
+        assert_eq!(pixel(&renderer.inner, 10, 10), [255, 255, 255, 255]);
+        assert_eq!(pixel(&renderer.inner, 10, 14), [255, 255, 255, 255]);
+        assert_eq!(pixel(&renderer.inner, 1, 1)[3], 0);
+    }
+
+    #[cfg(feature = "bidi")]
+    #[test]
+    fn carets_and_measurements_follow_the_reordered_text() {
+        let (_, mut ctx) = setup(8, 8);
+        roboto(&mut ctx);
+        ctx.font_size(20.0);
+        ctx.base_direction(BaseDirection::LeftToRight);
+        let ab = ctx.text_size("ab ").width;
+        let visual = ctx.text_size("ab 12").width;
+        let logical = ctx.text_size("12 ab").width;
+
+        // "12 ab" is drawn as "ab 12" right to left, so "1" is right of "ab "
+        ctx.base_direction(BaseDirection::RightToLeft);
+        assert_eq!(ctx.text_size("12 ab").width, visual);
+        assert!((ctx.caret_rect((10.0, 0.0), "12 ab", 0).xy.x - (10.0 + ab)).abs() < 1e-4);
+        assert!((ctx.caret_rect((10.0, 0.0), "12 ab", 3).xy.x - 10.0).abs() < 1e-4);
+        let lines = ctx.text_break_lines("12 ab", 1000.0);
+        assert_eq!(lines.len(), 1);
+        assert!((lines[0].2 - visual).abs() < 1e-4);
+
+        ctx.base_direction(BaseDirection::LeftToRight);
+        assert!((ctx.caret_rect((10.0, 0.0), "12 ab", 3).xy.x - 10.0).abs() > 1.0);
+        assert!((ctx.text_break_lines("12 ab", 1000.0)[0].2 - logical).abs() < 1e-4);
+    }
+
+    #[test]
+    fn replay_repeats_rects_triangles_and_cached_and_path_text() {
+        let (mut renderer, mut ctx) = recorder(200, 100);
+        roboto(&mut ctx);
+        ctx.font_size(20.0);
+        let cached = ctx.prepare_text(&mut renderer, "Hi").unwrap();
+        let list = ctx
+            .record(|ctx| {
+                ctx.fill_rect(
+                    &mut renderer,
+                    (10.0, 10.0, 20.0, 20.0),
+                    Color::rgb(1.0, 0.0, 0.0),
+                )?;
+                ctx.clear_rect(
+                    &mut renderer,
+                    (15.0, 15.0, 5.0, 5.0),
+                    Color::rgba(0.0, 0.0, 0.0, 0.0),
+                )?;
+                let vertices = [
+                    Vertex::new(50.0, 10.0, 0.5, 1.0),
+                    Vertex::new(50.0, 30.0, 0.5, 1.0),
+                    Vertex::new(70.0, 30.0, 0.5, 1.0),
+                ];
+                ctx.draw_triangles(&mut renderer, Color::rgb(0.0, 1.0, 0.0).into(), &vertices)?;
+                ctx.draw_cached_text(&mut renderer, &cached, (10.0, 80.0))?;
+                let path = [Point::new(100.0, 80.0), Point::new(190.0, 60.0)];
+                ctx.text_on_path(&mut renderer, &path, "Hi")
+            })
+            .unwrap();
+        assert_eq!(list.len(), 5);
+        let recorded = std::mem::take(&mut renderer.triangles);
+        assert_eq!(recorded.len(), 5);
+
+        ctx.replay(&mut renderer, &list).unwrap();
+        assert_eq!(renderer.triangles.len(), 5);
+        for (a, b) in recorded.iter().zip(&renderer.triangles) {
+            assert_eq!(a.0.inner_color.r, b.0.inner_color.r);
+            assert_eq!(a.1.len(), b.1.len());
+            for (va, vb) in a.1.iter().zip(&b.1) {
+                assert!((va.x - vb.x).abs() < 1e-4 && (va.y - vb.y).abs() < 1e-4);
+            }
+        }
     }
 }
@@ -10,4 +10,16 @@ pub enum NonaError {
 
     #[error("ERR_FONT: {0}")]
     Font(String),
+
+    #[error("ERR_IMAGE: {0}")]
+    Image(String),
+
+    #[error("ERR_SVG: {0}")]
+    Svg(String),
+
+    #[error("ERR_GEOMETRY: {0}")]
+    Geometry(String),
+
+    #[error("ERR_UNSUPPORTED: {0}")]
+    Unsupported(String),
 }
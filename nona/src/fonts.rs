@@ -1,19 +1,18 @@
-use crate::context::{ImageId, TextMetrics};
+use crate::context::{ImageId, PathSegment, TextMetrics};
 use crate::renderer::TextureType;
 use crate::{Align, Bounds, Extent, ImageFlags, NonaError, Renderer};
 use bitflags::_core::borrow::Borrow;
-use rusttype::gpu_cache::Cache;
-use rusttype::{Font, Glyph, Point, PositionedGlyph, Scale};
+use rusttype::gpu_cache::{Cache, CacheWriteErr};
+use rusttype::{Font, Glyph, OutlineBuilder, Point, PositionedGlyph, Scale};
 use slab::Slab;
 use std::{
-    collections::HashMap,
+    cell::RefCell,
+    collections::{hash_map::DefaultHasher, HashMap, HashSet},
     error::Error,
     fmt::{Debug, Display},
+    hash::{Hash, Hasher},
 };
 
-const TEX_WIDTH: usize = 1024;
-const TEX_HEIGHT: usize = 1024;
-
 pub type FontId = usize;
 
 #[derive(Debug)]
@@ -28,17 +27,53 @@ pub struct LayoutChar {
     pub bounds: Bounds,
 }
 
+/// Where and how `Fonts::layout_text` places glyphs, in device pixels
+#[derive(Debug, Copy, Clone)]
+pub struct TextLayout {
+    pub font_id: FontId,
+    pub position: crate::Point,
+    pub scale: Scale,
+    pub align: Align,
+    pub spacing: f32,
+    pub tab_size: f32,
+}
+
 #[derive(Debug)]
 struct FontData {
+    name: String,
     font: Font<'static>,
     fallback_fonts: Vec<FontId>,
 }
 
+/// Number of `text_size` results kept, least recently used ones are dropped first
+const MEASURE_CACHE_SIZE: usize = 512;
+
+/// Width and height of a text measured by `Fonts::text_size`, keyed by a hash of its inputs
+#[derive(Default)]
+struct MeasureCache {
+    entries: HashMap<u64, MeasureEntry>,
+    tick: u64,
+    /// Number of texts measured because they were not cached
+    misses: u64,
+}
+
+struct MeasureEntry {
+    text: String,
+    params: (FontId, u32, u32, u32, u32),
+    extent: Extent,
+    last_used: u64,
+}
+
 pub struct Fonts {
     fonts: Slab<FontData>,
     fonts_by_name: HashMap<String, FontId>,
     cache: Cache<'static>,
+    measure_cache: RefCell<MeasureCache>,
     pub(crate) img: ImageId,
+    /// Atlases replaced by a bigger one, kept until the end of the frame for the glyphs
+    /// already drawn from them
+    retired_imgs: Vec<ImageId>,
+    notdef: bool,
 }
 
 impl Debug for Fonts {
@@ -64,21 +99,28 @@ impl Error for FontError {
 }
 
 impl Fonts {
-    pub fn new<R: Renderer>(renderer: &mut R) -> Result<Fonts, NonaError> {
+    pub fn new<R: Renderer>(
+        renderer: &mut R,
+        width: usize,
+        height: usize,
+    ) -> Result<Fonts, NonaError> {
         Ok(Fonts {
             fonts: Default::default(),
             fonts_by_name: Default::default(),
             img: renderer.create_texture(
                 TextureType::Alpha,
-                TEX_WIDTH,
-                TEX_HEIGHT,
+                width,
+                height,
                 ImageFlags::empty(),
                 None,
             )?,
             cache: Cache::builder()
                 .multithread(true)
-                .dimensions(TEX_WIDTH as u32, TEX_HEIGHT as u32)
+                .dimensions(width as u32, height as u32)
                 .build(),
+            measure_cache: Default::default(),
+            retired_imgs: Vec::new(),
+            notdef: false,
         })
     }
 
@@ -87,17 +129,36 @@ impl Fonts {
         name: N,
         data: D,
     ) -> Result<FontId, NonaError> {
-        let font = Font::try_from_vec(data.into())
-            .ok_or(NonaError::Font(String::from("Incorrect font data format")))?;
+        let data = data.into();
+        if data.is_empty() {
+            return Err(NonaError::Font(String::from("Font data is empty")));
+        }
+        let error = font_data_error(&data);
+        let font = Font::try_from_vec(data).ok_or(NonaError::Font(error))?;
+        let name = name.into();
         let fd = FontData {
+            name: name.clone(),
             font,
             fallback_fonts: Default::default(),
         };
         let id = self.fonts.insert(fd);
-        self.fonts_by_name.insert(name.into(), id);
+        self.fonts_by_name.insert(name, id);
+        self.clear_measure_cache();
         Ok(id)
     }
 
+    pub fn name(&self, id: FontId) -> Option<&str> {
+        self.fonts.get(id).map(|fd| fd.name.as_str())
+    }
+
+    /// All loaded fonts with their names, ordered by id
+    pub fn fonts(&self) -> Vec<(FontId, &str)> {
+        self.fonts
+            .iter()
+            .map(|(id, fd)| (id, fd.name.as_str()))
+            .collect()
+    }
+
     pub fn find<N: Borrow<str>>(&self, name: N) -> Option<FontId> {
         self.fonts_by_name.get(name.borrow()).map(ToOwned::to_owned)
     }
@@ -106,36 +167,93 @@ impl Fonts {
         if let Some(fd) = self.fonts.get_mut(base) {
             fd.fallback_fonts.push(fallback);
         }
+        self.clear_measure_cache();
     }
 
-    fn glyph(&self, id: FontId, c: char) -> Option<(FontId, Glyph<'static>)> {
-        if let Some(fd) = self.fonts.get(id) {
-            let glyph = fd.font.glyph(c);
-            if glyph.id().0 != 0 {
-                Some((id, glyph))
-            } else {
-                for id in &fd.fallback_fonts {
-                    if let Some(fd) = self.fonts.get(*id) {
-                        let glyph = fd.font.glyph(c);
-                        if glyph.id().0 != 0 {
-                            return Some((*id, glyph));
-                        }
-                    }
-                }
-                None
-            }
+    pub fn set_notdef(&mut self, enabled: bool) {
+        self.notdef = enabled;
+        self.clear_measure_cache();
+    }
+
+    pub fn clear_measure_cache(&mut self) {
+        self.measure_cache.get_mut().entries.clear();
+    }
+
+    /// Number of `text_size` calls that had to measure the text instead of hitting the cache
+    #[cfg(test)]
+    pub(crate) fn measure_cache_misses(&self) -> u64 {
+        self.measure_cache.borrow().misses
+    }
+
+    /// Distance between tab stops, `tab_size` or four spaces of the font when not positive
+    fn tab_width(&self, fd: &FontData, scale: Scale, tab_size: f32) -> f32 {
+        if tab_size > 0.0 {
+            tab_size
         } else {
-            None
+            fd.font.glyph(' ').scaled(scale).h_metrics().advance_width * 4.0
+        }
+    }
+
+    fn glyph(&self, id: FontId, c: char) -> Option<(FontId, Glyph<'static>)> {
+        let fd = self.fonts.get(id)?;
+        let glyph = fd.font.glyph(c);
+        if glyph.id().0 != 0 {
+            return Some((id, glyph));
+        }
+
+        let mut visited = HashSet::new();
+        visited.insert(id);
+        let fallback = fd
+            .fallback_fonts
+            .iter()
+            .find_map(|fallback| self.fallback_glyph(*fallback, c, &mut visited));
+
+        if fallback.is_none() && self.notdef && !c.is_control() && !extends_cluster(c) {
+            // glyph 0 is the font's .notdef box
+            return Some((id, glyph));
+        }
+        fallback
+    }
+
+    /// Searches `id` and then its fallbacks depth first, skipping fonts already visited so
+    /// fallback cycles terminate
+    fn fallback_glyph(
+        &self,
+        id: FontId,
+        c: char,
+        visited: &mut HashSet<FontId>,
+    ) -> Option<(FontId, Glyph<'static>)> {
+        if !visited.insert(id) {
+            return None;
         }
+        let fd = self.fonts.get(id)?;
+        let glyph = fd.font.glyph(c);
+        if glyph.id().0 != 0 {
+            return Some((id, glyph));
+        }
+        fd.fallback_fonts
+            .iter()
+            .find_map(|fallback| self.fallback_glyph(*fallback, c, visited))
+    }
+
+    /// Font each char of `text` is drawn with, after fallbacks. Chars no font has a glyph
+    /// for are left out.
+    pub fn coverage(&self, id: FontId, text: &str) -> Vec<(char, FontId)> {
+        text.chars()
+            .filter_map(|c| match self.glyph(id, c) {
+                Some((id, glyph)) if glyph.id().0 != 0 => Some((c, id)),
+                _ => None,
+            })
+            .collect()
     }
 
     fn render_texture<R: Renderer>(&mut self, renderer: &mut R) -> Result<(), NonaError> {
-        let img = self.img.clone();
-        self.cache
-            .cache_queued(move |rect, data| {
+        loop {
+            let img = self.img;
+            let res = self.cache.cache_queued(|rect, data| {
                 renderer
                     .update_texture(
-                        img.clone(),
+                        img,
                         rect.min.x as usize,
                         rect.min.y as usize,
                         (rect.max.x - rect.min.x) as usize,
@@ -143,17 +261,76 @@ impl Fonts {
                         data,
                     )
                     .unwrap();
-            })
-            .map_err(|err| NonaError::Texture(err.to_string()))?;
+            });
+
+            match res {
+                Ok(_) => return Ok(()),
+                // the cache already evicts unused glyphs, so these mean the atlas is too small
+                Err(CacheWriteErr::GlyphTooLarge) | Err(CacheWriteErr::NoRoomForWholeQueue) => {
+                    self.grow_texture(renderer)?
+                }
+            }
+        }
+    }
+
+    /// Doubles the glyph atlas, up to the renderer's max texture size
+    fn grow_texture<R: Renderer>(&mut self, renderer: &mut R) -> Result<(), NonaError> {
+        let max_size = renderer.max_texture_size() as u32;
+        let (width, height) = self.cache.dimensions();
+        if width >= max_size && height >= max_size {
+            return Err(NonaError::Texture(format!(
+                "glyph atlas is full at max size {}×{}",
+                width, height
+            )));
+        }
+        let width = (width * 2).min(max_size);
+        let height = (height * 2).min(max_size);
+
+        // glyphs drawn so far in this frame sample the old atlas until the frame is flushed
+        self.retired_imgs.push(self.img);
+        self.img = renderer.create_texture(
+            TextureType::Alpha,
+            width as usize,
+            height as usize,
+            ImageFlags::empty(),
+            None,
+        )?;
+        self.cache
+            .to_builder()
+            .dimensions(width, height)
+            .rebuild(&mut self.cache);
+        Ok(())
+    }
+
+    /// Deletes the atlases replaced while growing, once the frame drawn from them is flushed
+    pub(crate) fn delete_retired_textures<R: Renderer>(
+        &mut self,
+        renderer: &mut R,
+    ) -> Result<(), NonaError> {
+        for img in self.retired_imgs.drain(..) {
+            renderer.delete_texture(img)?;
+        }
         Ok(())
     }
 
+    /// Returns the contours of the glyph for `c` at `size`, with the origin on the baseline and
+    /// y pointing down. Empty when neither the font nor its fallbacks have an outline for `c`.
+    pub fn glyph_outline(&self, id: FontId, c: char, size: f32) -> Vec<PathSegment> {
+        let mut outline = GlyphOutline::default();
+        if let Some((_, glyph)) = self.glyph(id, c) {
+            glyph
+                .scaled(Scale::uniform(size))
+                .build_outline(&mut outline);
+        }
+        outline.segments
+    }
+
     pub fn text_metrics(&self, id: FontId, size: f32) -> TextMetrics {
         if let Some(fd) = self.fonts.get(id) {
             let scale = Scale::uniform(size);
             let v_metrics = fd.font.v_metrics(scale);
             TextMetrics {
-                ascender: v_metrics.descent,
+                ascender: v_metrics.ascent,
                 descender: v_metrics.descent,
                 line_gap: v_metrics.line_gap,
             }
@@ -166,66 +343,282 @@ impl Fonts {
         }
     }
 
-    pub fn text_size(&self, text: &str, id: FontId, size: f32, spacing: f32) -> Extent {
+    /// Measures `text`, reusing the result of an earlier call with the same arguments
+    pub fn text_size(
+        &self,
+        text: &str,
+        id: FontId,
+        scale: Scale,
+        spacing: f32,
+        tab_size: f32,
+    ) -> Extent {
+        let params = (
+            id,
+            scale.x.to_bits(),
+            scale.y.to_bits(),
+            spacing.to_bits(),
+            tab_size.to_bits(),
+        );
+        let mut hasher = DefaultHasher::new();
+        text.hash(&mut hasher);
+        params.hash(&mut hasher);
+        let key = hasher.finish();
+
+        let mut cache = self.measure_cache.borrow_mut();
+        cache.tick += 1;
+        let tick = cache.tick;
+        if let Some(entry) = cache.entries.get_mut(&key) {
+            if entry.params == params && entry.text == text {
+                entry.last_used = tick;
+                return entry.extent;
+            }
+        }
+
+        cache.misses += 1;
+        let extent = self.measure_text(text, id, scale, spacing, tab_size);
+        if cache.entries.len() >= MEASURE_CACHE_SIZE && !cache.entries.contains_key(&key) {
+            let oldest = cache
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(key, _)| *key);
+            if let Some(oldest) = oldest {
+                cache.entries.remove(&oldest);
+            }
+        }
+        cache.entries.insert(
+            key,
+            MeasureEntry {
+                text: text.to_string(),
+                params,
+                extent,
+                last_used: tick,
+            },
+        );
+        extent
+    }
+
+    fn measure_text(
+        &self,
+        text: &str,
+        id: FontId,
+        scale: Scale,
+        spacing: f32,
+        tab_size: f32,
+    ) -> Extent {
         if let Some(fd) = self.fonts.get(id) {
-            let scale = Scale::uniform(size);
+            let tab_size = self.tab_width(fd, scale, tab_size);
             let v_metrics = fd.font.v_metrics(scale);
             let mut extent = Extent::new(
                 0.0,
                 v_metrics.ascent - v_metrics.descent + v_metrics.line_gap,
             );
             let mut last_glyph = None;
-            let mut char_count = 0;
 
+            // must advance exactly like `layout_text`, so measured and rendered widths agree
             for c in text.chars() {
+                if c == '\t' {
+                    extent.width = next_tab_stop(extent.width, tab_size);
+                    last_glyph = None;
+                    continue;
+                }
                 if let Some((_, glyph)) = self.glyph(id, c) {
                     let glyph = glyph.scaled(scale);
                     let h_metrics = glyph.h_metrics();
-                    extent.width += h_metrics.advance_width;
+                    if extends_cluster(c) && last_glyph.is_some() {
+                        extent.width += h_metrics.advance_width;
+                        continue;
+                    }
+                    extent.width += h_metrics.advance_width + spacing;
 
                     if let Some(last_glyph) = last_glyph {
                         extent.width += fd.font.pair_kerning(scale, last_glyph, glyph.id());
                     }
 
                     last_glyph = Some(glyph.id());
-                    char_count += 1;
                 }
             }
 
-            if char_count >= 2 {
-                extent.width += spacing * (char_count - 1) as f32;
-            }
-
             extent
         } else {
             Default::default()
         }
     }
 
+    /// Greedily wraps `text` into lines no wider than `break_width`, breaking after
+    /// whitespace where possible and between characters for words that don't fit on a line
+    /// by themselves. Returns the byte range and width of every line, with the whitespace
+    /// at the break collapsed out of the range.
+    pub fn break_lines(
+        &self,
+        text: &str,
+        id: FontId,
+        scale: Scale,
+        spacing: f32,
+        tab_size: f32,
+        break_width: f32,
+    ) -> Vec<(usize, usize, f32)> {
+        let mut lines = Vec::new();
+        let fd = match self.fonts.get(id) {
+            Some(fd) => fd,
+            None => return lines,
+        };
+        let tab_size = self.tab_width(fd, scale, tab_size);
+
+        let mut line_start = 0;
+        let mut line_end = 0;
+        let mut line_width = 0.0;
+        let mut width = 0.0;
+        let mut word_start = 0;
+        let mut word_start_width = 0.0;
+        let mut in_word = false;
+        let mut break_end = None;
+        let mut last_glyph = None;
+
+        for (idx, c) in text.char_indices() {
+            if c == '\n' {
+                lines.push((line_start, line_end, line_width));
+                line_start = idx + 1;
+                line_end = line_start;
+                line_width = 0.0;
+                width = 0.0;
+                in_word = false;
+                break_end = None;
+                last_glyph = None;
+                continue;
+            }
+
+            let mut advance = 0.0;
+            if c == '\t' {
+                advance = next_tab_stop(width, tab_size) - width;
+                last_glyph = None;
+            } else if let Some((_, glyph)) = self.glyph(id, c) {
+                let glyph = glyph.scaled(scale);
+                advance = glyph.h_metrics().advance_width + spacing;
+                if let Some(last_glyph) = last_glyph {
+                    advance += fd.font.pair_kerning(scale, last_glyph, glyph.id());
+                }
+                last_glyph = Some(glyph.id());
+            }
+
+            if c.is_whitespace() {
+                if line_end == line_start {
+                    // whitespace at the start of a line is collapsed
+                    line_start = idx + c.len_utf8();
+                    line_end = line_start;
+                    last_glyph = None;
+                } else {
+                    if in_word {
+                        break_end = Some((line_end, line_width));
+                        in_word = false;
+                    }
+                    width += advance;
+                }
+                continue;
+            }
+
+            if !in_word {
+                in_word = true;
+                word_start = idx;
+                word_start_width = width;
+            }
+
+            if width + advance > break_width && line_end > line_start {
+                if let Some((end, end_width)) = break_end.take() {
+                    lines.push((line_start, end, end_width));
+                    line_start = word_start;
+                    width -= word_start_width;
+                } else {
+                    lines.push((line_start, line_end, line_width));
+                    line_start = idx;
+                    width = 0.0;
+                }
+                word_start_width = 0.0;
+            }
+
+            width += advance;
+            line_end = idx + c.len_utf8();
+            line_width = width;
+        }
+
+        if line_end > line_start {
+            lines.push((line_start, line_end, line_width));
+        }
+
+        lines
+    }
+
     pub fn layout_text<R: Renderer>(
         &mut self,
         renderer: &mut R,
         text: &str,
-        id: FontId,
-        position: crate::Point,
-        size: f32,
-        align: Align,
-        spacing: f32,
+        layout: &TextLayout,
         cache: bool,
         result: &mut Vec<LayoutChar>,
     ) -> Result<(), NonaError> {
+        self.layout_glyphs(text, layout, result);
+
+        if cache {
+            self.cache_glyphs(renderer, result)?;
+        }
+
+        Ok(())
+    }
+
+    /// Adds the glyphs of `chars` to the atlas in a single pass and sets their `uv`. Glyphs
+    /// laid out in several parts have to be cached together, since growing the atlas drops
+    /// the glyphs cached before.
+    pub fn cache_glyphs<R: Renderer>(
+        &mut self,
+        renderer: &mut R,
+        chars: &mut [LayoutChar],
+    ) -> Result<(), NonaError> {
+        for lc in chars.iter() {
+            self.cache.queue_glyph(lc.id, lc.glyph.clone());
+        }
+
+        self.render_texture(renderer)?;
+
+        for lc in chars {
+            if let Ok(Some((uv, _))) = self.cache.rect_for(lc.id, &lc.glyph) {
+                lc.uv = Bounds {
+                    min: crate::Point {
+                        x: uv.min.x,
+                        y: uv.min.y,
+                    },
+                    max: crate::Point {
+                        x: uv.max.x,
+                        y: uv.max.y,
+                    },
+                };
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Positions the glyphs of `text` without touching the glyph atlas. Only glyphs with
+    /// ink are added to `result`.
+    pub fn layout_glyphs(&self, text: &str, layout: &TextLayout, result: &mut Vec<LayoutChar>) {
+        let TextLayout {
+            font_id: id,
+            position,
+            scale,
+            align,
+            spacing,
+            tab_size,
+        } = *layout;
         result.clear();
 
         if let Some(fd) = self.fonts.get(id) {
             let mut offset = Point { x: 0.0, y: 0.0 };
-            let scale = Scale::uniform(size);
             let v_metrics = fd.font.v_metrics(scale);
 
             let sz = if align.contains(Align::CENTER)
                 || align.contains(Align::RIGHT)
                 || align.contains(Align::MIDDLE)
             {
-                self.text_size(text, id, size, spacing)
+                self.text_size(text, id, scale, spacing, tab_size)
             } else {
                 Extent::new(0.0, 0.0)
             };
@@ -248,26 +641,58 @@ impl Fonts {
                 x: position.x + offset.x,
                 y: position.y + offset.y,
             };
+            let origin = position.x;
+            let tab_size = self.tab_width(fd, scale, tab_size);
             let mut last_glyph = None;
+            // x of the last base char and where its glyph ends, before spacing and kerning
+            let mut cluster = (position.x, position.x);
 
             for (idx, c) in text.chars().enumerate() {
+                if c == '\t' {
+                    position.x = origin + next_tab_stop(position.x - origin, tab_size);
+                    last_glyph = None;
+                    continue;
+                }
                 if let Some((id, glyph)) = self.glyph(id, c) {
                     let g = glyph.scaled(scale);
                     let h_metrics = g.h_metrics();
 
+                    if extends_cluster(c) && last_glyph.is_some() {
+                        let glyph = g.positioned(Point {
+                            x: cluster.1,
+                            y: position.y,
+                        });
+                        cluster.1 += h_metrics.advance_width;
+                        position.x += h_metrics.advance_width;
+                        if let Some(bb) = glyph.pixel_bounding_box() {
+                            result.push(LayoutChar {
+                                id,
+                                idx,
+                                c,
+                                x: cluster.0,
+                                next_x: position.x,
+                                glyph,
+                                uv: Default::default(),
+                                bounds: Bounds {
+                                    min: (bb.min.x as f32, bb.min.y as f32).into(),
+                                    max: (bb.max.x as f32, bb.max.y as f32).into(),
+                                },
+                            });
+                        }
+                        continue;
+                    }
+
                     let glyph = g.positioned(Point {
                         x: position.x,
                         y: position.y,
                     });
 
-                    let mut next_x = position.x + h_metrics.advance_width;
+                    let mut next_x = position.x + h_metrics.advance_width + spacing;
                     if let Some(last_glyph) = last_glyph {
                         next_x += fd.font.pair_kerning(scale, last_glyph, glyph.id());
                     }
 
                     if let Some(bb) = glyph.pixel_bounding_box() {
-                        self.cache.queue_glyph(id, glyph.clone());
-
                         result.push(LayoutChar {
                             id,
                             idx,
@@ -283,31 +708,105 @@ impl Fonts {
                         });
                     }
 
+                    cluster = (position.x, position.x + h_metrics.advance_width);
                     position.x = next_x;
                     last_glyph = Some(glyph.id());
                 }
             }
+        }
+    }
+}
 
-            if cache {
-                self.render_texture(renderer)?;
+/// Collects glyph contours as path segments, converting quadratic curves to cubic ones
+#[derive(Default)]
+struct GlyphOutline {
+    segments: Vec<PathSegment>,
+    last: crate::Point,
+}
 
-                for lc in result {
-                    if let Ok(Some((uv, _))) = self.cache.rect_for(lc.id, &lc.glyph) {
-                        lc.uv = Bounds {
-                            min: crate::Point {
-                                x: uv.min.x,
-                                y: uv.min.y,
-                            },
-                            max: crate::Point {
-                                x: uv.max.x,
-                                y: uv.max.y,
-                            },
-                        };
-                    }
-                }
-            }
-        }
+impl OutlineBuilder for GlyphOutline {
+    fn move_to(&mut self, x: f32, y: f32) {
+        self.last = crate::Point::new(x, y);
+        self.segments.push(PathSegment::MoveTo(self.last));
+    }
 
-        Ok(())
+    fn line_to(&mut self, x: f32, y: f32) {
+        self.last = crate::Point::new(x, y);
+        self.segments.push(PathSegment::LineTo(self.last));
+    }
+
+    fn quad_to(&mut self, x1: f32, y1: f32, x: f32, y: f32) {
+        let cp = crate::Point::new(x1, y1);
+        let pt = crate::Point::new(x, y);
+        self.segments.push(PathSegment::CubicTo(
+            self.last.lerp(cp, 2.0 / 3.0),
+            pt.lerp(cp, 2.0 / 3.0),
+            pt,
+        ));
+        self.last = pt;
+    }
+
+    fn curve_to(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, x: f32, y: f32) {
+        self.last = crate::Point::new(x, y);
+        self.segments.push(PathSegment::CubicTo(
+            crate::Point::new(x1, y1),
+            crate::Point::new(x2, y2),
+            self.last,
+        ));
+    }
+
+    fn close(&mut self) {
+        self.segments.push(PathSegment::Close);
     }
 }
+
+/// First tab stop past `x`, with stops every `tab_size` from the start of the line
+fn next_tab_stop(x: f32, tab_size: f32) -> f32 {
+    if tab_size <= 0.0 {
+        return x;
+    }
+    ((x / tab_size).floor() + 1.0) * tab_size
+}
+
+/// Whether `c` belongs to the cluster of the char before it, like combining marks, joiners and
+/// variation selectors. Such chars get no letter spacing or kerning and are drawn over the
+/// preceding glyph. Covers the common ranges rather than full grapheme segmentation.
+pub(crate) fn extends_cluster(c: char) -> bool {
+    matches!(c,
+        '\u{0300}'..='\u{036F}'
+        | '\u{0483}'..='\u{0489}'
+        | '\u{0591}'..='\u{05BD}'
+        | '\u{0610}'..='\u{061A}'
+        | '\u{064B}'..='\u{065F}'
+        | '\u{1AB0}'..='\u{1AFF}'
+        | '\u{1DC0}'..='\u{1DFF}'
+        | '\u{200C}'..='\u{200D}'
+        | '\u{20D0}'..='\u{20FF}'
+        | '\u{FE00}'..='\u{FE0F}'
+        | '\u{FE20}'..='\u{FE2F}'
+        | '\u{1F3FB}'..='\u{1F3FF}'
+        | '\u{E0100}'..='\u{E01EF}')
+}
+
+/// Describes why `data` failed to parse, based on the leading magic bytes
+fn font_data_error(data: &[u8]) -> String {
+    if data.len() < 4 {
+        return format!("Font data is truncated ({} bytes)", data.len());
+    }
+    let format = match &data[..4] {
+        [0x00, 0x01, 0x00, 0x00] | b"true" => "TrueType",
+        b"OTTO" => "OpenType (CFF)",
+        b"ttcf" => "TrueType collection",
+        magic => {
+            return format!(
+                "Unsupported font format (magic {:02x} {:02x} {:02x} {:02x})",
+                magic[0], magic[1], magic[2], magic[3]
+            )
+        }
+    };
+    format!(
+        "{} font data is truncated or corrupt ({} bytes)",
+        format,
+        data.len()
+    )
+}
@@ -1,20 +1,31 @@
 #[macro_use]
 extern crate bitflags;
 
+#[cfg(feature = "bidi")]
+mod bidi;
 mod cache;
 mod color;
 mod context;
 mod errors;
 mod fonts;
 mod math;
+mod profiler;
 pub mod renderer;
+mod software;
+mod svg;
 
+#[cfg(feature = "bidi")]
+pub use bidi::BaseDirection;
 pub use color::*;
 pub use context::{
-    Align, BasicCompositeOperation, BlendFactor, Canvas, CompositeOperation, Context, Gradient,
-    ImageFlags, ImageId, ImagePattern, LineCap, LineJoin, Paint, Solidity, TextMetrics,
+    Align, BasicCompositeOperation, BlendEquation, BlendFactor, CachedText, Canvas,
+    CompositeOperation, Context, ContextOptions, DisplayList, FillRule, Gradient, ImageFlags,
+    ImageId, ImagePattern, LineCap, LineJoin, Paint, PaintBuilder, PathSegment, Solidity,
+    StrokeAlign, TextDirection, TextMetrics,
 };
 pub use errors::*;
 pub use fonts::FontId;
 pub use math::*;
+pub use profiler::{Profiler, ProfilerHooks};
 pub use renderer::Renderer;
+pub use software::SoftwareRenderer;
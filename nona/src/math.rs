@@ -1,6 +1,6 @@
-use std::ops::{Add, Mul, MulAssign};
+use std::ops::{Add, AddAssign, Div, Mul, MulAssign, Neg, Sub, SubAssign};
 
-#[derive(Debug, Copy, Clone, Default)]
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
 pub struct Point {
     pub x: f32,
     pub y: f32,
@@ -17,11 +17,82 @@ impl Add for Point {
     }
 }
 
+impl AddAssign for Point {
+    fn add_assign(&mut self, rhs: Self) {
+        self.x += rhs.x;
+        self.y += rhs.y;
+    }
+}
+
+impl Sub for Point {
+    type Output = Point;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        Point {
+            x: self.x - rhs.x,
+            y: self.y - rhs.y,
+        }
+    }
+}
+
+impl SubAssign for Point {
+    fn sub_assign(&mut self, rhs: Self) {
+        self.x -= rhs.x;
+        self.y -= rhs.y;
+    }
+}
+
+impl Neg for Point {
+    type Output = Point;
+
+    fn neg(self) -> Self::Output {
+        Point {
+            x: -self.x,
+            y: -self.y,
+        }
+    }
+}
+
+impl Mul<f32> for Point {
+    type Output = Point;
+
+    fn mul(self, rhs: f32) -> Self::Output {
+        Point {
+            x: self.x * rhs,
+            y: self.y * rhs,
+        }
+    }
+}
+
+impl Div<f32> for Point {
+    type Output = Point;
+
+    fn div(self, rhs: f32) -> Self::Output {
+        Point {
+            x: self.x / rhs,
+            y: self.y / rhs,
+        }
+    }
+}
+
 impl Point {
     pub fn new(x: f32, y: f32) -> Point {
         Point { x, y }
     }
 
+    pub fn dot(self, pt: Point) -> f32 {
+        self.x * pt.x + self.y * pt.y
+    }
+
+    pub fn length(self) -> f32 {
+        self.dot(self).sqrt()
+    }
+
+    /// Point at `t` along the line from `self` (t = 0) to `pt` (t = 1)
+    pub fn lerp(self, pt: Point, t: f32) -> Point {
+        self + (pt - self) * t
+    }
+
     pub(crate) fn equals(self, pt: Point, tol: f32) -> bool {
         let dx = pt.x - self.x;
         let dy = pt.y - self.y;
@@ -89,6 +160,15 @@ impl Extent {
     pub fn new(width: f32, height: f32) -> Extent {
         Extent { width, height }
     }
+
+    pub fn area(&self) -> f32 {
+        self.width * self.height
+    }
+
+    /// True when either side is zero or negative
+    pub fn is_empty(&self) -> bool {
+        self.width <= 0.0 || self.height <= 0.0
+    }
 }
 
 impl From<(f32, f32)> for Extent {
@@ -115,7 +195,7 @@ impl Rect {
                 width: aw,
                 height: ah,
             },
-        } = rect;
+        } = self;
 
         let Rect {
             xy: Point { x: bx, y: by },
@@ -135,6 +215,19 @@ impl Rect {
         )
     }
 
+    /// Whether `pt` lies in the half-open rect `[x, x + width) x [y, y + height)`
+    pub fn contains(&self, pt: Point) -> bool {
+        pt.x >= self.xy.x
+            && pt.x < self.xy.x + self.size.width
+            && pt.y >= self.xy.y
+            && pt.y < self.xy.y + self.size.height
+    }
+
+    pub fn center(&self) -> Point {
+        self.xy
+            .offset(self.size.width * 0.5, self.size.height * 0.5)
+    }
+
     pub fn grow(&self, width: f32, height: f32) -> Rect {
         Rect::new(
             self.xy.offset(-width / 2.0, -height / 2.0),
@@ -156,6 +249,39 @@ pub struct Bounds {
 }
 
 impl Bounds {
+    /// Smallest bounds holding all `pts`. With no points, the result is inverted (min above
+    /// max), which acts as the identity for `union`.
+    pub fn from_points(pts: &[Point]) -> Bounds {
+        let empty = Bounds {
+            min: Point::new(f32::MAX, f32::MAX),
+            max: Point::new(f32::MIN, f32::MIN),
+        };
+        pts.iter().fold(empty, |bounds, pt| {
+            bounds.union(Bounds { min: *pt, max: *pt })
+        })
+    }
+
+    pub fn union(&self, bounds: Bounds) -> Bounds {
+        Bounds {
+            min: Point::new(self.min.x.min(bounds.min.x), self.min.y.min(bounds.min.y)),
+            max: Point::new(self.max.x.max(bounds.max.x), self.max.y.max(bounds.max.y)),
+        }
+    }
+
+    /// Overlap of the two bounds. Disjoint bounds give an inverted result, with a negative
+    /// `width` or `height`.
+    pub fn intersect(&self, bounds: Bounds) -> Bounds {
+        Bounds {
+            min: Point::new(self.min.x.max(bounds.min.x), self.min.y.max(bounds.min.y)),
+            max: Point::new(self.max.x.min(bounds.max.x), self.max.y.min(bounds.max.y)),
+        }
+    }
+
+    /// Whether `pt` lies inside or on the edge of the bounds
+    pub fn contains_point(&self, pt: Point) -> bool {
+        pt.x >= self.min.x && pt.x <= self.max.x && pt.y >= self.min.y && pt.y <= self.max.y
+    }
+
     pub fn width(&self) -> f32 {
         self.max.x - self.min.x
     }
@@ -240,6 +366,47 @@ impl Transform {
         )
     }
 
+    pub fn is_identity(&self) -> bool {
+        self.0 == [1.0, 0.0, 0.0, 1.0, 0.0, 0.0]
+    }
+
+    /// Transforms `pts` into `result`, replacing its contents
+    pub fn transform_points(&self, pts: &[Point], result: &mut Vec<Point>) {
+        result.clear();
+        result.extend_from_slice(pts);
+        self.map_points_mut(result);
+    }
+
+    pub fn map_points(&self, pts: &[Point]) -> Vec<Point> {
+        let mut result = Vec::with_capacity(pts.len());
+        self.transform_points(pts, &mut result);
+        result
+    }
+
+    /// Transforms `pts` in place, leaving them untouched for the identity
+    pub fn map_points_mut(&self, pts: &mut [Point]) {
+        if self.is_identity() {
+            return;
+        }
+        for pt in pts {
+            *pt = self.transform_point(*pt);
+        }
+    }
+
+    /// Splits the transform into translation, rotation (radians) and scale, such that
+    /// `scale * rotate * translate` rebuilds it. Any skew is folded into the y scale,
+    /// which keeps the area (determinant) of the original transform.
+    pub fn decompose(&self) -> (Point, f32, Extent) {
+        let t = &self.0;
+        let sx = (t[0] * t[0] + t[1] * t[1]).sqrt();
+        if sx < 1e-6 {
+            return (Point::new(t[4], t[5]), 0.0, Extent::new(0.0, 0.0));
+        }
+        let rotation = t[1].atan2(t[0]);
+        let sy = (t[0] * t[3] - t[2] * t[1]) / sx;
+        (Point::new(t[4], t[5]), rotation, Extent::new(sx, sy))
+    }
+
     pub(crate) fn average_scale(&self) -> f32 {
         let t = &self.0;
         let sx = (t[0] * t[0] + t[2] * t[2]).sqrt();
@@ -294,3 +461,157 @@ impl From<[f32; 6]> for Transform {
         Transform(values2)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-4
+    }
+
+    #[test]
+    fn decompose_recovers_scale_rotation_and_translation() {
+        let t =
+            Transform::scale(2.0, 3.0) * Transform::rotate(0.5) * Transform::translate(10.0, 20.0);
+        let (translation, rotation, scale) = t.decompose();
+        assert!(close(translation.x, 10.0) && close(translation.y, 20.0));
+        assert!(close(rotation, 0.5));
+        assert!(close(scale.width, 2.0) && close(scale.height, 3.0));
+    }
+
+    #[test]
+    fn decompose_reports_mirroring_as_negative_scale() {
+        let (_, rotation, scale) = Transform::scale(1.0, -2.0).decompose();
+        assert!(close(rotation, 0.0));
+        assert!(close(scale.width, 1.0) && close(scale.height, -2.0));
+    }
+
+    #[test]
+    fn transform_points_replaces_the_result() {
+        let pts = [Point::new(1.0, 2.0), Point::new(-3.0, 4.0)];
+        let mut result = vec![Point::new(9.0, 9.0)];
+        Transform::identity().transform_points(&pts, &mut result);
+        assert_eq!(result, pts);
+
+        Transform::translate(10.0, 20.0).transform_points(&pts, &mut result);
+        assert_eq!(result, [Point::new(11.0, 22.0), Point::new(7.0, 24.0)]);
+    }
+
+    #[test]
+    fn is_identity_only_for_the_identity() {
+        assert!(Transform::identity().is_identity());
+        assert!(!Transform::translate(1.0, 0.0).is_identity());
+        assert!(!Transform::scale(2.0, 1.0).is_identity());
+    }
+
+    #[test]
+    fn map_points_agree_with_transform_point() {
+        let t = Transform::rotate(0.3) * Transform::translate(5.0, -2.0);
+        let pts = [Point::new(1.0, 2.0), Point::new(-3.0, 4.0)];
+        let expected: Vec<Point> = pts.iter().map(|pt| t.transform_point(*pt)).collect();
+        assert_eq!(t.map_points(&pts), expected);
+
+        let mut in_place = pts;
+        t.map_points_mut(&mut in_place);
+        assert_eq!(in_place, expected[..]);
+
+        Transform::identity().map_points_mut(&mut in_place);
+        assert_eq!(in_place, expected[..]);
+    }
+
+    #[test]
+    fn point_operators_work_componentwise() {
+        let a = Point::new(1.0, 2.0);
+        let b = Point::new(3.0, -4.0);
+        assert_eq!(a + b, Point::new(4.0, -2.0));
+        assert_eq!(a - b, Point::new(-2.0, 6.0));
+        assert_eq!(-a, Point::new(-1.0, -2.0));
+        assert_eq!(a * 2.0, Point::new(2.0, 4.0));
+        assert_eq!(b / 2.0, Point::new(1.5, -2.0));
+
+        let mut c = a;
+        c += b;
+        assert_eq!(c, Point::new(4.0, -2.0));
+        c -= b;
+        assert_eq!(c, a);
+
+        // the expression used by the examples
+        let origin = Point::new(10.0, 10.0);
+        assert_eq!(origin + Point::new(2.0, 17.0), Point::new(12.0, 27.0));
+    }
+
+    #[test]
+    fn point_dot_length_and_lerp() {
+        let a = Point::new(3.0, 4.0);
+        assert!(close(a.dot(Point::new(2.0, -1.0)), 2.0));
+        assert!(close(a.length(), 5.0));
+        let b = Point::new(13.0, -6.0);
+        assert_eq!(a.lerp(b, 0.0), a);
+        assert_eq!(a.lerp(b, 1.0), b);
+        assert_eq!(a.lerp(b, 0.5), Point::new(8.0, -1.0));
+    }
+
+    #[test]
+    fn rect_contains_is_half_open() {
+        let r = Rect::from((10.0, 20.0, 30.0, 40.0));
+        assert!(r.contains(Point::new(10.0, 20.0)));
+        assert!(r.contains(Point::new(39.9, 59.9)));
+        assert!(!r.contains(Point::new(40.0, 30.0)));
+        assert!(!r.contains(Point::new(20.0, 60.0)));
+        assert!(!r.contains(Point::new(9.9, 30.0)));
+        assert_eq!(r.center(), Point::new(25.0, 40.0));
+    }
+
+    #[test]
+    fn extent_area_and_emptiness() {
+        assert!(close(Extent::new(3.0, 4.0).area(), 12.0));
+        assert!(!Extent::new(3.0, 4.0).is_empty());
+        assert!(Extent::new(0.0, 4.0).is_empty());
+        assert!(Extent::new(3.0, -1.0).is_empty());
+    }
+
+    #[test]
+    fn bounds_union_intersect_and_contains() {
+        let a = Bounds::from_points(&[Point::new(0.0, 0.0), Point::new(10.0, 5.0)]);
+        let b = Bounds::from_points(&[Point::new(5.0, -5.0), Point::new(20.0, 2.0)]);
+        let u = a.union(b);
+        assert_eq!(
+            (u.min, u.max),
+            (Point::new(0.0, -5.0), Point::new(20.0, 5.0))
+        );
+        let i = a.intersect(b);
+        assert_eq!(
+            (i.min, i.max),
+            (Point::new(5.0, 0.0), Point::new(10.0, 2.0))
+        );
+
+        assert!(a.contains_point(Point::new(10.0, 5.0)));
+        assert!(a.contains_point(Point::new(3.0, 3.0)));
+        assert!(!a.contains_point(Point::new(10.1, 3.0)));
+
+        // disjoint bounds intersect to an inverted result
+        let far = Bounds::from_points(&[Point::new(30.0, 30.0)]);
+        assert!(a.intersect(far).width() < 0.0);
+    }
+
+    #[test]
+    fn bounds_from_no_points_is_the_union_identity() {
+        let a = Bounds::from_points(&[Point::new(1.0, 2.0), Point::new(3.0, 4.0)]);
+        let u = Bounds::from_points(&[]).union(a);
+        assert_eq!((u.min, u.max), (a.min, a.max));
+    }
+
+    #[test]
+    fn rect_intersect_uses_both_rects() {
+        let a = Rect::from((0.0, 0.0, 10.0, 10.0));
+        let b = Rect::from((5.0, 2.0, 20.0, 4.0));
+        for r in [a.intersect(b), b.intersect(a)] {
+            assert_eq!(r.xy, Point::new(5.0, 2.0));
+            assert!(close(r.size.width, 5.0) && close(r.size.height, 4.0));
+        }
+
+        let disjoint = a.intersect(Rect::from((20.0, 20.0, 5.0, 5.0)));
+        assert!(disjoint.size.is_empty());
+    }
+}
@@ -0,0 +1,17 @@
+/// Receives timing-relevant events from a `Context`. All methods default to no-ops, so
+/// implementors only override the events they care about.
+pub trait Profiler {
+    fn on_fill_begin(&mut self) {}
+
+    fn on_fill_end(&mut self, _path_count: usize, _vertex_count: usize) {}
+
+    fn on_stroke_begin(&mut self) {}
+
+    fn on_stroke_end(&mut self, _path_count: usize, _vertex_count: usize) {}
+
+    fn on_flush_begin(&mut self, _draw_call_count: usize) {}
+
+    fn on_flush_end(&mut self, _draw_call_count: usize) {}
+}
+
+pub type ProfilerHooks = Box<dyn Profiler>;
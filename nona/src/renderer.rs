@@ -13,13 +13,75 @@ pub struct Scissor {
     pub extent: Extent,
 }
 
+/// Settings of a `Renderer::fill` call besides the paint
+#[derive(Debug, Copy, Clone)]
+#[non_exhaustive]
+pub struct FillParams {
+    pub fringe: f32,
+    /// Bounds of all paths, covered to resolve the stencil
+    pub bounds: Bounds,
+    pub fill_rule: FillRule,
+}
+
+/// Settings of a `Renderer::stroke` call besides the paint
+#[derive(Debug, Copy, Clone)]
+#[non_exhaustive]
+pub struct StrokeParams {
+    pub fringe: f32,
+    pub stroke_width: f32,
+    /// Whether the stroke vertexes carry the arc length fraction for a gradient along the
+    /// path, see `Context::stroke_gradient_along_path`
+    pub gradient_along_path: bool,
+}
+
+/// Features a backend supports, so `Context` can adapt what it submits
+#[derive(Debug, Copy, Clone)]
+pub struct RendererCaps {
+    /// Whether `fill` can use a stencil buffer. Without it, `Context` leaves out the
+    /// antialiasing fringe, which relies on the stencil to not overlap the fill.
+    pub supports_stencil: bool,
+    pub max_texture_size: usize,
+    /// Whether draws index vertexes with 32 bits, so they aren't split into batches of
+    /// 16-bit addressable vertexes
+    pub supports_u32_indices: bool,
+}
+
+/// A drawing backend.
+///
+/// `fill` must draw convex single paths (`Path::convex`) as a triangle fan of the fill
+/// vertexes plus a triangle strip of the fringe. Other fills are drawn in three steps: count
+/// the winding of the fill fans in a stencil, draw the fringe strips where the stencil is zero,
+/// then cover `params.bounds` where `params.fill_rule` considers the winding inside. `stroke` draws the
+/// stroke vertexes as triangle strips, `triangles` draws plain triangles sampling the paint
+/// image at the vertex uv. Vertex positions are in the units of `view_size`.
+///
+/// `clip` narrows the region later draws are limited to, to the inside of the fill fans of
+/// `paths` under `fill_rule`. Clips accumulate until `reset_clip` or the end of `flush`.
+///
+/// Only the methods without a default are needed for basic drawing. The defaults report
+/// texture wrapping, filtering, readback, clipping and gamma-correct blending as
+/// `NonaError::Unsupported`.
 pub trait Renderer {
     fn edge_antialias(&self) -> bool;
 
+    fn caps(&self) -> RendererCaps {
+        RendererCaps {
+            supports_stencil: true,
+            max_texture_size: self.max_texture_size(),
+            supports_u32_indices: false,
+        }
+    }
+
     fn view_size(&self) -> (f32, f32);
 
     fn device_pixel_ratio(&self) -> f32;
 
+    /// Largest width or height, in pixels, of a texture the backend can create. Defaults to
+    /// a size most GPUs support.
+    fn max_texture_size(&self) -> usize {
+        4096
+    }
+
     fn create_texture(
         &mut self,
         texture_type: TextureType,
@@ -43,19 +105,62 @@ pub trait Renderer {
 
     fn texture_size(&self, img: ImageId) -> Result<(usize, usize), NonaError>;
 
+    /// Switches the texture between clamping and repeating outside of its bounds
+    fn set_texture_repeat(
+        &mut self,
+        img: ImageId,
+        repeat_x: bool,
+        repeat_y: bool,
+    ) -> Result<(), NonaError> {
+        let _ = (img, repeat_x, repeat_y);
+        Err(NonaError::Unsupported("texture repeat".to_string()))
+    }
+
+    /// Switches the texture between nearest-neighbor and linear sampling
+    fn set_texture_nearest(&mut self, img: ImageId, nearest: bool) -> Result<(), NonaError> {
+        let _ = (img, nearest);
+        Err(NonaError::Unsupported(
+            "nearest texture sampling".to_string(),
+        ))
+    }
+
     fn viewport(&mut self, extent: Extent, device_pixel_ratio: f32) -> Result<(), NonaError>;
 
+    /// Switches to interpolating gradients and blending in linear light instead of sRGB, so
+    /// gradient midpoints and antialiased edges keep their perceived brightness
+    fn set_gamma_correct(&mut self, enabled: bool) -> Result<(), NonaError> {
+        if enabled {
+            Err(NonaError::Unsupported("gamma-correct blending".to_string()))
+        } else {
+            Ok(())
+        }
+    }
+
     fn clear_screen(&mut self, color: Color);
 
+    /// Draws what was submitted since the last flush. The clip set with `clip` is dropped, so
+    /// the context only flushes at the end of a frame.
     fn flush(&mut self) -> Result<(), NonaError>;
 
+    /// Reads back RGBA8 pixels of the rendered frame, rows top to bottom, where (x, y) is
+    /// the top left corner of the region in device pixels
+    fn read_pixels(
+        &mut self,
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+    ) -> Result<Vec<u8>, NonaError> {
+        let _ = (x, y, width, height);
+        Err(NonaError::Unsupported("reading back pixels".to_string()))
+    }
+
     fn fill(
         &mut self,
         paint: &Paint,
         composite_operation: CompositeOperationState,
         scissor: &Scissor,
-        fringe: f32,
-        bounds: Bounds,
+        params: &FillParams,
         paths: &[Path],
     ) -> Result<(), NonaError>;
 
@@ -64,8 +169,7 @@ pub trait Renderer {
         paint: &Paint,
         composite_operation: CompositeOperationState,
         scissor: &Scissor,
-        fringe: f32,
-        stroke_width: f32,
+        params: &StrokeParams,
         paths: &[Path],
     ) -> Result<(), NonaError>;
 
@@ -76,4 +180,14 @@ pub trait Renderer {
         scissor: &Scissor,
         vertexes: &[Vertex],
     ) -> Result<(), NonaError>;
+
+    fn clip(&mut self, fill_rule: FillRule, paths: &[Path]) -> Result<(), NonaError> {
+        let _ = (fill_rule, paths);
+        Err(NonaError::Unsupported("clip paths".to_string()))
+    }
+
+    /// Without clip support there is never a clip to reset
+    fn reset_clip(&mut self) -> Result<(), NonaError> {
+        Ok(())
+    }
 }
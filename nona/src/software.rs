@@ -0,0 +1,796 @@
+use crate::context::{CompositeOperationState, ImageId, Path, Vertex};
+use crate::renderer::{FillParams, Renderer, RendererCaps, Scissor, StrokeParams, TextureType};
+use crate::{
+    BlendEquation, BlendFactor, Color, Extent, FillRule, ImageFlags, NonaError, Paint, Point,
+    Transform,
+};
+use slab::Slab;
+
+struct Texture {
+    texture_type: TextureType,
+    width: usize,
+    height: usize,
+    flags: ImageFlags,
+    data: Vec<u8>,
+}
+
+impl Texture {
+    fn bytes_per_pixel(&self) -> usize {
+        match self.texture_type {
+            TextureType::RGBA => 4,
+            TextureType::Alpha => 1,
+        }
+    }
+
+    fn texel(&self, x: i64, y: i64) -> [f32; 4] {
+        let wrap = |v: i64, size: usize, repeat: bool| {
+            if repeat {
+                v.rem_euclid(size as i64) as usize
+            } else {
+                v.max(0).min(size as i64 - 1) as usize
+            }
+        };
+        let x = wrap(x, self.width, self.flags.contains(ImageFlags::REPEATX));
+        let y = wrap(y, self.height, self.flags.contains(ImageFlags::REPEATY));
+        let idx = (y * self.width + x) * self.bytes_per_pixel();
+        match self.texture_type {
+            TextureType::RGBA => [
+                self.data[idx] as f32 / 255.0,
+                self.data[idx + 1] as f32 / 255.0,
+                self.data[idx + 2] as f32 / 255.0,
+                self.data[idx + 3] as f32 / 255.0,
+            ],
+            TextureType::Alpha => [0.0, 0.0, 0.0, self.data[idx] as f32 / 255.0],
+        }
+    }
+
+    /// Samples at normalized coordinates, like `texture2D` in the GPU shader
+    fn sample(&self, u: f32, v: f32) -> [f32; 4] {
+        let x = u * self.width as f32;
+        let y = v * self.height as f32;
+        if self.flags.contains(ImageFlags::NEAREST) {
+            return self.texel(x.floor() as i64, y.floor() as i64);
+        }
+
+        let x = x - 0.5;
+        let y = y - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as i64, y0 as i64);
+        let t00 = self.texel(x0, y0);
+        let t10 = self.texel(x0 + 1, y0);
+        let t01 = self.texel(x0, y0 + 1);
+        let t11 = self.texel(x0 + 1, y0 + 1);
+        let mut res = [0.0; 4];
+        for i in 0..4 {
+            let top = t00[i] + (t10[i] - t00[i]) * fx;
+            let bottom = t01[i] + (t11[i] - t01[i]) * fx;
+            res[i] = top + (bottom - top) * fy;
+        }
+        res
+    }
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum ShaderType {
+    FillGradient,
+    FillImage,
+    Image,
+    StrokeGradientAlongPath,
+}
+
+/// The per-draw state of the GPU fragment shader
+struct Shader {
+    shader_type: ShaderType,
+    paint_mat: Transform,
+    scissor_mat: Transform,
+    scissor_ext: (f32, f32),
+    scissor_scale: (f32, f32),
+    extent: (f32, f32),
+    radius: f32,
+    feather: f32,
+    stroke_mult: f32,
+    inner_color: [f32; 4],
+    outer_color: [f32; 4],
+    image: Option<ImageId>,
+    premultiplied: bool,
+    gamma_correct: bool,
+}
+
+fn premul(color: Color) -> [f32; 4] {
+    [
+        color.r * color.a,
+        color.g * color.a,
+        color.b * color.a,
+        color.a,
+    ]
+}
+
+/// Converts an sRGB channel to linear light
+fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(c: f32) -> f32 {
+    if c <= 0.0031308 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Applies `f` to the color channels of a premultiplied color, leaving alpha as is
+fn map_premul(color: [f32; 4], f: fn(f32) -> f32) -> [f32; 4] {
+    if color[3] <= 0.0 {
+        return color;
+    }
+    let a = color[3];
+    [
+        f(color[0] / a) * a,
+        f(color[1] / a) * a,
+        f(color[2] / a) * a,
+        a,
+    ]
+}
+
+fn sdroundrect(pt: Point, ext: (f32, f32), rad: f32) -> f32 {
+    let dx = pt.x.abs() - (ext.0 - rad);
+    let dy = pt.y.abs() - (ext.1 - rad);
+    let outside = (dx.max(0.0) * dx.max(0.0) + dy.max(0.0) * dy.max(0.0)).sqrt();
+    dx.max(dy).min(0.0) + outside - rad
+}
+
+impl Shader {
+    fn new(
+        textures: &Slab<Texture>,
+        paint: &Paint,
+        scissor: &Scissor,
+        width: f32,
+        fringe: f32,
+        gamma_correct: bool,
+    ) -> Shader {
+        let color = |color: Color| {
+            if gamma_correct {
+                map_premul(premul(color), srgb_to_linear)
+            } else {
+                premul(color)
+            }
+        };
+        let mut shader = Shader {
+            shader_type: ShaderType::FillGradient,
+            paint_mat: paint.xform.inverse(),
+            scissor_mat: Transform([0.0; 6]),
+            scissor_ext: (1.0, 1.0),
+            scissor_scale: (1.0, 1.0),
+            extent: (paint.extent.width, paint.extent.height),
+            radius: paint.radius,
+            feather: paint.feather,
+            stroke_mult: (width * 0.5 + fringe * 0.5) / fringe,
+            inner_color: color(paint.inner_color),
+            outer_color: color(paint.outer_color),
+            image: None,
+            premultiplied: false,
+            gamma_correct,
+        };
+
+        if scissor.extent.width >= -0.5 && scissor.extent.height >= -0.5 {
+            let xf = &scissor.xform.0;
+            shader.scissor_mat = scissor.xform.inverse();
+            shader.scissor_ext = (scissor.extent.width, scissor.extent.height);
+            shader.scissor_scale = (
+                (xf[0] * xf[0] + xf[2] * xf[2]).sqrt() / fringe,
+                (xf[1] * xf[1] + xf[3] * xf[3]).sqrt() / fringe,
+            );
+        }
+
+        if let Some(texture) = paint.image.and_then(|img| textures.get(img)) {
+            if texture.flags.contains(ImageFlags::FLIPY) {
+                let m1 = Transform::translate(0.0, shader.extent.1 * 0.5) * paint.xform;
+                let m2 = Transform::scale(1.0, -1.0) * m1;
+                let m1 = Transform::translate(0.0, -shader.extent.1 * 0.5) * m2;
+                shader.paint_mat = m1.inverse();
+            }
+            shader.shader_type = ShaderType::FillImage;
+            shader.image = paint.image;
+            shader.premultiplied = texture.flags.contains(ImageFlags::PREMULTIPLIED);
+        }
+        shader
+    }
+
+    fn sample(&self, textures: &Slab<Texture>, u: f32, v: f32) -> [f32; 4] {
+        match self.image.and_then(|img| textures.get(img)) {
+            Some(texture) => {
+                let mut color = texture.sample(u, v);
+                match texture.texture_type {
+                    TextureType::Alpha => color = [color[3]; 4],
+                    TextureType::RGBA if !self.premultiplied => {
+                        color = [
+                            color[0] * color[3],
+                            color[1] * color[3],
+                            color[2] * color[3],
+                            color[3],
+                        ]
+                    }
+                    TextureType::RGBA => {}
+                }
+                if self.gamma_correct {
+                    if let TextureType::RGBA = texture.texture_type {
+                        color = map_premul(color, srgb_to_linear);
+                    }
+                }
+                color
+            }
+            None => [1.0; 4],
+        }
+    }
+
+    /// Premultiplied color of the pixel at `pos`
+    fn shade(&self, textures: &Slab<Texture>, pos: Point, uv: (f32, f32)) -> [f32; 4] {
+        let sc = self.scissor_mat.transform_point(pos);
+        let scx = 0.5 - (sc.x.abs() - self.scissor_ext.0) * self.scissor_scale.0;
+        let scy = 0.5 - (sc.y.abs() - self.scissor_ext.1) * self.scissor_scale.1;
+        let scissor = scx.clamp(0.0, 1.0) * scy.clamp(0.0, 1.0);
+
+        let stroke_alpha =
+            ((1.0 - (uv.0 * 2.0 - 1.0).abs()) * self.stroke_mult).min(1.0) * uv.1.min(1.0);
+
+        let mix = |d: f32| {
+            let mut color = [0.0; 4];
+            for (i, c) in color.iter_mut().enumerate() {
+                *c = self.inner_color[i] + (self.outer_color[i] - self.inner_color[i]) * d;
+            }
+            color
+        };
+
+        let (color, coverage) = match self.shader_type {
+            ShaderType::FillGradient => {
+                let pt = self.paint_mat.transform_point(pos);
+                let d = ((sdroundrect(pt, self.extent, self.radius) + self.feather * 0.5)
+                    / self.feather)
+                    .clamp(0.0, 1.0);
+                (mix(d), stroke_alpha * scissor)
+            }
+            ShaderType::FillImage => {
+                let pt = self.paint_mat.transform_point(pos);
+                let mut color = self.sample(textures, pt.x / self.extent.0, pt.y / self.extent.1);
+                for (c, inner) in color.iter_mut().zip(&self.inner_color) {
+                    *c *= inner;
+                }
+                (color, stroke_alpha * scissor)
+            }
+            ShaderType::Image => {
+                let mut color = self.sample(textures, uv.0, uv.1);
+                for (c, inner) in color.iter_mut().zip(&self.inner_color) {
+                    *c *= inner;
+                }
+                (color, scissor)
+            }
+            ShaderType::StrokeGradientAlongPath => {
+                (mix((uv.1 - 1.0).clamp(0.0, 1.0)), stroke_alpha * scissor)
+            }
+        };
+        [
+            color[0] * coverage,
+            color[1] * coverage,
+            color[2] * coverage,
+            color[3] * coverage,
+        ]
+    }
+}
+
+fn blend_factor(factor: BlendFactor, src: [f32; 4], dst: [f32; 4], channel: usize) -> f32 {
+    match factor {
+        BlendFactor::Zero => 0.0,
+        BlendFactor::One => 1.0,
+        BlendFactor::SrcColor => src[channel],
+        BlendFactor::OneMinusSrcColor => 1.0 - src[channel],
+        BlendFactor::DstColor => dst[channel],
+        BlendFactor::OneMinusDstColor => 1.0 - dst[channel],
+        BlendFactor::SrcAlpha => src[3],
+        BlendFactor::OneMinusSrcAlpha => 1.0 - src[3],
+        BlendFactor::DstAlpha => dst[3],
+        BlendFactor::OneMinusDstAlpha => 1.0 - dst[3],
+        BlendFactor::SrcAlphaSaturate if channel == 3 => 1.0,
+        BlendFactor::SrcAlphaSaturate => src[3].min(1.0 - dst[3]),
+    }
+}
+
+/// Whether pixel centers exactly on the edge from `a` to `b` belong to the triangle. Shared
+/// edges are walked in opposite directions by their two triangles, so exactly one owns them.
+fn owns_edge(a: Point, b: Point) -> bool {
+    b.y > a.y || (b.y == a.y && b.x < a.x)
+}
+
+fn edge(a: Point, b: Point, p: Point) -> f32 {
+    (b.x - a.x) * (p.y - a.y) - (b.y - a.y) * (p.x - a.x)
+}
+
+/// Calls `f` with the pixel coordinates, interpolated uv and orientation (1 or -1) of every
+/// pixel center of the `width` x `height` target covered by the triangle
+fn rasterize<F: FnMut(usize, usize, (f32, f32), i32)>(
+    width: usize,
+    height: usize,
+    v: [&Vertex; 3],
+    mut f: F,
+) {
+    let mut p = [
+        Point::new(v[0].x, v[0].y),
+        Point::new(v[1].x, v[1].y),
+        Point::new(v[2].x, v[2].y),
+    ];
+    let mut uv = [(v[0].u, v[0].v), (v[1].u, v[1].v), (v[2].u, v[2].v)];
+    let mut area = edge(p[0], p[1], p[2]);
+    if area == 0.0 || !area.is_finite() {
+        return;
+    }
+
+    let orientation = if area > 0.0 { 1 } else { -1 };
+    if area < 0.0 {
+        p.swap(1, 2);
+        uv.swap(1, 2);
+        area = -area;
+    }
+
+    let min_x = p.iter().fold(f32::MAX, |m, p| m.min(p.x)).floor().max(0.0) as usize;
+    let min_y = p.iter().fold(f32::MAX, |m, p| m.min(p.y)).floor().max(0.0) as usize;
+    let max_x = (p.iter().fold(f32::MIN, |m, p| m.max(p.x)).ceil().max(0.0) as usize).min(width);
+    let max_y = (p.iter().fold(f32::MIN, |m, p| m.max(p.y)).ceil().max(0.0) as usize).min(height);
+
+    let owns = [
+        owns_edge(p[1], p[2]),
+        owns_edge(p[2], p[0]),
+        owns_edge(p[0], p[1]),
+    ];
+    for y in min_y..max_y {
+        for x in min_x..max_x {
+            let pt = Point::new(x as f32 + 0.5, y as f32 + 0.5);
+            let w = [
+                edge(p[1], p[2], pt),
+                edge(p[2], p[0], pt),
+                edge(p[0], p[1], pt),
+            ];
+            let inside = w
+                .iter()
+                .zip(&owns)
+                .all(|(w, owns)| *w > 0.0 || (*w == 0.0 && *owns));
+            if inside {
+                let (b0, b1, b2) = (w[0] / area, w[1] / area, w[2] / area);
+                let u = uv[0].0 * b0 + uv[1].0 * b1 + uv[2].0 * b2;
+                let v = uv[0].1 * b0 + uv[1].1 * b1 + uv[2].1 * b2;
+                f(x, y, (u, v), orientation);
+            }
+        }
+    }
+}
+
+fn fan(vertexes: &[Vertex]) -> impl Iterator<Item = [&Vertex; 3]> {
+    (2..vertexes.len()).map(move |i| [&vertexes[0], &vertexes[i - 1], &vertexes[i]])
+}
+
+fn strip(vertexes: &[Vertex]) -> impl Iterator<Item = [&Vertex; 3]> {
+    (2..vertexes.len()).map(move |i| [&vertexes[i - 2], &vertexes[i - 1], &vertexes[i]])
+}
+
+/// Renders into an RGBA8 buffer in memory, without a GPU or window. Meant for server side
+/// image generation and tests: it draws immediately and is much slower than a GPU backend.
+pub struct SoftwareRenderer {
+    width: usize,
+    height: usize,
+    device_pixel_ratio: f32,
+    pixels: Vec<u8>,
+    textures: Slab<Texture>,
+    /// Pixels draws are limited to while a clip is set
+    clip: Option<Vec<bool>>,
+    gamma_correct: bool,
+}
+
+impl SoftwareRenderer {
+    pub fn new(width: usize, height: usize) -> SoftwareRenderer {
+        SoftwareRenderer {
+            width,
+            height,
+            device_pixel_ratio: 1.0,
+            pixels: vec![0; width * height * 4],
+            textures: Default::default(),
+            clip: None,
+            gamma_correct: false,
+        }
+    }
+
+    pub fn set_device_pixel_ratio(&mut self, ratio: f32) {
+        self.device_pixel_ratio = ratio;
+    }
+
+    /// The rendered RGBA8 pixels, rows top to bottom
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    fn texture(&self, img: ImageId) -> Result<&Texture, NonaError> {
+        self.textures
+            .get(img)
+            .ok_or_else(|| NonaError::Texture(format!("texture '{}' not found", img)))
+    }
+
+    fn texture_mut(&mut self, img: ImageId) -> Result<&mut Texture, NonaError> {
+        self.textures
+            .get_mut(img)
+            .ok_or_else(|| NonaError::Texture(format!("texture '{}' not found", img)))
+    }
+
+    fn blend(&mut self, x: usize, y: usize, src: [f32; 4], op: CompositeOperationState) {
+        let idx = (y * self.width + x) * 4;
+        let pixel = &mut self.pixels[idx..idx + 4];
+        let mut dst = [
+            pixel[0] as f32 / 255.0,
+            pixel[1] as f32 / 255.0,
+            pixel[2] as f32 / 255.0,
+            pixel[3] as f32 / 255.0,
+        ];
+        if self.gamma_correct {
+            dst = map_premul(dst, srgb_to_linear);
+        }
+        let mut res = [0.0; 4];
+        for (i, out) in res.iter_mut().enumerate() {
+            let (src_factor, dst_factor) = if i == 3 {
+                (op.src_alpha, op.dst_alpha)
+            } else {
+                (op.src_rgb, op.dst_rgb)
+            };
+            let weighted_src = src[i] * blend_factor(src_factor, src, dst, i);
+            let weighted_dst = dst[i] * blend_factor(dst_factor, src, dst, i);
+            let value = match op.equation {
+                BlendEquation::Add => weighted_src + weighted_dst,
+                BlendEquation::Subtract => weighted_src - weighted_dst,
+                BlendEquation::ReverseSubtract => weighted_dst - weighted_src,
+                BlendEquation::Min => src[i].min(dst[i]),
+                BlendEquation::Max => src[i].max(dst[i]),
+            };
+            *out = value.clamp(0.0, 1.0);
+        }
+        if self.gamma_correct {
+            res = map_premul(res, linear_to_srgb);
+        }
+        for (out, value) in pixel.iter_mut().zip(&res) {
+            *out = (value * 255.0).round() as u8;
+        }
+    }
+
+    /// Winding number of the fill fans of `paths` at each pixel
+    fn winding(&self, paths: &[Path]) -> Vec<i32> {
+        let (width, height) = (self.width, self.height);
+        let mut winding = vec![0i32; width * height];
+        for path in paths {
+            for triangle in fan(path.get_fill()) {
+                rasterize(width, height, triangle, |x, y, _, orientation| {
+                    winding[y * width + x] += orientation;
+                });
+            }
+        }
+        winding
+    }
+
+    fn draw_triangles<'a, I: Iterator<Item = [&'a Vertex; 3]>>(
+        &mut self,
+        triangles: I,
+        shader: &Shader,
+        op: CompositeOperationState,
+        mask: Option<&dyn Fn(usize, usize) -> bool>,
+    ) {
+        let (width, height) = (self.width, self.height);
+        let clip = self.clip.as_deref();
+        let mut covered = Vec::new();
+        for triangle in triangles {
+            rasterize(width, height, triangle, |x, y, uv, _| {
+                if let Some(mask) = mask {
+                    if !mask(x, y) {
+                        return;
+                    }
+                }
+                if let Some(clip) = clip {
+                    if !clip[y * width + x] {
+                        return;
+                    }
+                }
+                covered.push((x, y, uv));
+            });
+        }
+        for (x, y, uv) in covered {
+            let pos = Point::new(x as f32 + 0.5, y as f32 + 0.5);
+            let color = shader.shade(&self.textures, pos, uv);
+            self.blend(x, y, color, op);
+        }
+    }
+}
+
+impl Renderer for SoftwareRenderer {
+    fn edge_antialias(&self) -> bool {
+        true
+    }
+
+    fn caps(&self) -> RendererCaps {
+        RendererCaps {
+            supports_stencil: true,
+            max_texture_size: self.max_texture_size(),
+            supports_u32_indices: true,
+        }
+    }
+
+    fn view_size(&self) -> (f32, f32) {
+        (self.width as f32, self.height as f32)
+    }
+
+    fn device_pixel_ratio(&self) -> f32 {
+        self.device_pixel_ratio
+    }
+
+    fn max_texture_size(&self) -> usize {
+        16384
+    }
+
+    fn create_texture(
+        &mut self,
+        texture_type: TextureType,
+        width: usize,
+        height: usize,
+        flags: ImageFlags,
+        data: Option<&[u8]>,
+    ) -> Result<ImageId, NonaError> {
+        let mut texture = Texture {
+            texture_type,
+            width,
+            height,
+            flags,
+            data: Vec::new(),
+        };
+        let size = width * height * texture.bytes_per_pixel();
+        texture.data = match data {
+            Some(data) if data.len() < size => {
+                return Err(NonaError::Texture(format!(
+                    "expected {} bytes of texture data, got {}",
+                    size,
+                    data.len()
+                )))
+            }
+            Some(data) => data[..size].to_vec(),
+            None => vec![0; size],
+        };
+        Ok(self.textures.insert(texture))
+    }
+
+    fn delete_texture(&mut self, img: ImageId) -> Result<(), NonaError> {
+        self.texture(img)?;
+        self.textures.remove(img);
+        Ok(())
+    }
+
+    fn update_texture(
+        &mut self,
+        img: ImageId,
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+        data: &[u8],
+    ) -> Result<(), NonaError> {
+        let texture = self.texture_mut(img)?;
+        let bpp = texture.bytes_per_pixel();
+        if x + width > texture.width || y + height > texture.height {
+            return Err(NonaError::Texture(format!(
+                "update of texture '{}' out of bounds",
+                img
+            )));
+        }
+        if data.len() < width * height * bpp {
+            return Err(NonaError::Texture(format!(
+                "expected {} bytes of texture data, got {}",
+                width * height * bpp,
+                data.len()
+            )));
+        }
+
+        for row in 0..height {
+            let src = &data[row * width * bpp..(row + 1) * width * bpp];
+            let start = ((y + row) * texture.width + x) * bpp;
+            texture.data[start..start + width * bpp].copy_from_slice(src);
+        }
+        Ok(())
+    }
+
+    fn texture_size(&self, img: ImageId) -> Result<(usize, usize), NonaError> {
+        let texture = self.texture(img)?;
+        Ok((texture.width, texture.height))
+    }
+
+    fn set_texture_repeat(
+        &mut self,
+        img: ImageId,
+        repeat_x: bool,
+        repeat_y: bool,
+    ) -> Result<(), NonaError> {
+        let texture = self.texture_mut(img)?;
+        texture.flags.set(ImageFlags::REPEATX, repeat_x);
+        texture.flags.set(ImageFlags::REPEATY, repeat_y);
+        Ok(())
+    }
+
+    fn set_texture_nearest(&mut self, img: ImageId, nearest: bool) -> Result<(), NonaError> {
+        self.texture_mut(img)?
+            .flags
+            .set(ImageFlags::NEAREST, nearest);
+        Ok(())
+    }
+
+    fn viewport(&mut self, _extent: Extent, _device_pixel_ratio: f32) -> Result<(), NonaError> {
+        Ok(())
+    }
+
+    fn set_gamma_correct(&mut self, enabled: bool) -> Result<(), NonaError> {
+        self.gamma_correct = enabled;
+        Ok(())
+    }
+
+    fn clear_screen(&mut self, color: Color) {
+        let color = [color.r, color.g, color.b, color.a]
+            .iter()
+            .map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8)
+            .collect::<Vec<_>>();
+        for pixel in self.pixels.chunks_mut(4) {
+            pixel.copy_from_slice(&color);
+        }
+    }
+
+    fn flush(&mut self) -> Result<(), NonaError> {
+        // everything is drawn as it is submitted
+        self.clip = None;
+        Ok(())
+    }
+
+    fn read_pixels(
+        &mut self,
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+    ) -> Result<Vec<u8>, NonaError> {
+        if x + width > self.width || y + height > self.height {
+            return Err(NonaError::Image(format!(
+                "region {}x{} at ({}, {}) is outside of the {}x{} target",
+                width, height, x, y, self.width, self.height
+            )));
+        }
+
+        let mut res = Vec::with_capacity(width * height * 4);
+        for row in y..y + height {
+            let start = (row * self.width + x) * 4;
+            res.extend_from_slice(&self.pixels[start..start + width * 4]);
+        }
+        Ok(res)
+    }
+
+    fn fill(
+        &mut self,
+        paint: &Paint,
+        composite_operation: CompositeOperationState,
+        scissor: &Scissor,
+        params: &FillParams,
+        paths: &[Path],
+    ) -> Result<(), NonaError> {
+        let FillParams {
+            fringe,
+            bounds,
+            fill_rule,
+        } = *params;
+        let shader = Shader::new(
+            &self.textures,
+            paint,
+            scissor,
+            fringe,
+            fringe,
+            self.gamma_correct,
+        );
+
+        if paths.len() == 1 && paths[0].convex {
+            let path = &paths[0];
+            let triangles = fan(path.get_fill()).chain(strip(path.get_stroke()));
+            self.draw_triangles(triangles, &shader, composite_operation, None);
+            return Ok(());
+        }
+
+        // stands in for the stencil buffer of the GPU backends
+        let winding = self.winding(paths);
+        let width = self.width;
+
+        // antialiased fringes only outside of the shape, then cover the inside
+        let outside = |x: usize, y: usize| winding[y * width + x] == 0;
+        let fringes = paths.iter().flat_map(|path| strip(path.get_stroke()));
+        self.draw_triangles(fringes, &shader, composite_operation, Some(&outside));
+
+        let cover = [
+            Vertex::new(bounds.max.x, bounds.max.y, 0.5, 1.0),
+            Vertex::new(bounds.max.x, bounds.min.y, 0.5, 1.0),
+            Vertex::new(bounds.min.x, bounds.max.y, 0.5, 1.0),
+            Vertex::new(bounds.min.x, bounds.min.y, 0.5, 1.0),
+        ];
+        let inside = |x: usize, y: usize| match fill_rule {
+            FillRule::NonZero => winding[y * width + x] != 0,
+            FillRule::EvenOdd => winding[y * width + x] % 2 != 0,
+        };
+        self.draw_triangles(strip(&cover), &shader, composite_operation, Some(&inside));
+        Ok(())
+    }
+
+    fn stroke(
+        &mut self,
+        paint: &Paint,
+        composite_operation: CompositeOperationState,
+        scissor: &Scissor,
+        params: &StrokeParams,
+        paths: &[Path],
+    ) -> Result<(), NonaError> {
+        let StrokeParams {
+            fringe,
+            stroke_width,
+            gradient_along_path,
+        } = *params;
+        let mut shader = Shader::new(
+            &self.textures,
+            paint,
+            scissor,
+            stroke_width,
+            fringe,
+            self.gamma_correct,
+        );
+        if gradient_along_path && paint.image.is_none() {
+            shader.shader_type = ShaderType::StrokeGradientAlongPath;
+        }
+
+        let triangles = paths.iter().flat_map(|path| strip(path.get_stroke()));
+        self.draw_triangles(triangles, &shader, composite_operation, None);
+        Ok(())
+    }
+
+    fn triangles(
+        &mut self,
+        paint: &Paint,
+        composite_operation: CompositeOperationState,
+        scissor: &Scissor,
+        vertexes: &[Vertex],
+    ) -> Result<(), NonaError> {
+        let mut shader = Shader::new(&self.textures, paint, scissor, 1.0, 1.0, self.gamma_correct);
+        shader.shader_type = ShaderType::Image;
+
+        let triangles = vertexes.chunks_exact(3).map(|v| [&v[0], &v[1], &v[2]]);
+        self.draw_triangles(triangles, &shader, composite_operation, None);
+        Ok(())
+    }
+
+    fn clip(&mut self, fill_rule: FillRule, paths: &[Path]) -> Result<(), NonaError> {
+        let winding = self.winding(paths);
+        let clip = self.clip.get_or_insert_with(|| vec![true; winding.len()]);
+        for (clip, winding) in clip.iter_mut().zip(winding) {
+            *clip &= match fill_rule {
+                FillRule::NonZero => winding != 0,
+                FillRule::EvenOdd => winding % 2 != 0,
+            };
+        }
+        Ok(())
+    }
+
+    fn reset_clip(&mut self) -> Result<(), NonaError> {
+        self.clip = None;
+        Ok(())
+    }
+}
@@ -0,0 +1,178 @@
+use crate::{NonaError, Point};
+use std::f32::consts::PI;
+
+/// Tokenizer for the SVG path data syntax (the `d` attribute)
+pub(crate) struct PathParser<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> PathParser<'a> {
+    pub(crate) fn new(data: &'a str) -> PathParser<'a> {
+        PathParser {
+            data: data.as_bytes(),
+            pos: 0,
+        }
+    }
+
+    fn skip_separators(&mut self) {
+        while let Some(c) = self.data.get(self.pos) {
+            if c.is_ascii_whitespace() || *c == b',' {
+                self.pos += 1;
+            } else {
+                break;
+            }
+        }
+    }
+
+    pub(crate) fn is_end(&mut self) -> bool {
+        self.skip_separators();
+        self.pos >= self.data.len()
+    }
+
+    /// Consumes the next command letter, if the next token is one
+    pub(crate) fn command(&mut self) -> Option<u8> {
+        self.skip_separators();
+        match self.data.get(self.pos) {
+            Some(c) if c.is_ascii_alphabetic() => {
+                self.pos += 1;
+                Some(*c)
+            }
+            _ => None,
+        }
+    }
+
+    pub(crate) fn number(&mut self) -> Result<f32, NonaError> {
+        self.skip_separators();
+        let start = self.pos;
+        let digits = |parser: &mut Self| {
+            while let Some(b'0'..=b'9') = parser.data.get(parser.pos) {
+                parser.pos += 1;
+            }
+        };
+
+        if let Some(b'+') | Some(b'-') = self.data.get(self.pos) {
+            self.pos += 1;
+        }
+        digits(self);
+        if let Some(b'.') = self.data.get(self.pos) {
+            self.pos += 1;
+            digits(self);
+        }
+        if let Some(b'e') | Some(b'E') = self.data.get(self.pos) {
+            self.pos += 1;
+            if let Some(b'+') | Some(b'-') = self.data.get(self.pos) {
+                self.pos += 1;
+            }
+            digits(self);
+        }
+
+        std::str::from_utf8(&self.data[start..self.pos])
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .ok_or_else(|| self.error("expected a number", start))
+    }
+
+    pub(crate) fn point(&mut self) -> Result<Point, NonaError> {
+        Ok(Point::new(self.number()?, self.number()?))
+    }
+
+    /// Arc flags are a single `0` or `1` and may be written without separators
+    pub(crate) fn flag(&mut self) -> Result<bool, NonaError> {
+        self.skip_separators();
+        let res = match self.data.get(self.pos) {
+            Some(b'0') => false,
+            Some(b'1') => true,
+            _ => return Err(self.error("expected an arc flag", self.pos)),
+        };
+        self.pos += 1;
+        Ok(res)
+    }
+
+    pub(crate) fn error(&self, message: &str, pos: usize) -> NonaError {
+        NonaError::Svg(format!("{} at offset {}", message, pos))
+    }
+
+    pub(crate) fn pos(&self) -> usize {
+        self.pos
+    }
+}
+
+/// Converts an SVG elliptical arc from `p0` to `p1` into cubic bezier segments of at most
+/// 90 degrees each, returned as (control 1, control 2, end point).
+pub(crate) fn arc_to_beziers(
+    p0: Point,
+    radius: Point,
+    x_axis_rotation: f32,
+    large_arc: bool,
+    sweep: bool,
+    p1: Point,
+) -> Vec<(Point, Point, Point)> {
+    let mut rx = radius.x.abs();
+    let mut ry = radius.y.abs();
+    let (sin, cos) = x_axis_rotation.to_radians().sin_cos();
+
+    let dx2 = (p0.x - p1.x) * 0.5;
+    let dy2 = (p0.y - p1.y) * 0.5;
+    let x1p = cos * dx2 + sin * dy2;
+    let y1p = -sin * dx2 + cos * dy2;
+
+    // scale up radii that are too small to reach the end point
+    let lambda = (x1p * x1p) / (rx * rx) + (y1p * y1p) / (ry * ry);
+    if lambda > 1.0 {
+        rx *= lambda.sqrt();
+        ry *= lambda.sqrt();
+    }
+
+    let num = rx * rx * ry * ry - rx * rx * y1p * y1p - ry * ry * x1p * x1p;
+    let den = rx * rx * y1p * y1p + ry * ry * x1p * x1p;
+    let mut coef = if den > 0.0 {
+        (num / den).max(0.0).sqrt()
+    } else {
+        0.0
+    };
+    if large_arc == sweep {
+        coef = -coef;
+    }
+    let cxp = coef * rx * y1p / ry;
+    let cyp = -coef * ry * x1p / rx;
+    let cx = cos * cxp - sin * cyp + (p0.x + p1.x) * 0.5;
+    let cy = sin * cxp + cos * cyp + (p0.y + p1.y) * 0.5;
+
+    let angle = |ux: f32, uy: f32, vx: f32, vy: f32| (ux * vy - uy * vx).atan2(ux * vx + uy * vy);
+    let a0 = angle(1.0, 0.0, (x1p - cxp) / rx, (y1p - cyp) / ry);
+    let mut da = angle(
+        (x1p - cxp) / rx,
+        (y1p - cyp) / ry,
+        (-x1p - cxp) / rx,
+        (-y1p - cyp) / ry,
+    );
+    if !sweep && da > 0.0 {
+        da -= PI * 2.0;
+    } else if sweep && da < 0.0 {
+        da += PI * 2.0;
+    }
+
+    let segments = (da.abs() / (PI * 0.5)).ceil().max(1.0) as usize;
+    let delta = da / segments as f32;
+    let kappa = 4.0 / 3.0 * (delta / 4.0).tan();
+    let map = |x: f32, y: f32| {
+        Point::new(
+            cx + cos * rx * x - sin * ry * y,
+            cy + sin * rx * x + cos * ry * y,
+        )
+    };
+
+    (0..segments)
+        .map(|i| {
+            let (s0, c0) = (a0 + delta * i as f32).sin_cos();
+            let (s1, c1) = (a0 + delta * (i + 1) as f32).sin_cos();
+            (
+                map(c0 - kappa * s0, s0 + kappa * c0),
+                map(c1 + kappa * s1, s1 - kappa * c1),
+                // end exactly on `p1`, so following relative commands don't drift
+                if i + 1 == segments { p1 } else { map(c1, s1) },
+            )
+        })
+        .collect()
+}
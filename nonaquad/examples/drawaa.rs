@@ -37,7 +37,7 @@ impl EventHandler for Stage {
                     .begin_frame(Some(Color::rgb_i(128, 128, 255)))
                     .unwrap();
 
-                // uncomment to draw a lot of circles - more than maximum GPU vertices on openGL ES 2/WebGL
+                // uncomment to draw a lot of circles - more than fit in a single GPU vertex buffer on openGL ES 2/WebGL
                 // note: performance is currently low, very CPU-bound. Something to fix in the future.
                 // for i in 0..405 {
                 //     canvas.begin_path();
@@ -124,6 +124,6 @@ fn main() {
             window_title: String::from("Draw test"),
             ..Default::default()
         },
-        |mut ctx| UserData::owning(Stage::new(&mut ctx), ctx),
+        |ctx| Box::new(Stage::new(ctx)),
     );
 }
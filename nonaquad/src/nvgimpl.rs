@@ -1,14 +1,17 @@
 use glam::{Mat4, Vec4};
-use miniquad::graphics::Context as MiniContext;
+use miniquad::Context as MiniContext;
+use miniquad::gl::{self, GLenum};
 use miniquad::graphics::*;
 use nona::{renderer::*, NonaError};
 use slab::Slab;
+use std::ops::Range;
 
 enum ShaderType {
     FillGradient,
     FillImage,
     Simple,
     Image,
+    StrokeGradientAlongPath,
 }
 
 #[derive(PartialEq, Eq, Debug)]
@@ -17,12 +20,22 @@ enum CallType {
     ConvexFill,
     Stroke,
     Triangles,
+    Clip,
+    ResetClip,
 }
 
+/// Stencil bit marking pixels outside of the clip paths. Fills count their winding in the
+/// remaining bits.
+const CLIP_BIT: u32 = 0x80;
+const WINDING_BITS: u32 = 0x7f;
+
 /// Color and Alpha blend states
+#[derive(PartialEq)]
 struct Blend {
     pub color: BlendState,
     pub alpha: BlendState,
+    /// miniquad has no min/max equations, so the equation is set directly on GL
+    pub equation: GLenum,
 }
 
 impl From<CompositeOperationState> for Blend {
@@ -38,6 +51,13 @@ impl From<CompositeOperationState> for Blend {
                 convert_blend_factor(state.src_alpha),
                 convert_blend_factor(state.dst_alpha),
             ),
+            equation: match state.equation {
+                BlendEquation::Add => gl::GL_FUNC_ADD,
+                BlendEquation::Subtract => gl::GL_FUNC_SUBTRACT,
+                BlendEquation::ReverseSubtract => gl::GL_FUNC_REVERSE_SUBTRACT,
+                BlendEquation::Min => GL_MIN,
+                BlendEquation::Max => GL_MAX,
+            },
         }
     }
 }
@@ -51,6 +71,116 @@ struct Call {
     triangle_count: usize,
     uniform_offset: usize,
     blend_func: Blend,
+    fill_rule: FillRule,
+    /// First vertex of the buffer chunk this call draws from, its offsets are relative to it
+    vertex_base: usize,
+    /// Axis aligned envelope of the scissor, used for the hardware scissor test
+    scissor_bounds: Option<Bounds>,
+}
+
+impl Call {
+    /// Whether `next` draws triangles right after this call's with the same state besides
+    /// uniforms, so both can be drawn as one. The uniforms also hold the scissor.
+    fn continued_by(&self, next: &Call) -> bool {
+        self.call_type == CallType::Triangles
+            && next.call_type == CallType::Triangles
+            && self.image == next.image
+            && self.blend_func == next.blend_func
+            && self.vertex_base == next.vertex_base
+            && self.triangle_offset + self.triangle_count == next.triangle_offset
+    }
+}
+
+/// Copy of what the vertex buffer holds, to skip uploading unchanged geometry
+struct UploadedVertexes(Vec<Vertex>);
+
+impl UploadedVertexes {
+    /// Records `chunk` as uploaded, returning whether it differs from the buffer contents
+    fn replace(&mut self, chunk: &[Vertex]) -> bool {
+        if self.0 == chunk {
+            return false;
+        }
+        self.0.clear();
+        self.0.extend_from_slice(chunk);
+        true
+    }
+}
+
+/// Bindings of a flush, with the vertex buffer holding one chunk of the frame at a time
+struct ChunkBindings<'a> {
+    bindings: &'a mut Bindings,
+    vertexes: &'a [Vertex],
+    uploaded: &'a mut UploadedVertexes,
+    bound_base: Option<usize>,
+    max_vertices: usize,
+    /// Indices narrowed for a 16-bit index buffer, `None` when it takes 32-bit indices
+    short_indices: Option<&'a mut Vec<u16>>,
+}
+
+impl ChunkBindings<'_> {
+    /// Makes the vertex buffer hold the chunk starting at `vertex_base`
+    fn bind(&mut self, ctx: &mut MiniContext, vertex_base: usize) {
+        if self.bound_base == Some(vertex_base) {
+            return;
+        }
+        self.bound_base = Some(vertex_base);
+        let end = (vertex_base + self.max_vertices).min(self.vertexes.len());
+        let chunk = &self.vertexes[vertex_base..end];
+
+        ctx.apply_bindings(self.bindings); // NEEDED - must be called before vertex buffer update; TODO_BUG: can be optimized in miniquad; we only need to update index buffer in most cases, see do_convex_fill()
+                                           // static frames produce the same geometry every time, the GPU already has it
+        if self.uploaded.replace(chunk) {
+            self.bindings.vertex_buffers[0].update(ctx, chunk); // TODO: miniquad BUG? this line must show after apply_bindings otherwise no display of vertex buffer can happen
+        }
+    }
+
+    /// Draws `indices` of the chunk starting at `vertex_base`
+    fn draw(&mut self, ctx: &mut MiniContext, indices: &[u32], vertex_base: usize) {
+        if indices.is_empty() {
+            return;
+        }
+        self.bind(ctx, vertex_base);
+        match &mut self.short_indices {
+            // chunks of 16-bit buffers are small enough for their offsets to fit
+            Some(short_indices) => {
+                short_indices.clear();
+                short_indices.extend(indices.iter().map(|i| *i as u16));
+                self.bindings
+                    .index_buffer
+                    .update(ctx, short_indices.as_slice());
+            }
+            None => self.bindings.index_buffer.update(ctx, indices),
+        }
+        ctx.apply_bindings(self.bindings);
+        ctx.draw(0, indices.len() as i32, 1);
+    }
+
+    /// Draws the triangles `add` gives for each of `paths`, once per run of paths in the same
+    /// chunk
+    fn draw_paths(
+        &mut self,
+        ctx: &mut MiniContext,
+        indices: &mut Vec<u32>,
+        paths: &[GLPath],
+        add: impl Fn(&mut Vec<u32>, &GLPath),
+    ) {
+        let mut start = 0;
+        while start < paths.len() {
+            let vertex_base = paths[start].vertex_base;
+            let end = start
+                + paths[start..]
+                    .iter()
+                    .take_while(|path| path.vertex_base == vertex_base)
+                    .count();
+            indices.clear();
+            for path in &paths[start..end] {
+                add(indices, path);
+            }
+            self.draw(ctx, indices, vertex_base);
+            start = end;
+        }
+        indices.clear();
+    }
 }
 
 struct Texture {
@@ -65,6 +195,8 @@ impl Drop for Texture {
 }
 
 struct GLPath {
+    /// First vertex of the buffer chunk the offsets are relative to
+    vertex_base: usize,
     fill_offset: usize,
     fill_count: usize,
     stroke_offset: usize,
@@ -82,8 +214,19 @@ pub struct Renderer {
     calls: Vec<Call>,
     paths: Vec<GLPath>,
     vertexes: Vec<Vertex>,
-    indices: Vec<u16>,
+    uploaded_vertexes: UploadedVertexes,
+    chunk_start: usize,
+    /// Vertexes a chunk holds, more when the index buffer takes 32-bit indices
+    max_vertices: usize,
+    indices: Vec<u32>,
+    /// Whether the index buffer takes 32-bit indices, otherwise `short_indices` narrows them
+    u32_indices: bool,
+    short_indices: Vec<u16>,
     uniforms: Vec<shader::Uniforms>,
+    clear_color: Option<Color>,
+    max_texture_size: usize,
+    /// Whether the queued calls leave a clip set
+    clipping: bool,
 }
 
 pub struct RendererCtx<'a> {
@@ -92,6 +235,8 @@ pub struct RendererCtx<'a> {
 }
 
 impl Renderer {
+    /// Binds the miniquad context of the current frame, giving the `nona::Renderer` to draw
+    /// with. The renderer itself outlives any single context borrow.
     pub fn with_context<'a>(&'a mut self, ctx: &'a mut MiniContext) -> RendererCtx<'a> {
         RendererCtx {
             renderer: self,
@@ -134,7 +279,7 @@ mod shader {
         }
     }
 
-    #[derive(Default)]
+    #[derive(Default, PartialEq)]
     #[repr(C)]
     pub struct Uniforms {
         pub view_size: (f32, f32),
@@ -154,8 +299,15 @@ mod shader {
     }
 }
 
+// not exported by miniquad::gl on every platform
+const GL_MIN: GLenum = 0x8007;
+const GL_MAX: GLenum = 0x8008;
+
 const MAX_VERTICES: usize = 21845; // u16.max / 3 due to index buffer limitations
 const MAX_INDICES: usize = u16::max_value() as usize;
+// with 32-bit indices the chunk size only bounds the memory of the stream buffers
+const MAX_VERTICES_U32: usize = 1 << 18;
+const MAX_INDICES_U32: usize = MAX_VERTICES_U32 * 3;
 
 impl Renderer {
     pub fn create(ctx: &mut MiniContext) -> Result<Renderer, NonaError> {
@@ -171,19 +323,34 @@ impl Renderer {
                 color_blend: None, // set during draws
                 color_write: (true, true, true, true),
                 front_face_order: FrontFaceOrder::CounterClockwise,
+                // fill fringes overlap the interior on their back side, culling avoids blending twice
+                cull_face: CullFace::Back,
                 ..Default::default()
             },
         );
 
+        // GLES2 and WebGL 1 only have 32-bit indices through an extension, miniquad tells
+        // them apart by the lack of instancing
+        let u32_indices = ctx.features().instancing;
+        let (max_vertices, index_buffer) = if u32_indices {
+            let buffer = Buffer::index_stream(
+                ctx,
+                IndexType::Int,
+                MAX_INDICES_U32 * std::mem::size_of::<u32>(),
+            );
+            (MAX_VERTICES_U32, buffer)
+        } else {
+            let buffer = Buffer::index_stream(
+                ctx,
+                IndexType::Short,
+                MAX_INDICES * std::mem::size_of::<u16>(),
+            );
+            (MAX_VERTICES, buffer)
+        };
         let vertex_buffer = Buffer::stream(
             ctx,
             BufferType::VertexBuffer,
-            MAX_VERTICES * std::mem::size_of::<Vertex>(),
-        );
-        let index_buffer = Buffer::stream(
-            ctx,
-            BufferType::IndexBuffer,
-            MAX_INDICES * std::mem::size_of::<u16>(),
+            max_vertices * std::mem::size_of::<Vertex>(),
         );
 
         let pixels: [u8; 4 * 4 * 4] = [
@@ -208,9 +375,22 @@ impl Renderer {
             view: Default::default(),
             calls: Default::default(),
             paths: Default::default(),
-            vertexes: Default::default(),
-            indices: Default::default(),
+            // sized to the GPU buffers so frames never reallocate; flush only clears these
+            vertexes: Vec::with_capacity(max_vertices),
+            uploaded_vertexes: UploadedVertexes(Vec::with_capacity(max_vertices)),
+            chunk_start: 0,
+            max_vertices,
+            indices: Vec::with_capacity(max_vertices * 3),
+            u32_indices,
+            short_indices: if u32_indices {
+                Vec::new()
+            } else {
+                Vec::with_capacity(MAX_INDICES)
+            },
             uniforms: Default::default(),
+            clear_color: None,
+            max_texture_size: query_max_texture_size(),
+            clipping: false,
         })
     }
 
@@ -231,34 +411,40 @@ impl Renderer {
         ctx: &mut MiniContext,
         call: &Call,
         paths: &[GLPath],
-        bindings: &Bindings,
-        indices: &mut Vec<u16>,
+        bindings: &mut ChunkBindings,
+        indices: &mut Vec<u32>,
         uniforms: &shader::Uniforms,
         uniforms_next: &shader::Uniforms,
     ) {
         indices.clear();
         // TODO: test!!!
 
+        // non-zero counts front and back faces; even-odd only tracks the parity of overlaps
+        let (front_op, back_op) = match call.fill_rule {
+            FillRule::NonZero => (StencilOp::IncrementWrap, StencilOp::DecrementWrap),
+            FillRule::EvenOdd => (StencilOp::Invert, StencilOp::Invert),
+        };
         ctx.set_stencil(Some(StencilState {
             front: StencilFaceState {
                 fail_op: StencilOp::Keep,
                 depth_fail_op: StencilOp::Keep,
-                pass_op: StencilOp::IncrementWrap,
-                test_func: CompareFunc::Always,
+                pass_op: front_op,
+                test_func: CompareFunc::Equal,
                 test_ref: 0,
-                test_mask: 0xff,
-                write_mask: 0xff,
+                test_mask: CLIP_BIT,
+                write_mask: WINDING_BITS,
             },
             back: StencilFaceState {
                 fail_op: StencilOp::Keep,
                 depth_fail_op: StencilOp::Keep,
-                pass_op: StencilOp::DecrementWrap,
-                test_func: CompareFunc::Always,
+                pass_op: back_op,
+                test_func: CompareFunc::Equal,
                 test_ref: 0,
-                test_mask: 0xff,
-                write_mask: 0xff,
+                test_mask: CLIP_BIT,
+                write_mask: WINDING_BITS,
             },
         }));
+        // windings are only counted inside the clip, so the cover pass skips clipped pixels
         ctx.set_color_write((false, false, false, false));
         // glEnable(GL_STENCIL_TEST);
         // glStencilMask(0xff);
@@ -269,16 +455,8 @@ impl Renderer {
         // glStencilOpSeparate(GL_BACK, GL_KEEP, GL_KEEP, GL_DECR_WRAP);
         // glDisable(GL_CULL_FACE);
         ctx.set_cull_face(CullFace::Nothing);
-        for path in paths {
-            // glDrawArrays(GL_TRIANGLE_FAN, path.fill_offset as i32, path.fill_count as i32);
-            Self::add_triangle_fan(indices, path.fill_offset as u16, path.fill_count as u16);
-        }
-
-        // draw
-        bindings.index_buffer.update(ctx, &indices);
-        ctx.apply_bindings(bindings);
-        ctx.draw(0, indices.len() as i32, 1);
-        indices.clear();
+        // glDrawArrays(GL_TRIANGLE_FAN, path.fill_offset as i32, path.fill_count as i32);
+        bindings.draw_paths(ctx, indices, paths, Self::add_path_fill);
 
         // glEnable(GL_CULL_FACE);
         ctx.set_cull_face(CullFace::Back);
@@ -309,15 +487,9 @@ impl Renderer {
                 write_mask: 0xff,
             },
         }));
-        for path in paths {
-            // glDrawArrays(GL_TRIANGLE_STRIP, path.stroke_offset as i32, path.stroke_count as i32);
-            Self::add_triangle_strip(indices, path.stroke_offset as u16, path.stroke_count as u16);
-        }
-        bindings.index_buffer.update(ctx, &indices);
-        ctx.apply_bindings(bindings);
-        ctx.draw(0, indices.len() as i32, 1);
+        // glDrawArrays(GL_TRIANGLE_STRIP, path.stroke_offset as i32, path.stroke_count as i32);
+        bindings.draw_paths(ctx, indices, paths, Self::add_path_stroke);
 
-        indices.clear();
         // glStencilFunc(GL_NOTEQUAL, 0x00, 0xff);
         // glStencilOp(GL_ZERO, GL_ZERO, GL_ZERO);
         ctx.set_stencil(Some(StencilState {
@@ -327,8 +499,8 @@ impl Renderer {
                 pass_op: StencilOp::Zero,
                 test_func: CompareFunc::NotEqual,
                 test_ref: 0,
-                test_mask: 0xff,
-                write_mask: 0xff,
+                test_mask: WINDING_BITS,
+                write_mask: WINDING_BITS,
             },
             back: StencilFaceState {
                 fail_op: StencilOp::Zero,
@@ -336,24 +508,136 @@ impl Renderer {
                 pass_op: StencilOp::Zero,
                 test_func: CompareFunc::NotEqual,
                 test_ref: 0,
-                test_mask: 0xff,
-                write_mask: 0xff,
+                test_mask: WINDING_BITS,
+                write_mask: WINDING_BITS,
             },
         }));
         // glDrawArrays(GL_TRIANGLE_STRIP, call.triangle_offset as i32, call.triangle_count as i32);
-        Self::add_triangle_strip(
-            indices,
-            call.triangle_offset as u16,
-            call.triangle_count as u16,
-        );
-        bindings.index_buffer.update(ctx, &indices);
-        ctx.apply_bindings(bindings);
-        ctx.draw(0, indices.len() as i32, 1);
+        Self::add_triangle_strip(indices, call.triangle_offset, call.triangle_count);
+        bindings.draw(ctx, indices, call.vertex_base);
 
         ctx.set_stencil(None);
         // glDisable(GL_STENCIL_TEST);
     }
 
+    /// Sets `CLIP_BIT` outside of the fill of `paths`, keeping it where it is already set
+    fn do_clip(
+        ctx: &mut MiniContext,
+        call: &Call,
+        paths: &[GLPath],
+        bindings: &mut ChunkBindings,
+        indices: &mut Vec<u32>,
+        uniforms: &shader::Uniforms,
+    ) {
+        indices.clear();
+        Self::set_uniforms(ctx, uniforms, call.image);
+        ctx.set_color_write((false, false, false, false));
+        ctx.set_cull_face(CullFace::Nothing);
+
+        // count windings inside the current clip, as do_fill does
+        let (front_op, back_op) = match call.fill_rule {
+            FillRule::NonZero => (StencilOp::IncrementWrap, StencilOp::DecrementWrap),
+            FillRule::EvenOdd => (StencilOp::Invert, StencilOp::Invert),
+        };
+        ctx.set_stencil(Some(StencilState {
+            front: StencilFaceState {
+                fail_op: StencilOp::Keep,
+                depth_fail_op: StencilOp::Keep,
+                pass_op: front_op,
+                test_func: CompareFunc::Equal,
+                test_ref: 0,
+                test_mask: CLIP_BIT,
+                write_mask: WINDING_BITS,
+            },
+            back: StencilFaceState {
+                fail_op: StencilOp::Keep,
+                depth_fail_op: StencilOp::Keep,
+                pass_op: back_op,
+                test_func: CompareFunc::Equal,
+                test_ref: 0,
+                test_mask: CLIP_BIT,
+                write_mask: WINDING_BITS,
+            },
+        }));
+        bindings.draw_paths(ctx, indices, paths, Self::add_path_fill);
+
+        // over the whole view: no winding becomes clipped, any winding is reset to unclipped
+        let face = StencilFaceState {
+            fail_op: StencilOp::Zero,
+            depth_fail_op: StencilOp::Zero,
+            pass_op: StencilOp::Replace,
+            test_func: CompareFunc::Equal,
+            test_ref: CLIP_BIT as i32,
+            test_mask: WINDING_BITS,
+            write_mask: 0xff,
+        };
+        ctx.set_stencil(Some(StencilState {
+            front: face,
+            back: face,
+        }));
+        Self::add_triangle_strip(indices, call.triangle_offset, call.triangle_count);
+        bindings.draw(ctx, indices, call.vertex_base);
+
+        ctx.set_stencil(None);
+        ctx.set_cull_face(CullFace::Back);
+        ctx.set_color_write((true, true, true, true));
+    }
+
+    /// Clears `CLIP_BIT` over the whole view
+    fn do_reset_clip(
+        ctx: &mut MiniContext,
+        call: &Call,
+        bindings: &mut ChunkBindings,
+        indices: &mut Vec<u32>,
+        uniforms: &shader::Uniforms,
+    ) {
+        indices.clear();
+        Self::set_uniforms(ctx, uniforms, call.image);
+        ctx.set_color_write((false, false, false, false));
+        ctx.set_cull_face(CullFace::Nothing);
+
+        let face = StencilFaceState {
+            fail_op: StencilOp::Zero,
+            depth_fail_op: StencilOp::Zero,
+            pass_op: StencilOp::Zero,
+            test_func: CompareFunc::Always,
+            test_ref: 0,
+            test_mask: 0xff,
+            write_mask: CLIP_BIT,
+        };
+        ctx.set_stencil(Some(StencilState {
+            front: face,
+            back: face,
+        }));
+        Self::add_triangle_strip(indices, call.triangle_offset, call.triangle_count);
+        bindings.draw(ctx, indices, call.vertex_base);
+
+        ctx.set_stencil(None);
+        ctx.set_cull_face(CullFace::Back);
+        ctx.set_color_write((true, true, true, true));
+    }
+
+    /// Limits the following draws to pixels without `CLIP_BIT`, or lifts the limit
+    fn set_clip_test(ctx: &mut MiniContext, clipping: bool) {
+        if !clipping {
+            ctx.set_stencil(None);
+            return;
+        }
+        let face = StencilFaceState {
+            fail_op: StencilOp::Keep,
+            depth_fail_op: StencilOp::Keep,
+            pass_op: StencilOp::Keep,
+            test_func: CompareFunc::Equal,
+            test_ref: 0,
+            test_mask: CLIP_BIT,
+            write_mask: 0,
+        };
+        ctx.set_stencil(Some(StencilState {
+            front: face,
+            back: face,
+        }));
+    }
+
     // from https://www.khronos.org/opengl/wiki/Primitive:
     // GL_TRIANGLE_FAN:
     // Indices:     0 1 2 3 4 5 ... (6 total indices)
@@ -368,12 +652,12 @@ impl Renderer {
     //                   {3 4 5}
     /// Adds indices to convert from GL_TRIANGLE_FAN to GL_TRIANGLES
     #[inline]
-    fn add_triangle_fan(indices: &mut Vec<u16>, first_vertex_index: u16, index_count: u16) {
+    fn add_triangle_fan(indices: &mut Vec<u32>, first_vertex_index: usize, index_count: usize) {
         let start_index = first_vertex_index;
         for i in first_vertex_index..first_vertex_index + index_count - 2 {
-            indices.push(start_index);
-            indices.push(i + 1);
-            indices.push(i + 2);
+            indices.push(start_index as u32);
+            indices.push((i + 1) as u32);
+            indices.push((i + 2) as u32);
         }
     }
 
@@ -384,12 +668,11 @@ impl Renderer {
     //                   {3 4 5}    (2 total indices)
     /// Adds indices to draw GL_TRIANGLES
     #[inline]
-    fn add_triangles(indices: &mut Vec<u16>, first_vertex_index: u16, index_count: u16) {
-        // TODO: test!
+    fn add_triangles(indices: &mut Vec<u32>, first_vertex_index: usize, index_count: usize) {
         for i in (first_vertex_index..first_vertex_index + index_count).step_by(3) {
-            indices.push(i);
-            indices.push(i + 1);
-            indices.push(i + 2);
+            indices.push(i as u32);
+            indices.push((i + 1) as u32);
+            indices.push((i + 2) as u32);
         }
     }
 
@@ -406,19 +689,37 @@ impl Renderer {
     // Triangles:  {0 1 2}
     //                   {3 4 5}
     /// Adds indices to convert from GL_TRIANGLE_STRIP to GL_TRIANGLES
+    ///
+    /// Triangle `n` of the strip is `(n, n+1, n+2)` when `n` is even and `(n+1, n, n+2)` when
+    /// odd, counted from the start of each strip. This is the order GL uses, so every triangle
+    /// keeps the winding of the first one and face culling treats them alike.
     #[inline]
-    fn add_triangle_strip(indices: &mut Vec<u16>, first_vertex_index: u16, index_count: u16) {
+    fn add_triangle_strip(indices: &mut Vec<u32>, first_vertex_index: usize, index_count: usize) {
         let mut draw_order_winding = true; // true to draw in straight (0 1 2) order; false to draw in (1 0 2) order to maintain proper winding
         for i in first_vertex_index..first_vertex_index + index_count - 2 {
             if draw_order_winding {
-                indices.push(i);
-                indices.push(i + 1);
+                indices.push(i as u32);
+                indices.push((i + 1) as u32);
             } else {
-                indices.push(i + 1);
-                indices.push(i);
+                indices.push((i + 1) as u32);
+                indices.push(i as u32);
             }
             draw_order_winding = !draw_order_winding;
-            indices.push(i + 2);
+            indices.push((i + 2) as u32);
+        }
+    }
+
+    /// Adds the fill fan of `path`, if it has one
+    fn add_path_fill(indices: &mut Vec<u32>, path: &GLPath) {
+        if path.fill_count > 0 {
+            Self::add_triangle_fan(indices, path.fill_offset, path.fill_count);
+        }
+    }
+
+    /// Adds the fringe or stroke strip of `path`, if it has one
+    fn add_path_stroke(indices: &mut Vec<u32>, path: &GLPath) {
+        if path.stroke_count > 0 {
+            Self::add_triangle_strip(indices, path.stroke_offset, path.stroke_count);
         }
     }
 
@@ -426,42 +727,31 @@ impl Renderer {
         ctx: &mut MiniContext,
         call: &Call,
         paths: &[GLPath],
-        bindings: &Bindings,
-        indices: &mut Vec<u16>,
+        bindings: &mut ChunkBindings,
+        indices: &mut Vec<u32>,
         uniforms: &shader::Uniforms,
     ) {
         indices.clear();
         Self::set_uniforms(ctx, uniforms, call.image);
 
-        // convert all fans and strips into single draw call
+        // convert all fans and strips of a chunk into single draw call
         // more info: https://gamedev.stackexchange.com/questions/133208/difference-in-gldrawarrays-and-gldrawelements
-        for path in paths {
+        bindings.draw_paths(ctx, indices, paths, |indices, path| {
             // draw TRIANGLE_FAN from path.fill_offset with path.fill_count, same as
             // glDrawArrays(GL_TRIANGLE_FAN, path.fill_offset, path.fill_count); // note: count is "number of indices to render"
-            Self::add_triangle_fan(indices, path.fill_offset as u16, path.fill_count as u16);
-
-            if path.stroke_count > 0 {
-                // draw TRIANGLE_STRIP from path.stroke_offset with path.stroke_count, same as
-                // glDrawArrays(GL_TRIANGLE_STRIP,path.stroke_offset, path.stroke_count);
-                Self::add_triangle_strip(
-                    indices,
-                    path.stroke_offset as u16,
-                    path.stroke_count as u16,
-                );
-            }
-        }
-
-        bindings.index_buffer.update(ctx, &indices);
-        ctx.apply_bindings(bindings);
-        ctx.draw(0, indices.len() as i32, 1);
+            Self::add_path_fill(indices, path);
+            // draw TRIANGLE_STRIP from path.stroke_offset with path.stroke_count, same as
+            // glDrawArrays(GL_TRIANGLE_STRIP,path.stroke_offset, path.stroke_count);
+            Self::add_path_stroke(indices, path);
+        });
     }
 
     fn do_stroke(
         ctx: &mut MiniContext,
         call: &Call,
         paths: &[GLPath],
-        bindings: &Bindings,
-        indices: &mut Vec<u16>,
+        bindings: &mut ChunkBindings,
+        indices: &mut Vec<u32>,
         uniforms: &shader::Uniforms,
         uniforms_next: &shader::Uniforms,
     ) {
@@ -475,19 +765,14 @@ impl Renderer {
 
         // self.set_uniforms(call.uniform_offset + 1, call.image);
         Self::set_uniforms(ctx, uniforms_next, call.image);
-        for path in paths {
-            // glDrawArrays(GL_TRIANGLE_STRIP, path.stroke_offset as i32, path.stroke_count as i32);
-            Self::add_triangle_strip(indices, path.stroke_offset as u16, path.stroke_count as u16);
-        }
-        bindings.index_buffer.update(ctx, &indices);
-        ctx.apply_bindings(bindings);
-        ctx.draw(0, indices.len() as i32, 1);
+        // glDrawArrays(GL_TRIANGLE_STRIP, path.stroke_offset as i32, path.stroke_count as i32);
+        bindings.draw_paths(ctx, indices, paths, Self::add_path_stroke);
 
         // self.set_uniforms(call.uniform_offset, call.image);
         Self::set_uniforms(ctx, uniforms, call.image);
         // TODO glStencilFunc(GL_EQUAL, 0x0, 0xff);
         // TODO glStencilOp(GL_KEEP, GL_KEEP, GL_KEEP);
-        ctx.draw(0, indices.len() as i32, 1);
+        bindings.draw_paths(ctx, indices, paths, Self::add_path_stroke);
 
         // TODO glColorMask(GL_FALSE, GL_FALSE, GL_FALSE, GL_FALSE);
         // TODO glStencilFunc(GL_ALWAYS, 0x0, 0xff);
@@ -501,8 +786,8 @@ impl Renderer {
     fn do_triangles(
         ctx: &mut MiniContext,
         call: &Call,
-        bindings: &Bindings,
-        indices: &mut Vec<u16>,
+        bindings: &mut ChunkBindings,
+        indices: &mut Vec<u32>,
         uniforms: &shader::Uniforms,
     ) {
         indices.clear();
@@ -510,15 +795,8 @@ impl Renderer {
 
         // draw TRIANGLES from call.triangle_offset with call.triangle_count, same as
         // glDrawArrays(GL_TRIANGLES, call.triangle_offset as i32, call.triangle_count as i32); // note: triangle_count is "number of indices to render", not number of triangles
-        Self::add_triangles(
-            indices,
-            call.triangle_offset as u16,
-            call.triangle_count as u16,
-        );
-
-        bindings.index_buffer.update(ctx, &indices);
-        ctx.apply_bindings(bindings);
-        ctx.draw(0, indices.len() as i32, 1);
+        Self::add_triangles(indices, call.triangle_offset, call.triangle_count);
+        bindings.draw(ctx, indices, call.vertex_base);
     }
 
     fn convert_paint(
@@ -528,7 +806,7 @@ impl Renderer {
         width: f32,
         fringe: f32,
         stroke_thr: f32,
-    ) -> shader::Uniforms {
+    ) -> Result<shader::Uniforms, NonaError> {
         let mut frag = shader::Uniforms {
             view_size: Default::default(),
             scissor_mat: Mat4::ZERO,
@@ -579,17 +857,7 @@ impl Renderer {
                 };
 
                 frag.type_ = ShaderType::FillImage as i32;
-                match texture.tex.format {
-                    TextureFormat::RGBA8 => {
-                        frag.tex_type = if texture.flags.contains(ImageFlags::PREMULTIPLIED) {
-                            0
-                        } else {
-                            1
-                        }
-                    }
-                    TextureFormat::Alpha => frag.tex_type = 2,
-                    _ => todo!("Unsupported texture type"),
-                }
+                frag.tex_type = paint_tex_type(texture.tex.format, texture.flags)?;
             }
         } else {
             frag.type_ = ShaderType::FillGradient as i32;
@@ -600,12 +868,74 @@ impl Renderer {
 
         frag.paint_mat = xform_to_4x4(invxform);
 
-        frag
+        Ok(frag)
     }
 
     fn append_uniforms(&mut self, uniforms: shader::Uniforms) {
         self.uniforms.push(uniforms);
     }
+
+    /// Fast reject for pixels outside the scissor, the shader still does the feathered edge
+    fn apply_scissor(ctx: &mut MiniContext, bounds: Option<Bounds>) {
+        let (x, y, width, height) = scissor_rect(bounds, ctx.screen_size());
+        ctx.apply_scissor_rect(x, y, width, height);
+    }
+
+    /// Returns the start of the vertex buffer chunk that can take `count` more vertexes,
+    /// beginning a new chunk when the current one is full
+    fn reserve_chunk(&mut self, count: usize) -> Result<usize, NonaError> {
+        if count > self.max_vertices {
+            return Err(NonaError::Geometry(format!(
+                "{} vertexes in a single draw, the limit is {}",
+                count, self.max_vertices
+            )));
+        }
+        if self.vertexes.len() - self.chunk_start + count > self.max_vertices {
+            self.chunk_start = self.vertexes.len();
+        }
+        Ok(self.chunk_start)
+    }
+
+    /// Adds a fill fan as one path, or as several when it is too large for a chunk
+    fn append_fill(&mut self, fill: &[Vertex]) -> Result<(), NonaError> {
+        if fill.is_empty() {
+            return Ok(());
+        }
+        for range in fan_pieces(fill.len(), self.max_vertices) {
+            let count = range.len() + 1;
+            let vertex_base = self.reserve_chunk(count)?;
+            self.paths.push(GLPath {
+                vertex_base,
+                fill_offset: self.vertexes.len() - vertex_base,
+                fill_count: count,
+                stroke_offset: 0,
+                stroke_count: 0,
+            });
+            self.vertexes.push(fill[0]);
+            self.vertexes.extend(&fill[range]);
+        }
+        Ok(())
+    }
+
+    /// Adds a fringe or stroke strip as one path, or as several when it is too large for a
+    /// chunk
+    fn append_stroke(&mut self, stroke: &[Vertex]) -> Result<(), NonaError> {
+        if stroke.is_empty() {
+            return Ok(());
+        }
+        for range in strip_pieces(stroke.len(), self.max_vertices) {
+            let vertex_base = self.reserve_chunk(range.len())?;
+            self.paths.push(GLPath {
+                vertex_base,
+                fill_offset: 0,
+                fill_count: 0,
+                stroke_offset: self.vertexes.len() - vertex_base,
+                stroke_count: range.len(),
+            });
+            self.vertexes.extend(&stroke[range]);
+        }
+        Ok(())
+    }
 }
 
 trait IntoTuple4<T> {
@@ -623,6 +953,14 @@ impl renderer::Renderer for RendererCtx<'_> {
         self.renderer.edge_antialias()
     }
 
+    fn caps(&self) -> RendererCaps {
+        RendererCaps {
+            supports_stencil: true,
+            max_texture_size: self.renderer.max_texture_size,
+            supports_u32_indices: self.renderer.u32_indices,
+        }
+    }
+
     fn view_size(&self) -> (f32, f32) {
         self.renderer.view_size(self.ctx)
     }
@@ -631,6 +969,10 @@ impl renderer::Renderer for RendererCtx<'_> {
         self.renderer.device_pixel_ratio(self.ctx)
     }
 
+    fn max_texture_size(&self) -> usize {
+        self.renderer.max_texture_size
+    }
+
     fn create_texture(
         &mut self,
         texture_type: TextureType,
@@ -664,6 +1006,20 @@ impl renderer::Renderer for RendererCtx<'_> {
         self.renderer.texture_size(img)
     }
 
+    fn set_texture_repeat(
+        &mut self,
+        img: ImageId,
+        repeat_x: bool,
+        repeat_y: bool,
+    ) -> Result<(), NonaError> {
+        self.renderer
+            .set_texture_repeat(self.ctx, img, repeat_x, repeat_y)
+    }
+
+    fn set_texture_nearest(&mut self, img: ImageId, nearest: bool) -> Result<(), NonaError> {
+        self.renderer.set_texture_nearest(self.ctx, img, nearest)
+    }
+
     fn viewport(&mut self, extent: Extent, device_pixel_ratio: f32) -> Result<(), NonaError> {
         self.renderer.viewport(extent, device_pixel_ratio)
     }
@@ -676,24 +1032,26 @@ impl renderer::Renderer for RendererCtx<'_> {
         self.renderer.flush(self.ctx)
     }
 
+    fn read_pixels(
+        &mut self,
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+    ) -> Result<Vec<u8>, NonaError> {
+        self.renderer.read_pixels(self.ctx, x, y, width, height)
+    }
+
     fn fill(
         &mut self,
         paint: &Paint,
         composite_operation: CompositeOperationState,
         scissor: &Scissor,
-        fringe: f32,
-        bounds: Bounds,
+        params: &FillParams,
         paths: &[Path],
     ) -> Result<(), NonaError> {
-        self.renderer.fill(
-            self.ctx,
-            paint,
-            composite_operation,
-            scissor,
-            fringe,
-            bounds,
-            paths,
-        )
+        self.renderer
+            .fill(self.ctx, paint, composite_operation, scissor, params, paths)
     }
 
     fn stroke(
@@ -701,19 +1059,11 @@ impl renderer::Renderer for RendererCtx<'_> {
         paint: &Paint,
         composite_operation: CompositeOperationState,
         scissor: &Scissor,
-        fringe: f32,
-        stroke_width: f32,
+        params: &StrokeParams,
         paths: &[Path],
     ) -> Result<(), NonaError> {
-        self.renderer.stroke(
-            self.ctx,
-            paint,
-            composite_operation,
-            scissor,
-            fringe,
-            stroke_width,
-            paths,
-        )
+        self.renderer
+            .stroke(self.ctx, paint, composite_operation, scissor, params, paths)
     }
 
     fn triangles(
@@ -726,6 +1076,14 @@ impl renderer::Renderer for RendererCtx<'_> {
         self.renderer
             .triangles(self.ctx, paint, composite_operation, scissor, vertexes)
     }
+
+    fn clip(&mut self, fill_rule: FillRule, paths: &[Path]) -> Result<(), NonaError> {
+        self.renderer.clip(self.ctx, fill_rule, paths)
+    }
+
+    fn reset_clip(&mut self) -> Result<(), NonaError> {
+        self.renderer.reset_clip(self.ctx)
+    }
 }
 
 impl Renderer {
@@ -760,17 +1118,19 @@ impl Renderer {
             data,
             TextureParams {
                 format,
-                wrap: TextureWrap::Clamp, // TODO: support repeatx/y/mirror
-                filter: if flags.contains(ImageFlags::NEAREST) {
-                    FilterMode::Nearest
-                } else {
-                    FilterMode::Linear
-                },
+                wrap: TextureWrap::Clamp, // TODO: support mirror
+                filter: texture_filter(flags.contains(ImageFlags::NEAREST)),
                 width: width as u32,
                 height: height as u32,
             },
         );
 
+        tex.set_wrap_xy(
+            ctx,
+            texture_wrap(flags.contains(ImageFlags::REPEATX)),
+            texture_wrap(flags.contains(ImageFlags::REPEATY)),
+        );
+
         // TODO: support ImageFlags::GENERATE_MIPMAPS) with/without if flags.contains(ImageFlags::NEAREST) {
 
         let id = self.textures.insert(Texture { tex, flags });
@@ -815,34 +1175,126 @@ impl Renderer {
         }
     }
 
+    fn set_texture_repeat(
+        &mut self,
+        ctx: &mut MiniContext,
+        img: ImageId,
+        repeat_x: bool,
+        repeat_y: bool,
+    ) -> Result<(), NonaError> {
+        if let Some(texture) = self.textures.get_mut(img) {
+            texture
+                .tex
+                .set_wrap_xy(ctx, texture_wrap(repeat_x), texture_wrap(repeat_y));
+            texture.flags.set(ImageFlags::REPEATX, repeat_x);
+            texture.flags.set(ImageFlags::REPEATY, repeat_y);
+            Ok(())
+        } else {
+            Err(NonaError::Texture(format!("texture '{}' not found", img)))
+        }
+    }
+
+    fn set_texture_nearest(
+        &mut self,
+        ctx: &mut MiniContext,
+        img: ImageId,
+        nearest: bool,
+    ) -> Result<(), NonaError> {
+        if let Some(texture) = self.textures.get_mut(img) {
+            texture.tex.set_filter(ctx, texture_filter(nearest));
+            texture.flags.set(ImageFlags::NEAREST, nearest);
+            Ok(())
+        } else {
+            Err(NonaError::Texture(format!("texture '{}' not found", img)))
+        }
+    }
+
     fn viewport(&mut self, extent: Extent, _device_pixel_ratio: f32) -> Result<(), NonaError> {
         self.view = extent;
         Ok(())
     }
 
-    fn clear_screen(&mut self, ctx: &mut MiniContext, color: Color) {
-        ctx.clear(Some((color.r, color.g, color.b, color.a)), None, None);
+    fn clear_screen(&mut self, _ctx: &mut MiniContext, color: Color) {
+        // the clear is applied when the pass begins in flush(), so it can't be overridden by the pass action
+        self.clear_color = Some(color);
+    }
+
+    /// Returns the pass action that applies the pending `clear_screen` color, if any
+    fn take_pass_action(&mut self) -> PassAction {
+        pass_action(self.clear_color.take())
+    }
+
+    fn read_pixels(
+        &mut self,
+        ctx: &mut MiniContext,
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+    ) -> Result<Vec<u8>, NonaError> {
+        let (screen_width, screen_height) = ctx.screen_size();
+        check_read_region(
+            x,
+            y,
+            width,
+            height,
+            (screen_width as usize, screen_height as usize),
+        )?;
+        let mut pixels = vec![0u8; width * height * 4];
+        if pixels.is_empty() {
+            return Ok(pixels);
+        }
+
+        // GL reads rows bottom to top, starting from the bottom left corner
+        let framebuffer_height = screen_height as i32;
+        unsafe {
+            miniquad::gl::glReadPixels(
+                x as i32,
+                framebuffer_height - (y + height) as i32,
+                width as i32,
+                height as i32,
+                miniquad::gl::GL_RGBA,
+                miniquad::gl::GL_UNSIGNED_BYTE,
+                pixels.as_mut_ptr() as *mut _,
+            );
+        }
+
+        let stride = width * 4;
+        for row in 0..height / 2 {
+            let (top, bottom) = pixels.split_at_mut((height - row - 1) * stride);
+            top[row * stride..(row + 1) * stride].swap_with_slice(&mut bottom[..stride]);
+        }
+        Ok(pixels)
     }
 
     fn flush(&mut self, ctx: &mut MiniContext) -> Result<(), NonaError> {
+        // clips last until the end of the frame
+        if self.clipping {
+            self.reset_clip(ctx)?;
+        }
+
         if self.calls.is_empty() {
+            if self.clear_color.is_some() {
+                // nothing to draw, but the requested clear must still happen
+                ctx.begin_default_pass(self.take_pass_action());
+                ctx.end_render_pass();
+            }
+
             self.vertexes.clear();
             self.paths.clear();
             self.calls.clear();
             self.uniforms.clear();
+            self.chunk_start = 0;
 
             return Ok(());
         }
-        ctx.begin_default_pass(PassAction::Nothing);
+        ctx.begin_default_pass(self.take_pass_action());
 
         // glUseProgram(self.shader.prog); DONE
         ctx.apply_pipeline(&self.pipeline);
-        ctx.apply_bindings(&self.bindings); // NEEDED - must be called before vertex buffer update; TODO_BUG: can be optimized in miniquad; we only need to update index buffer in most cases, see do_convex_fill()
-        self.bindings.vertex_buffers[0].update(ctx, &self.vertexes); // TODO: miniquad BUG? this line must show after apply_bindings otherwise no display of vertex buffer can happen
 
-        // glEnable(GL_CULL_FACE);
-        // glCullFace(GL_BACK);
-        ctx.set_cull_face(CullFace::Back);
+        // glEnable(GL_CULL_FACE); // DONE cull_face, applied with the pipeline
+        // glCullFace(GL_BACK); // DONE cull_face
         // glFrontFace(GL_CCW); // DONE front_face_order
 
         // glEnable(GL_BLEND); // TODO_BELOW
@@ -894,11 +1346,29 @@ impl Renderer {
         let calls = &self.calls[..];
         // println!("START CALLS"); // DEBUG
 
+        let mut bindings = ChunkBindings {
+            bindings: &mut self.bindings,
+            vertexes: &self.vertexes,
+            uploaded: &mut self.uploaded_vertexes,
+            bound_base: None,
+            max_vertices: self.max_vertices,
+            short_indices: if self.u32_indices {
+                None
+            } else {
+                Some(&mut self.short_indices)
+            },
+        };
+        let mut clipping = false;
+
         for call in calls {
             let call: &Call = call; // added to make rust-analyzer type inferrence work. See https://github.com/rust-analyzer/rust-analyzer/issues/4160
             let blend = &call.blend_func;
 
             ctx.set_blend(Some(blend.color), Some(blend.alpha));
+            unsafe {
+                gl::glBlendEquationSeparate(blend.equation, blend.equation);
+            }
+            Self::apply_scissor(ctx, call.scissor_bounds);
 
             // {
             //     // TODO: set image in a better way!!!
@@ -922,10 +1392,14 @@ impl Renderer {
             }
             let uniforms: &shader::Uniforms = &self.uniforms[call.uniform_offset];
             if let Some(image_index) = call.image {
-                self.bindings.images[0] = self.textures[image_index].tex;
+                bindings.bindings.images[0] = self.textures[image_index].tex;
                 // ctx.apply_bindings(&self.bindings); // not needed - will be called in the call_type handlers below
             }
 
+            // fills and clips set up the stencil themselves
+            Self::set_clip_test(ctx, clipping);
+            ctx.set_cull_face(call_cull_face(&call.call_type));
+
             match call.call_type {
                 CallType::Fill => {
                     // TODO: test!
@@ -937,7 +1411,7 @@ impl Renderer {
                         ctx,
                         call,
                         paths,
-                        &self.bindings,
+                        &mut bindings,
                         &mut self.indices,
                         &uniforms,
                         &uniforms_next,
@@ -971,7 +1445,7 @@ impl Renderer {
                         ctx,
                         call,
                         paths,
-                        &self.bindings,
+                        &mut bindings,
                         &mut self.indices,
                         uniforms,
                     );
@@ -984,18 +1458,28 @@ impl Renderer {
                         ctx,
                         call,
                         paths,
-                        &self.bindings,
+                        &mut bindings,
                         &mut self.indices,
                         &uniforms,
                         &uniforms_next,
                     );
                 }
                 CallType::Triangles => {
-                    Self::do_triangles(ctx, call, &self.bindings, &mut self.indices, uniforms);
+                    Self::do_triangles(ctx, call, &mut bindings, &mut self.indices, uniforms);
+                }
+                CallType::Clip => {
+                    let paths = &self.paths[call.path_offset..call.path_offset + call.path_count];
+                    Self::do_clip(ctx, call, paths, &mut bindings, &mut self.indices, uniforms);
+                    clipping = true;
+                }
+                CallType::ResetClip => {
+                    Self::do_reset_clip(ctx, call, &mut bindings, &mut self.indices, uniforms);
+                    clipping = false;
                 }
             }
         }
 
+        ctx.set_stencil(None);
         ctx.end_render_pass();
 
         // TODO: commented, not needed??
@@ -1005,6 +1489,11 @@ impl Renderer {
 
         // glDisable(GL_CULL_FACE);
         ctx.set_cull_face(CullFace::Nothing);
+        Self::apply_scissor(ctx, None);
+        // miniquad's blend state cache assumes the Add equation it sets
+        unsafe {
+            gl::glBlendEquationSeparate(gl::GL_FUNC_ADD, gl::GL_FUNC_ADD);
+        }
 
         // glBindBuffer(GL_ARRAY_BUFFER, 0);
         // glUseProgram(0);
@@ -1014,20 +1503,26 @@ impl Renderer {
         self.paths.clear();
         self.calls.clear();
         self.uniforms.clear();
+        self.chunk_start = 0;
         Ok(())
     }
 
     fn fill(
         &mut self,
-        ctx: &mut MiniContext,
+        _ctx: &mut MiniContext,
         paint: &Paint,
         composite_operation: CompositeOperationState,
         scissor: &Scissor,
-        fringe: f32,
-        bounds: Bounds,
+        params: &FillParams,
         paths: &[Path],
     ) -> Result<(), NonaError> {
-        let mut new_vertex_count = self.vertexes.len();
+        let FillParams {
+            fringe,
+            bounds,
+            fill_rule,
+            ..
+        } = *params;
+        let mut new_vertex_count = 0;
         for path in paths {
             new_vertex_count += path.get_fill().len();
             new_vertex_count += path.get_stroke().len();
@@ -1043,52 +1538,33 @@ impl Renderer {
             new_vertex_count += 4;
         }
 
-        // if GPU overflow
-        if new_vertex_count >= MAX_VERTICES {
-            self.flush(ctx)?;
-        }
+        let uniforms = self.convert_paint(paint, scissor, fringe, fringe, -1.0)?;
+        // keeps small fills in one chunk, larger ones continue in the next
+        self.reserve_chunk(new_vertex_count.min(self.max_vertices))?;
 
         let mut call = Call {
             call_type,
             image: paint.image,
             path_offset: self.paths.len(),
-            path_count: paths.len(),
+            path_count: 0,
             triangle_offset: 0,
             triangle_count: 4,
             uniform_offset: 0,
             blend_func: composite_operation.into(),
+            fill_rule,
+            vertex_base: self.chunk_start,
+            scissor_bounds: scissor_bounds(scissor),
         };
 
-        let mut offset = self.vertexes.len();
         for path in paths {
-            let fill = path.get_fill();
-            let mut gl_path = GLPath {
-                fill_offset: 0,
-                fill_count: 0,
-                stroke_offset: 0,
-                stroke_count: 0,
-            };
-
-            if !fill.is_empty() {
-                gl_path.fill_offset = offset;
-                gl_path.fill_count = fill.len();
-                self.vertexes.extend(fill);
-                offset += fill.len();
-            }
-
-            let stroke = path.get_stroke();
-            if !stroke.is_empty() {
-                gl_path.stroke_offset = offset;
-                gl_path.stroke_count = stroke.len();
-                self.vertexes.extend(stroke);
-                offset += stroke.len();
-            }
-
-            self.paths.push(gl_path);
+            self.append_fill(path.get_fill())?;
+            self.append_stroke(path.get_stroke())?;
         }
+        call.path_count = self.paths.len() - call.path_offset;
 
         if call.call_type == CallType::Fill {
-            call.triangle_offset = offset;
+            call.vertex_base = self.reserve_chunk(4)?;
+            call.triangle_offset = self.vertexes.len() - call.vertex_base;
             self.vertexes
                 .push(Vertex::new(bounds.max.x, bounds.max.y, 0.5, 1.0));
             self.vertexes
@@ -1104,10 +1580,10 @@ impl Renderer {
                 type_: ShaderType::Simple as i32,
                 ..shader::Uniforms::default()
             });
-            self.append_uniforms(self.convert_paint(paint, scissor, fringe, fringe, -1.0));
+            self.append_uniforms(uniforms);
         } else {
             call.uniform_offset = self.uniforms.len();
-            self.append_uniforms(self.convert_paint(paint, scissor, fringe, fringe, -1.0));
+            self.append_uniforms(uniforms);
         }
 
         self.calls.push(call);
@@ -1116,63 +1592,57 @@ impl Renderer {
 
     fn stroke(
         &mut self,
-        ctx: &mut MiniContext,
+        _ctx: &mut MiniContext,
         paint: &Paint,
         composite_operation: CompositeOperationState,
         scissor: &Scissor,
-        fringe: f32,
-        stroke_width: f32,
+        params: &StrokeParams,
         paths: &[Path],
     ) -> Result<(), NonaError> {
-        let mut new_vertex_count = self.vertexes.len();
+        let StrokeParams {
+            fringe,
+            stroke_width,
+            gradient_along_path,
+            ..
+        } = *params;
+        let mut new_vertex_count = 0;
         for path in paths {
             new_vertex_count += path.get_stroke().len();
         }
 
-        // if GPU overflow
-        if new_vertex_count >= MAX_VERTICES {
-            self.flush(ctx)?;
+        let mut uniforms = self.convert_paint(paint, scissor, stroke_width, fringe, -1.0)?;
+        let mut uniforms_aa =
+            self.convert_paint(paint, scissor, stroke_width, fringe, 1.0 - 0.5 / 255.0)?;
+        if gradient_along_path && paint.image.is_none() {
+            uniforms.type_ = ShaderType::StrokeGradientAlongPath as i32;
+            uniforms_aa.type_ = ShaderType::StrokeGradientAlongPath as i32;
         }
 
+        // keeps small strokes in one chunk, larger ones continue in the next
+        let vertex_base = self.reserve_chunk(new_vertex_count.min(self.max_vertices))?;
+
         let mut call = Call {
             call_type: CallType::Stroke,
             image: paint.image,
             path_offset: self.paths.len(),
-            path_count: paths.len(),
+            path_count: 0,
             triangle_offset: 0,
             triangle_count: 0,
             uniform_offset: 0,
             blend_func: composite_operation.into(),
+            fill_rule: FillRule::NonZero,
+            vertex_base,
+            scissor_bounds: scissor_bounds(scissor),
         };
 
-        let mut offset = self.vertexes.len();
         for path in paths {
-            let mut gl_path = GLPath {
-                fill_offset: 0,
-                fill_count: 0,
-                stroke_offset: 0,
-                stroke_count: 0,
-            };
-
-            let stroke = path.get_stroke();
-            if !stroke.is_empty() {
-                gl_path.stroke_offset = offset;
-                gl_path.stroke_count = stroke.len();
-                self.vertexes.extend(stroke);
-                offset += stroke.len();
-                self.paths.push(gl_path);
-            }
+            self.append_stroke(path.get_stroke())?;
         }
+        call.path_count = self.paths.len() - call.path_offset;
 
         call.uniform_offset = self.uniforms.len();
-        self.append_uniforms(self.convert_paint(paint, scissor, stroke_width, fringe, -1.0));
-        self.append_uniforms(self.convert_paint(
-            paint,
-            scissor,
-            stroke_width,
-            fringe,
-            1.0 - 0.5 / 255.0,
-        ));
+        self.append_uniforms(uniforms);
+        self.append_uniforms(uniforms_aa);
 
         self.calls.push(call);
         Ok(())
@@ -1180,39 +1650,137 @@ impl Renderer {
 
     fn triangles(
         &mut self,
-        ctx: &mut MiniContext,
+        _ctx: &mut MiniContext,
         paint: &Paint,
         composite_operation: CompositeOperationState,
         scissor: &Scissor,
         vertexes: &[Vertex],
     ) -> Result<(), NonaError> {
-        let mut new_vertex_count = self.vertexes.len();
-        new_vertex_count += vertexes.len();
-
-        // if GPU overflow
-        if new_vertex_count >= MAX_VERTICES {
-            self.flush(ctx)?;
+        // triangles are independent, so large batches are split across chunks
+        let max_vertices = self.max_vertices - self.max_vertices % 3;
+        for part in vertexes.chunks(max_vertices) {
+            self.append_triangles(paint, composite_operation, scissor, part)?;
         }
+        Ok(())
+    }
+
+    fn append_triangles(
+        &mut self,
+        paint: &Paint,
+        composite_operation: CompositeOperationState,
+        scissor: &Scissor,
+        vertexes: &[Vertex],
+    ) -> Result<(), NonaError> {
+        let vertex_base = self.reserve_chunk(vertexes.len())?;
 
         let call = Call {
             call_type: CallType::Triangles,
             image: paint.image,
             path_offset: 0,
             path_count: 0,
-            triangle_offset: self.vertexes.len(),
+            triangle_offset: self.vertexes.len() - vertex_base,
             triangle_count: vertexes.len(),
             uniform_offset: self.uniforms.len(),
             blend_func: composite_operation.into(),
+            fill_rule: FillRule::NonZero,
+            vertex_base,
+            scissor_bounds: scissor_bounds(scissor),
         };
 
+        let mut uniforms = self.convert_paint(paint, scissor, 1.0, 1.0, -1.0)?;
+        uniforms.type_ = ShaderType::Image as i32;
+        if paint.image.is_none() {
+            // plain paint color, whatever texture is bound
+            uniforms.tex_type = 3;
+        }
+
+        // consecutive draws with identical state, e.g. several text runs, share one call
+        if let Some(last) = self.calls.last_mut() {
+            if last.continued_by(&call) && self.uniforms[last.uniform_offset] == uniforms {
+                last.triangle_count += call.triangle_count;
+                self.vertexes.extend(vertexes);
+                return Ok(());
+            }
+        }
+
         self.calls.push(call);
         self.vertexes.extend(vertexes);
-
-        let mut uniforms = self.convert_paint(paint, scissor, 1.0, 1.0, -1.0);
-        uniforms.type_ = ShaderType::Image as i32;
         self.append_uniforms(uniforms);
         Ok(())
     }
+
+    fn clip(
+        &mut self,
+        _ctx: &mut MiniContext,
+        fill_rule: FillRule,
+        paths: &[Path],
+    ) -> Result<(), NonaError> {
+        let mut new_vertex_count = 4;
+        for path in paths {
+            new_vertex_count += path.get_fill().len();
+        }
+        self.reserve_chunk(new_vertex_count.min(self.max_vertices))?;
+
+        let path_offset = self.paths.len();
+        for path in paths {
+            self.append_fill(path.get_fill())?;
+        }
+
+        let vertex_base = self.reserve_chunk(4)?;
+        let mut call = self.clip_call(CallType::Clip, vertex_base);
+        call.path_offset = path_offset;
+        call.path_count = self.paths.len() - path_offset;
+        call.fill_rule = fill_rule;
+        call.triangle_offset = self.vertexes.len() - vertex_base;
+        self.push_view_quad();
+        self.calls.push(call);
+        self.clipping = true;
+        Ok(())
+    }
+
+    fn reset_clip(&mut self, _ctx: &mut MiniContext) -> Result<(), NonaError> {
+        let vertex_base = self.reserve_chunk(4)?;
+        let mut call = self.clip_call(CallType::ResetClip, vertex_base);
+        call.triangle_offset = self.vertexes.len() - vertex_base;
+        self.push_view_quad();
+        self.calls.push(call);
+        self.clipping = false;
+        Ok(())
+    }
+
+    /// Call that only writes the stencil, unscissored since the clip covers the whole view
+    fn clip_call(&mut self, call_type: CallType, vertex_base: usize) -> Call {
+        let composite_operation: CompositeOperationState =
+            CompositeOperation::Basic(BasicCompositeOperation::SrcOver).into();
+        let call = Call {
+            call_type,
+            image: None,
+            path_offset: 0,
+            path_count: 0,
+            triangle_offset: 0,
+            triangle_count: 4,
+            uniform_offset: self.uniforms.len(),
+            blend_func: composite_operation.into(),
+            fill_rule: FillRule::NonZero,
+            vertex_base,
+            scissor_bounds: None,
+        };
+        self.append_uniforms(shader::Uniforms {
+            stroke_thr: -1.0,
+            type_: ShaderType::Simple as i32,
+            ..shader::Uniforms::default()
+        });
+        call
+    }
+
+    /// Triangle strip covering the whole view
+    fn push_view_quad(&mut self) {
+        let Extent { width, height } = self.view;
+        self.vertexes.push(Vertex::new(width, height, 0.5, 1.0));
+        self.vertexes.push(Vertex::new(width, 0.0, 0.5, 1.0));
+        self.vertexes.push(Vertex::new(0.0, height, 0.5, 1.0));
+        self.vertexes.push(Vertex::new(0.0, 0.0, 0.5, 1.0));
+    }
 }
 
 fn convert_blend_factor(factor: nona::BlendFactor) -> miniquad::BlendFactor {
@@ -1242,6 +1810,138 @@ fn convert_blend_factor(factor: nona::BlendFactor) -> miniquad::BlendFactor {
     }
 }
 
+/// Returns the axis aligned envelope of the scissor rectangle, or `None` when there is no scissor
+fn scissor_bounds(scissor: &Scissor) -> Option<Bounds> {
+    if scissor.extent.width < -0.5 || scissor.extent.height < -0.5 {
+        return None;
+    }
+
+    let (ex, ey) = (scissor.extent.width, scissor.extent.height);
+    let mut bounds = Bounds {
+        min: Point::new(f32::MAX, f32::MAX),
+        max: Point::new(f32::MIN, f32::MIN),
+    };
+    for (x, y) in &[(-ex, -ey), (ex, -ey), (ex, ey), (-ex, ey)] {
+        let pt = scissor.xform.transform_point(Point::new(*x, *y));
+        bounds.min.x = bounds.min.x.min(pt.x);
+        bounds.min.y = bounds.min.y.min(pt.y);
+        bounds.max.x = bounds.max.x.max(pt.x);
+        bounds.max.y = bounds.max.y.max(pt.y);
+    }
+    Some(bounds)
+}
+
+/// Splits a triangle fan of `count` vertexes into fans of at most `max` vertexes. Each piece
+/// is the first vertex of the fan followed by the returned range. Consecutive pieces share an
+/// edge, so together they draw the same triangles.
+fn fan_pieces(count: usize, max: usize) -> impl Iterator<Item = Range<usize>> {
+    pieces(1, count, max - 1, 1)
+}
+
+/// Splits a triangle strip of `count` vertexes into strips of at most `max` vertexes. Pieces
+/// start on even vertexes, so their triangles keep the winding they have in the whole strip.
+fn strip_pieces(count: usize, max: usize) -> impl Iterator<Item = Range<usize>> {
+    pieces(0, count, max & !1, 2)
+}
+
+fn pieces(
+    first: usize,
+    count: usize,
+    max_len: usize,
+    overlap: usize,
+) -> impl Iterator<Item = Range<usize>> {
+    let piece = move |start: usize| start..(start + max_len).min(count);
+    std::iter::successors(Some(piece(first)), move |prev: &Range<usize>| {
+        if prev.end < count {
+            Some(piece(prev.end - overlap))
+        } else {
+            None
+        }
+    })
+}
+
+/// Face culling for the draws of `call_type`. Triangles keep the winding they were given,
+/// which a mirroring transform flips, so they are never culled.
+fn call_cull_face(call_type: &CallType) -> CullFace {
+    match call_type {
+        CallType::Triangles => CullFace::Nothing,
+        _ => CullFace::Back,
+    }
+}
+
+/// GL scissor rectangle, as x, y, width and height, covering `bounds` in a view of
+/// `screen_size` pixels, or the whole view without bounds
+fn scissor_rect(bounds: Option<Bounds>, screen_size: (f32, f32)) -> (i32, i32, i32, i32) {
+    let (width, height) = screen_size;
+    let (x0, y0, x1, y1) = match bounds {
+        // one pixel of slack keeps the feathered edge intact
+        Some(bounds) => (
+            (bounds.min.x - 1.0).floor().max(0.0),
+            (bounds.min.y - 1.0).floor().max(0.0),
+            (bounds.max.x + 1.0).ceil().min(width),
+            (bounds.max.y + 1.0).ceil().min(height),
+        ),
+        None => (0.0, 0.0, width, height),
+    };
+
+    // GL scissor origin is the bottom left corner
+    (
+        x0 as i32,
+        (height - y1) as i32,
+        (x1 - x0).max(0.0) as i32,
+        (y1 - y0).max(0.0) as i32,
+    )
+}
+
+/// How the shader reads a paint texture of `format`
+fn paint_tex_type(format: TextureFormat, flags: ImageFlags) -> Result<i32, NonaError> {
+    match format {
+        TextureFormat::RGBA8 if flags.contains(ImageFlags::PREMULTIPLIED) => Ok(0),
+        TextureFormat::RGBA8 => Ok(1),
+        // no alpha channel, so already the same as premultiplied
+        TextureFormat::RGB8 => Ok(0),
+        // coverage, e.g. the glyph atlas: only scales the premultiplied paint color
+        TextureFormat::Alpha => Ok(2),
+        format => Err(NonaError::Texture(format!(
+            "unsupported texture format {:?} for paint",
+            format
+        ))),
+    }
+}
+
+/// Fails unless the `width`x`height` region at (`x`, `y`) lies inside a framebuffer of `size`
+fn check_read_region(
+    x: usize,
+    y: usize,
+    width: usize,
+    height: usize,
+    size: (usize, usize),
+) -> Result<(), NonaError> {
+    if x + width > size.0 || y + height > size.1 {
+        return Err(NonaError::Image(format!(
+            "region {}x{} at ({}, {}) is outside of the {}x{} target",
+            width, height, x, y, size.0, size.1
+        )));
+    }
+    Ok(())
+}
+
+/// Clears to `clear_color` when the pass begins, or keeps what is there
+fn pass_action(clear_color: Option<Color>) -> PassAction {
+    match clear_color {
+        Some(color) => PassAction::clear_color(color.r, color.g, color.b, color.a),
+        None => PassAction::Nothing,
+    }
+}
+
+fn query_max_texture_size() -> usize {
+    let mut size: i32 = 0;
+    unsafe {
+        miniquad::gl::glGetIntegerv(miniquad::gl::GL_MAX_TEXTURE_SIZE, &mut size);
+    }
+    size.max(0) as usize
+}
+
 #[inline]
 fn premul_color(color: Color) -> Color {
     Color {
@@ -1290,3 +1990,297 @@ fn xform_to_4x4(xform: Transform) -> Mat4 {
         Vec4::new(0.0, 0.0, 0.0, 0.0),
     )
 }
+
+fn texture_wrap(repeat: bool) -> TextureWrap {
+    if repeat {
+        TextureWrap::Repeat
+    } else {
+        TextureWrap::Clamp
+    }
+}
+
+fn texture_filter(nearest: bool) -> FilterMode {
+    if nearest {
+        FilterMode::Nearest
+    } else {
+        FilterMode::Linear
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pass_action_clears_to_requested_color() {
+        match pass_action(Some(Color::rgb(0.0, 0.0, 0.0))) {
+            PassAction::Clear {
+                color: Some(color), ..
+            } => assert_eq!(color, (0.0, 0.0, 0.0, 1.0)),
+            _ => panic!("expected a clear to black"),
+        }
+        assert!(matches!(pass_action(None), PassAction::Nothing));
+    }
+
+    #[test]
+    fn full_chunk_indices_fit_the_preallocated_storage() {
+        // the most indices a single draw of one chunk can produce is a strip over all of it
+        for (max_vertices, max_indices) in [
+            (MAX_VERTICES, MAX_INDICES),
+            (MAX_VERTICES_U32, MAX_INDICES_U32),
+        ] {
+            let mut indices: Vec<u32> = Vec::with_capacity(max_vertices * 3);
+            Renderer::add_triangle_strip(&mut indices, 0, max_vertices);
+            assert!(indices.len() <= max_indices);
+            assert!(indices.len() <= indices.capacity());
+
+            indices.clear();
+            Renderer::add_triangle_fan(&mut indices, 0, max_vertices);
+            assert!(indices.len() <= max_indices);
+            assert!(indices.len() <= indices.capacity());
+        }
+        // 16-bit chunks are narrowed without losing offsets
+        assert!(MAX_VERTICES <= u16::MAX as usize + 1);
+    }
+
+    #[test]
+    fn u32_chunks_draw_over_65k_vertexes_at_once() {
+        let count = 100_000;
+        assert_eq!(fan_pieces(count, MAX_VERTICES_U32).count(), 1);
+        assert!(fan_pieces(count, MAX_VERTICES).count() > 1);
+
+        let mut indices: Vec<u32> = Vec::new();
+        Renderer::add_triangle_fan(&mut indices, 0, count);
+        assert_eq!(indices.len(), (count - 2) * 3);
+        assert_eq!(indices.iter().max(), Some(&(count as u32 - 1)));
+    }
+
+    #[test]
+    fn read_regions_must_fit_the_framebuffer() {
+        assert!(check_read_region(0, 0, 800, 600, (800, 600)).is_ok());
+        assert!(check_read_region(800, 600, 0, 0, (800, 600)).is_ok());
+        assert!(matches!(
+            check_read_region(790, 0, 11, 1, (800, 600)),
+            Err(NonaError::Image(_))
+        ));
+        assert!(matches!(
+            check_read_region(0, 600, 1, 1, (800, 600)),
+            Err(NonaError::Image(_))
+        ));
+    }
+
+    fn triangles_call(image: Option<usize>, triangle_offset: usize) -> Call {
+        let composite_operation: CompositeOperationState =
+            CompositeOperation::Basic(BasicCompositeOperation::SrcOver).into();
+        Call {
+            call_type: CallType::Triangles,
+            image,
+            path_offset: 0,
+            path_count: 0,
+            triangle_offset,
+            triangle_count: 6,
+            uniform_offset: 0,
+            blend_func: composite_operation.into(),
+            fill_rule: FillRule::NonZero,
+            vertex_base: 0,
+            scissor_bounds: None,
+        }
+    }
+
+    #[test]
+    fn only_adjacent_triangles_with_the_same_state_merge() {
+        let first = triangles_call(Some(1), 0);
+        assert!(first.continued_by(&triangles_call(Some(1), 6)));
+        // a gap, another texture or another blend keep their own call
+        assert!(!first.continued_by(&triangles_call(Some(1), 12)));
+        assert!(!first.continued_by(&triangles_call(Some(2), 6)));
+        let mut lighter = triangles_call(Some(1), 6);
+        let composite_operation: CompositeOperationState =
+            CompositeOperation::Basic(BasicCompositeOperation::Lighter).into();
+        lighter.blend_func = composite_operation.into();
+        assert!(!first.continued_by(&lighter));
+        let mut next_chunk = triangles_call(Some(1), 6);
+        next_chunk.vertex_base = MAX_VERTICES;
+        assert!(!first.continued_by(&next_chunk));
+    }
+
+    #[test]
+    fn unchanged_geometry_is_uploaded_once() {
+        let mut uploaded = UploadedVertexes(Vec::new());
+        let frame = [Vertex::new(1.0, 2.0, 0.5, 1.0); 3];
+        assert!(uploaded.replace(&frame));
+        assert!(!uploaded.replace(&frame));
+
+        let mut moved = frame;
+        moved[1].x = 5.0;
+        assert!(uploaded.replace(&moved));
+        assert!(uploaded.replace(&frame[..2]));
+        assert!(!uploaded.replace(&frame[..2]));
+    }
+
+    /// Triangles of the pieces of a 30k vertex path, as indices into the whole path
+    fn split_triangles(pieces: impl Iterator<Item = Range<usize>>, fan: bool) -> Vec<[usize; 3]> {
+        let mut triangles = Vec::new();
+        for range in pieces {
+            let source: Vec<usize> = if fan {
+                std::iter::once(0).chain(range).collect()
+            } else {
+                range.collect()
+            };
+            assert!(source.len() <= MAX_VERTICES);
+
+            let mut indices: Vec<u32> = Vec::new();
+            if fan {
+                Renderer::add_triangle_fan(&mut indices, 0, source.len());
+            } else {
+                Renderer::add_triangle_strip(&mut indices, 0, source.len());
+            }
+            triangles.extend(indices.chunks(3).map(|triangle| {
+                [
+                    source[triangle[0] as usize],
+                    source[triangle[1] as usize],
+                    source[triangle[2] as usize],
+                ]
+            }));
+        }
+        triangles
+    }
+
+    #[test]
+    fn oversized_fans_and_strips_split_into_the_same_triangles() {
+        let count = 30_000;
+
+        let fan = split_triangles(fan_pieces(count, MAX_VERTICES), true);
+        let whole_fan: Vec<_> = (1..count - 1).map(|i| [0, i, i + 1]).collect();
+        assert_eq!(fan, whole_fan);
+
+        let strip = split_triangles(strip_pieces(count, MAX_VERTICES), false);
+        let whole_strip: Vec<_> = (0..count - 2)
+            .map(|i| {
+                if i % 2 == 0 {
+                    [i, i + 1, i + 2]
+                } else {
+                    [i + 1, i, i + 2]
+                }
+            })
+            .collect();
+        assert_eq!(strip, whole_strip);
+
+        // paths that fit stay whole
+        assert_eq!(
+            fan_pieces(10, MAX_VERTICES).collect::<Vec<_>>(),
+            vec![1..10]
+        );
+        assert_eq!(
+            strip_pieces(10, MAX_VERTICES).collect::<Vec<_>>(),
+            vec![0..10]
+        );
+    }
+
+    #[test]
+    fn triangles_keep_their_vertex_order() {
+        let mut indices = Vec::new();
+        Renderer::add_triangles(&mut indices, 3, 6);
+        assert_eq!(indices, [3, 4, 5, 6, 7, 8]);
+    }
+
+    #[test]
+    fn strip_triangles_alternate_order_and_keep_winding() {
+        let mut indices = Vec::new();
+        Renderer::add_triangle_strip(&mut indices, 4, 6);
+        // parity counts from the start of the strip, not from vertex 0 of the buffer
+        assert_eq!(indices, [4, 5, 6, 6, 5, 7, 6, 7, 8, 8, 7, 9]);
+
+        // a zig-zag strip along x, like a stroke
+        let points: Vec<(f32, f32)> = (0..10)
+            .map(|i| ((i / 2) as f32, if i % 2 == 0 { 0.0 } else { 1.0 }))
+            .collect();
+        let mut indices = Vec::new();
+        Renderer::add_triangle_strip(&mut indices, 0, points.len());
+        for triangle in indices.chunks(3) {
+            let [a, b, c] = [0, 1, 2].map(|i| points[triangle[i] as usize]);
+            let area = (b.0 - a.0) * (c.1 - a.1) - (c.0 - a.0) * (b.1 - a.1);
+            assert!(area < 0.0, "{:?}", triangle);
+        }
+    }
+
+    #[test]
+    fn triangles_are_not_culled() {
+        // draw_triangles under a mirroring transform reaches the GPU clockwise
+        assert!(matches!(
+            call_cull_face(&CallType::Triangles),
+            CullFace::Nothing
+        ));
+        assert!(matches!(call_cull_face(&CallType::Fill), CullFace::Back));
+        assert!(matches!(call_cull_face(&CallType::Stroke), CullFace::Back));
+    }
+
+    #[test]
+    fn scissor_rect_covers_the_rotated_scissor() {
+        let no_scissor = Scissor {
+            xform: Transform::identity(),
+            extent: Extent::new(-1.0, -1.0),
+        };
+        assert!(scissor_bounds(&no_scissor).is_none());
+        assert_eq!(scissor_rect(None, (200.0, 100.0)), (0, 0, 200, 100));
+
+        // a 20x20 square centered on (50, 40), turned 45 degrees
+        let mut xform = Transform::rotate(std::f32::consts::FRAC_PI_4);
+        xform.0[4] = 50.0;
+        xform.0[5] = 40.0;
+        let scissor = Scissor {
+            xform,
+            extent: Extent::new(10.0, 10.0),
+        };
+        let bounds = scissor_bounds(&scissor).unwrap();
+        let half_diagonal = 10.0 * std::f32::consts::SQRT_2;
+        assert!((bounds.min.x - (50.0 - half_diagonal)).abs() < 1e-3);
+        assert!((bounds.max.y - (40.0 + half_diagonal)).abs() < 1e-3);
+
+        // with a pixel of slack, flipped to GL's bottom left origin
+        let (x, y, width, height) = scissor_rect(Some(bounds), (200.0, 100.0));
+        assert_eq!((x, width, height), (34, 32, 32));
+        assert_eq!(y, 100 - 56);
+
+        // clamped to the screen
+        assert_eq!(scissor_rect(Some(bounds), (60.0, 50.0)), (34, 0, 26, 26));
+    }
+
+    #[test]
+    fn paint_textures_map_to_shader_texture_types() {
+        let premultiplied = ImageFlags::PREMULTIPLIED;
+        let plain = ImageFlags::empty();
+        assert_eq!(paint_tex_type(TextureFormat::RGBA8, plain).unwrap(), 1);
+        assert_eq!(
+            paint_tex_type(TextureFormat::RGBA8, premultiplied).unwrap(),
+            0
+        );
+        assert_eq!(paint_tex_type(TextureFormat::RGB8, plain).unwrap(), 0);
+        assert_eq!(paint_tex_type(TextureFormat::Alpha, plain).unwrap(), 2);
+        assert!(matches!(
+            paint_tex_type(TextureFormat::Depth, plain),
+            Err(NonaError::Texture(_))
+        ));
+    }
+
+    /// One renderer drawing two frames, each through its own context borrow. A `MiniContext`
+    /// can only be made inside a window with a live GL context, so this is type-checked by the
+    /// test build rather than run.
+    #[allow(dead_code)]
+    fn draw_two_frames(
+        renderer: &mut Renderer,
+        first: &mut MiniContext,
+        second: &mut MiniContext,
+    ) -> Result<(), NonaError> {
+        let mut nona = nona::Context::create(&mut renderer.with_context(first))?;
+        for ctx in [first, second] {
+            let mut frame = renderer.with_context(ctx);
+            nona.begin_frame(&mut frame, Some(nona::Color::rgb(0.0, 0.0, 0.0)))?;
+            nona.begin_path();
+            nona.rect((10.0, 10.0, 20.0, 20.0));
+            nona.fill(&mut frame)?;
+            nona.end_frame(&mut frame)?;
+        }
+        Ok(())
+    }
+}
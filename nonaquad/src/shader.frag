@@ -18,6 +18,7 @@ uniform float strokeThr;
 // 0: RGBA, premultiplied
 // 1: RGBA, not premultiplied
 // 2: Alpha texture, alpha value is stored in .a (miniquad always stores in .a for alpha textures)
+// 3: no texture, textured tris use the paint color only
 uniform int texType;
 uniform int type;
 
@@ -69,11 +70,16 @@ void main(void) {
         result = vec4(1, 1, 1, 1);
     } else if (type == 3) {
         // Textured tris
-        vec4 color = texture2D(tex, ftcoord);
+        vec4 color = texType == 3 ? vec4(1.0) : texture2D(tex, ftcoord);
         if (texType == 1) color = vec4(color.xyz * color.w, color.w); // premultiply non-premultiplied texture
         if (texType == 2) color = vec4(color.a); // alpha texture
         color *= scissor;
         result = color * innerCol;
+    } else if (type == 4) {
+        // Stroke gradient along path, the arc length fraction is stored in ftcoord.y - 1
+        vec4 color = mix(innerCol, outerCol, clamp(ftcoord.y - 1.0, 0.0, 1.0));
+        color *= strokeAlpha * scissor;
+        result = color;
     }
 
     gl_FragColor = result;
@@ -6,7 +6,7 @@ uniform vec2 viewSize;
 // layout(location = 1) in vec2 tcoord;
 attribute vec2 vertex;
 attribute vec2 tcoord;
-varying lowp vec2 ftcoord;
+varying vec2 ftcoord;
 varying highp vec2 fpos;
 
 void main(void) {